@@ -1,6 +1,6 @@
 import { createContext, useContext, useEffect, useRef, type ReactNode } from "react";
 import { useQueryClient } from "@tanstack/react-query";
-import { isTauri } from "~/client/lib/tauri";
+import { openServerEvents, type ServerEventSource } from "~/client/lib/tauri";
 
 type ServerEventType =
 	| "connected"
@@ -56,11 +56,11 @@ const ServerEventsContext = createContext<ServerEventsContextValue | null>(null)
 
 export function ServerEventsProvider({ children }: { children: ReactNode }) {
 	const queryClient = useQueryClient();
-	const eventSourceRef = useRef<EventSource | null>(null);
+	const eventSourceRef = useRef<ServerEventSource | null>(null);
 	const handlersRef = useRef<Map<ServerEventType, Set<EventHandler>>>(new Map());
 
 	useEffect(() => {
-		const eventSource = new EventSource("/api/v1/events");
+		const eventSource = openServerEvents();
 		eventSourceRef.current = eventSource;
 
 		eventSource.addEventListener("connected", () => {
@@ -69,23 +69,10 @@ export function ServerEventsProvider({ children }: { children: ReactNode }) {
 
 		eventSource.addEventListener("heartbeat", () => {});
 
-		eventSource.addEventListener("backup:started", async (e) => {
+		eventSource.addEventListener("backup:started", (e) => {
 			const data = JSON.parse(e.data) as BackupEvent;
 			console.info("[SSE] Backup started:", data);
 
-			// Send desktop notification for backup start
-			if (isTauri()) {
-				try {
-					const { invoke } = await import("@tauri-apps/api/core");
-					const title = "⏳ Iniciando copia de seguridad";
-					const body = `${data.volumeName} → ${data.repositoryName}`;
-
-					await invoke("send_notification", { title, body });
-				} catch (error) {
-					console.error("Failed to send notification:", error);
-				}
-			}
-
 			handlersRef.current.get("backup:started")?.forEach((handler) => {
 				handler(data);
 			});
@@ -99,31 +86,13 @@ export function ServerEventsProvider({ children }: { children: ReactNode }) {
 			});
 		});
 
-		eventSource.addEventListener("backup:completed", async (e) => {
+		eventSource.addEventListener("backup:completed", (e) => {
 			const data = JSON.parse(e.data) as BackupEvent;
 			console.info("[SSE] Backup completed:", data);
 
 			void queryClient.invalidateQueries();
 			void queryClient.refetchQueries();
 
-			// Send desktop notification for backup completion
-			if (isTauri()) {
-				try {
-					const { invoke } = await import("@tauri-apps/api/core");
-					const title = data.status === "success"
-						? "✓ Copia de seguridad completada"
-						: data.status === "error"
-						? "✗ Error en copia de seguridad"
-						: "⚠ Copia de seguridad completada con advertencias";
-
-					const body = `${data.volumeName} → ${data.repositoryName}`;
-
-					await invoke("send_notification", { title, body });
-				} catch (error) {
-					console.error("Failed to send notification:", error);
-				}
-			}
-
 			handlersRef.current.get("backup:completed")?.forEach((handler) => {
 				handler(data);
 			});
@@ -169,53 +138,22 @@ export function ServerEventsProvider({ children }: { children: ReactNode }) {
 			});
 		});
 
-		eventSource.addEventListener("mirror:started", async (e) => {
+		eventSource.addEventListener("mirror:started", (e) => {
 			const data = JSON.parse(e.data) as MirrorEvent;
 			console.info("[SSE] Mirror copy started:", data);
 
-			// Send desktop notification for mirror start
-			if (isTauri()) {
-				try {
-					const { invoke } = await import("@tauri-apps/api/core");
-					const title = "⏳ Iniciando copia espejo";
-					const body = data.repositoryName;
-
-					await invoke("send_notification", { title, body });
-				} catch (error) {
-					console.error("Failed to send notification:", error);
-				}
-			}
-
 			handlersRef.current.get("mirror:started")?.forEach((handler) => {
 				handler(data);
 			});
 		});
 
-		eventSource.addEventListener("mirror:completed", async (e) => {
+		eventSource.addEventListener("mirror:completed", (e) => {
 			const data = JSON.parse(e.data) as MirrorEvent;
 			console.info("[SSE] Mirror copy completed:", data);
 
 			// Invalidate queries to refresh mirror status in the UI
 			void queryClient.invalidateQueries();
 
-			// Send desktop notification for mirror completion
-			if (isTauri()) {
-				try {
-					const { invoke } = await import("@tauri-apps/api/core");
-					const title = data.status === "success"
-						? "✓ Copia espejo completada"
-						: "✗ Error en copia espejo";
-
-					const body = data.status === "error" && data.error
-						? `${data.repositoryName}: ${data.error}`
-						: data.repositoryName;
-
-					await invoke("send_notification", { title, body });
-				} catch (error) {
-					console.error("Failed to send notification:", error);
-				}
-			}
-
 			handlersRef.current.get("mirror:completed")?.forEach((handler) => {
 				handler(data);
 			});
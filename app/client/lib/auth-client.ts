@@ -2,6 +2,7 @@ import { createAuthClient } from "better-auth/react";
 import { twoFactorClient, usernameClient } from "better-auth/client/plugins";
 import { inferAdditionalFields } from "better-auth/client/plugins";
 import type { auth } from "~/lib/auth";
+import { getBackendHeaders } from "./tauri";
 
 // Auth client with dynamic baseURL support
 // In Tauri, the backend URL will be set after initialization
@@ -19,8 +20,14 @@ export const authClient = createAuthClient({
 				? urlString
 				: `${backendUrl}${urlString.startsWith('/') ? '' : '/'}${urlString}`;
 
+			// The sidecar rejects requests without the shell's channel token
+			const headers = new Headers(options?.headers);
+			for (const [name, value] of Object.entries(getBackendHeaders())) {
+				headers.set(name, value);
+			}
+
 			console.info("[Auth Client] Fetching:", fullUrl);
-			return fetch(fullUrl, options);
+			return fetch(fullUrl, { ...options, headers });
 		},
 	},
 	plugins: [inferAdditionalFields<typeof auth>(), usernameClient(), twoFactorClient()],
@@ -50,6 +50,23 @@ export async function isBackendReady(): Promise<boolean> {
 	}
 }
 
+/**
+ * Take the route the app was asked to open on at launch (e.g. `open backups` on the command line)
+ * Returns null when there is none or it was already taken
+ */
+export async function takePendingRoute(): Promise<string | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		return await invoke<string | null>("take_pending_route");
+	} catch {
+		return null;
+	}
+}
+
 /**
  * Get the app data directory
  */
@@ -84,24 +101,689 @@ export async function openDataDir(): Promise<void> {
 
 /**
  * Wait for the backend to be ready with a timeout
+ * Resolves on the `backend://ready` event pushed by the Tauri shell, or immediately
+ * if the backend was already up before we subscribed
  */
 export async function waitForBackend(timeoutMs = 60000): Promise<string> {
 	if (!isTauri()) {
 		return "/"; // In web mode, use relative URL
 	}
 
-	const startTime = Date.now();
+	const { listen } = await import("@tauri-apps/api/event");
+
+	return new Promise<string>((resolve, reject) => {
+		const unlisteners: Array<() => void> = [];
+		let settled = false;
+
+		const settle = (finish: () => void) => {
+			if (settled) return;
+			settled = true;
+			clearTimeout(timer);
+			for (const unlisten of unlisteners) unlisten();
+			finish();
+		};
+
+		const timer = setTimeout(() => {
+			settle(() => reject(new Error("Backend failed to start within the timeout period")));
+		}, timeoutMs);
+
+		Promise.all([
+			listen<{ url: string }>("backend://ready", (event) => settle(() => resolve(event.payload.url))),
+			listen<{ error: string }>("backend://failed", (event) => settle(() => reject(new Error(event.payload.error)))),
+		])
+			.then(async (subscriptions) => {
+				if (settled) {
+					for (const unlisten of subscriptions) unlisten();
+					return;
+				}
+				unlisteners.push(...subscriptions);
+
+				// Late subscriber: the shell may have finished starting before we listened
+				const status = await getBackendStatus();
+				if (status?.state === "healthy" && status.url) {
+					const url = status.url;
+					settle(() => resolve(url));
+				} else if (status?.state === "crashed") {
+					settle(() => reject(new Error(status.exit)));
+				}
+			})
+			.catch((error) => settle(() => reject(error)));
+	});
+}
 
-	while (Date.now() - startTime < timeoutMs) {
-		const ready = await isBackendReady();
-		if (ready) {
-			const url = await getBackendUrl();
-			if (url) {
-				return url;
+export type BackendState =
+	| { state: "starting" }
+	| { state: "healthy" }
+	| { state: "degraded" }
+	| { state: "crashed"; exit: string; code: number | null }
+	| { state: "stopping" }
+	| { state: "stopped" };
+
+export type BackendStatus = BackendState & { url: string | null };
+
+/**
+ * Get the current backend lifecycle state from Tauri
+ */
+export async function getBackendStatus(): Promise<BackendStatus | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		return await invoke<BackendStatus>("get_backend_status");
+	} catch {
+		return null;
+	}
+}
+
+/**
+ * Subscribe to backend lifecycle transitions pushed by the Tauri shell
+ * Returns an unsubscribe function
+ */
+export async function onBackendStatus(handler: (status: BackendStatus) => void): Promise<() => void> {
+	if (!isTauri()) {
+		return () => {};
+	}
+
+	const { listen } = await import("@tauri-apps/api/event");
+	return await listen<BackendStatus>("backend://status", (event) => handler(event.payload));
+}
+
+export interface BackendConnection {
+	url: string;
+	channelToken: string;
+	transport: "tcp" | "localSocket";
+}
+
+let backendConnection: BackendConnection | null = null;
+
+/**
+ * Fetch how to reach the backend, including the per-launch channel token the
+ * sidecar requires on every request. The token is only ever handed out by the shell.
+ */
+export async function getBackendConnection(): Promise<BackendConnection | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		backendConnection = await invoke<BackendConnection>("get_backend_connection");
+		return backendConnection;
+	} catch {
+		return null;
+	}
+}
+
+/**
+ * Headers to add to every backend request made outside the generated API client
+ */
+export function getBackendHeaders(): Record<string, string> {
+	return backendConnection ? { "X-Channel-Token": backendConnection.channelToken } : {};
+}
+
+export interface ServerEventSource {
+	addEventListener(type: string, listener: (event: MessageEvent<string>) => void): void;
+	onerror: ((error: Event) => void) | null;
+	close(): void;
+}
+
+/**
+ * Subscribe to the backend's server-sent events
+ * EventSource cannot set headers, so the channel token goes in the query string.
+ * Over a local socket the webview cannot connect at all and the shell relays the events instead.
+ */
+export function openServerEvents(): ServerEventSource {
+	if (!backendConnection) {
+		return new EventSource("/api/v1/events");
+	}
+
+	if (backendConnection.transport === "localSocket") {
+		return relayServerEvents();
+	}
+
+	const token = encodeURIComponent(backendConnection.channelToken);
+	const source = new EventSource(`${backendConnection.url}/api/v1/events?channel_token=${token}`);
+
+	if (import.meta.env.DEV) {
+		forwardServerEvents(source);
+	}
+
+	return source;
+}
+
+/** Events the shell acts on: notifications and the tray's running backups */
+const SHELL_EVENTS = [
+	"connected",
+	"backup:started",
+	"backup:progress",
+	"backup:completed",
+	"mirror:started",
+	"mirror:completed",
+	"volume:status_changed",
+];
+
+/**
+ * Debug builds run against the Vite dev server, whose events the shell cannot
+ * subscribe to without the launch tokens, so the webview hands them over
+ */
+function forwardServerEvents(source: ServerEventSource) {
+	for (const event of SHELL_EVENTS) {
+		source.addEventListener(event, ({ data }) => {
+			import("@tauri-apps/api/core")
+				.then(({ invoke }) => invoke("handle_backend_event", { event, data }))
+				.catch((error) => console.error("Failed to forward backend event:", error));
+		});
+	}
+}
+
+function relayServerEvents(): ServerEventSource {
+	const listeners = new Map<string, Set<(event: MessageEvent<string>) => void>>();
+	let unlisten: (() => void) | null = null;
+	let closed = false;
+
+	const source: ServerEventSource = {
+		addEventListener(type, listener) {
+			if (!listeners.has(type)) {
+				listeners.set(type, new Set());
+			}
+			listeners.get(type)?.add(listener);
+		},
+		onerror: null,
+		close() {
+			closed = true;
+			unlisten?.();
+		},
+	};
+
+	import("@tauri-apps/api/event")
+		.then(({ listen }) =>
+			listen<{ event: string; data: string }>("backend://event", ({ payload }) => {
+				const event = new MessageEvent(payload.event, { data: payload.data });
+				listeners.get(payload.event)?.forEach((listener) => listener(event));
+			}),
+		)
+		.then((stop) => {
+			if (closed) {
+				stop();
+			} else {
+				unlisten = stop;
 			}
-		}
-		await new Promise((resolve) => setTimeout(resolve, 500));
+		})
+		.catch((error) => source.onerror?.(new ErrorEvent("error", { error })));
+
+	return source;
+}
+
+export interface SidecarStatus {
+	restartCount: number;
+	consecutiveCrashes: number;
+	lastExitStatus: string | null;
+	lastExitCode: number | null;
+	lastExitAt: number | null;
+	gaveUp: boolean;
+}
+
+/**
+ * Get the sidecar supervisor status (restart count and last exit)
+ */
+export async function getSidecarStatus(): Promise<SidecarStatus | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		return await invoke<SidecarStatus>("get_sidecar_status");
+	} catch {
+		return null;
+	}
+}
+
+export type PreflightStatus = "ok" | "warning" | "error";
+
+export interface PreflightCheck {
+	/** `sidecar`, a tool name, `migrations` or `storage:<location>` */
+	id: string;
+	status: PreflightStatus;
+	message: string;
+	fix: string | null;
+}
+
+export interface PreflightReport {
+	checkedAt: number;
+	checks: PreflightCheck[];
+}
+
+/**
+ * Get the checks run before the last backend start (binaries, migrations and storage)
+ */
+export async function getPreflightReport(): Promise<PreflightReport | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		return await invoke<PreflightReport | null>("get_preflight_report");
+	} catch {
+		return null;
+	}
+}
+
+export interface NotificationSettings {
+	backupStarted: boolean;
+	backupSuccess: boolean;
+	backupWarning: boolean;
+	backupError: boolean;
+	mirrorStarted: boolean;
+	mirrorSuccess: boolean;
+	mirrorError: boolean;
+	volumeStatus: boolean;
+}
+
+/**
+ * Get which backend events raise native notifications from the Tauri shell
+ */
+export async function getNotificationSettings(): Promise<NotificationSettings | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		return await invoke<NotificationSettings>("get_notification_settings");
+	} catch {
+		return null;
+	}
+}
+
+/**
+ * Persist which backend events raise native notifications
+ */
+export async function setNotificationSettings(notifications: NotificationSettings): Promise<void> {
+	if (!isTauri()) {
+		return;
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	await invoke("set_notification_settings", { notifications });
+}
+
+export type StorageLocation = "data" | "repositories" | "volumes" | "cache" | "password";
+
+export interface StorageSettings {
+	dataDir: string | null;
+	repositoriesDir: string | null;
+	volumesDir: string | null;
+	cacheDir: string | null;
+	passwordDir: string | null;
+}
+
+export interface StoragePaths {
+	dataDir: string;
+	database: string;
+	repositoriesDir: string;
+	volumesDir: string;
+	cacheDir: string;
+	passwordFile: string;
+}
+
+export type PasswordStore = "keyring" | "file";
+
+export interface StorageInfo {
+	settings: StorageSettings;
+	paths: StoragePaths;
+	dataDirPinned: boolean;
+	/** Where the restic password is kept, null until the backend has started */
+	passwordStore: PasswordStore | null;
+}
+
+/**
+ * Get where the backend keeps its database, repositories, volumes, cache and password file
+ */
+export async function getStorageSettings(): Promise<StorageInfo | null> {
+	if (!isTauri()) {
+		return null;
 	}
 
-	throw new Error("Backend failed to start within the timeout period");
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		return await invoke<StorageInfo>("get_storage_settings");
+	} catch {
+		return null;
+	}
+}
+
+/**
+ * Move a storage location (or reset it with `null`), restarting the backend.
+ * With `migrate`, the existing data is moved to the new location.
+ */
+export async function setStorageLocation(
+	location: StorageLocation,
+	dir: string | null,
+	migrate: boolean,
+): Promise<StorageInfo> {
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<StorageInfo>("set_storage_location", { location, dir, migrate });
+}
+
+/**
+ * Let the user choose a directory with the native dialog
+ */
+export async function pickDirectory(title: string): Promise<string | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<string | null>("pick_directory", { title });
+}
+
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
+
+export interface LoggingSettings {
+	level: LogLevel;
+	/** Raising it beyond the level the backend was started with takes effect once it restarts */
+	sidecarLevel: LogLevel;
+	maxFileSizeMb: number;
+	rotation: "never" | "daily" | "weekly";
+	keepFiles: number;
+	/** `null` keeps rotated files by count only */
+	keepDays: number | null;
+}
+
+export interface SnapshotSettings {
+	enabled: boolean;
+	/** A snapshot is taken once the newest one is this old */
+	intervalHours: number;
+	keep: number;
+}
+
+export type UpgradeOutcome = "pending" | "succeeded" | "rolledBack";
+
+export interface UpgradeRecord {
+	/** `null` when the previous version predates the upgrade history */
+	from: string | null;
+	to: string;
+	/** Unix timestamp (seconds) */
+	startedAt: number;
+	finishedAt: number | null;
+	/** Snapshot of the database taken before the upgrade */
+	snapshot: string | null;
+	/** `pending` until the backend of the new version starts */
+	outcome: UpgradeOutcome;
+}
+
+export interface UpgradeHistory {
+	lastRunVersion: string | null;
+	/** Oldest first */
+	history: UpgradeRecord[];
+}
+
+export interface ShellSettings {
+	version: number;
+	sidecar: {
+		shutdownGraceSecs: number;
+		startupTimeoutSecs: number;
+		transport: "tcp" | "localSocket";
+		port: number | null;
+	};
+	health: {
+		intervalSecs: number;
+		timeoutSecs: number;
+		failureThreshold: number;
+		notifyAfterFailures: number;
+		restartAfterFailures: number | null;
+	};
+	notifications: NotificationSettings;
+	storage: StorageSettings;
+	logging: LoggingSettings;
+	window: { closeToTray: boolean };
+	tray: { leftClick: "showWindow" | "showMenu" };
+	snapshots: SnapshotSettings;
+	/** Written by the shell only */
+	upgrades: UpgradeHistory;
+}
+
+type SettingsPatch = { [K in keyof ShellSettings]?: Partial<ShellSettings[K]> };
+
+/**
+ * Get the desktop shell settings
+ */
+export async function getSettings(): Promise<ShellSettings | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	try {
+		const { invoke } = await import("@tauri-apps/api/core");
+		return await invoke<ShellSettings>("get_settings");
+	} catch {
+		return null;
+	}
+}
+
+/**
+ * Change some of the desktop shell settings; rejects with the reason when they are invalid.
+ * Sidecar settings take effect the next time the backend starts.
+ */
+export async function updateSettings(patch: SettingsPatch): Promise<ShellSettings> {
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<ShellSettings>("update_settings", { patch });
+}
+
+/**
+ * Subscribe to changes of the desktop shell settings
+ * Returns an unsubscribe function
+ */
+export async function onSettingsChanged(handler: (settings: ShellSettings) => void): Promise<() => void> {
+	if (!isTauri()) {
+		return () => {};
+	}
+
+	const { listen } = await import("@tauri-apps/api/event");
+	return await listen<ShellSettings>("settings://changed", (event) => handler(event.payload));
+}
+
+export type LogSource = "shell" | "sidecar";
+
+export interface LogFile {
+	name: string;
+	source: LogSource;
+	size: number;
+	modifiedMs: number | null;
+	/** Being written to, as opposed to a rotated copy */
+	current: boolean;
+}
+
+export interface LogEntry {
+	/** Line of the file the entry starts on, from 1 */
+	line: number;
+	source: LogSource;
+	/** Local time as `YYYY-MM-DD HH:MM:SS` */
+	timestamp: string | null;
+	level: LogLevel | null;
+	target: string | null;
+	message: string;
+}
+
+export interface LogFilter {
+	/** Least severe level to include */
+	level?: LogLevel;
+	source?: LogSource;
+	/** Local times as `YYYY-MM-DD HH:MM:SS` (or `YYYY-MM-DDTHH:MM`), or a prefix such as a date; inclusive */
+	since?: string;
+	until?: string;
+	/** Text the message must contain, ignoring case */
+	text?: string;
+}
+
+export interface LogRange {
+	/** Last matching entries before this line, for scrolling back */
+	beforeLine?: number;
+	/** First matching entries after this line */
+	afterLine?: number;
+	limit: number;
+}
+
+export interface LogPage {
+	entries: LogEntry[];
+	/** More matching entries exist in the direction read */
+	hasMore: boolean;
+	totalLines: number;
+}
+
+/**
+ * List the shell and backend log files, current ones first
+ */
+export async function listLogFiles(): Promise<LogFile[]> {
+	if (!isTauri()) {
+		return [];
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<LogFile[]>("list_log_files");
+}
+
+/**
+ * Read the entries of a log file that match a filter. Without a line bound, returns the last ones.
+ */
+export async function readLog(file: string, filter: LogFilter, range: LogRange): Promise<LogPage> {
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<LogPage>("read_log", { file, filter, range });
+}
+
+/**
+ * Stream entries appended to the current log files, replacing any previous watch.
+ * Returns a function that stops the stream.
+ */
+export async function watchLogs(
+	filter: LogFilter,
+	handler: (file: string, entries: LogEntry[]) => void,
+): Promise<() => void> {
+	if (!isTauri()) {
+		return () => {};
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	const { listen } = await import("@tauri-apps/api/event");
+	const unlisten = await listen<{ file: string; entries: LogEntry[] }>("logs://entries", (event) =>
+		handler(event.payload.file, event.payload.entries),
+	);
+	await invoke("watch_logs", { filter });
+
+	return () => {
+		unlisten();
+		void invoke("unwatch_logs");
+	};
+}
+
+/**
+ * Ask where to save a support bundle (logs, paths, versions and recent health, with
+ * secrets redacted) and write it. Resolves to its path, or null when cancelled.
+ */
+export async function createSupportBundle(): Promise<string | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<string | null>("create_support_bundle");
+}
+
+/** Minimum length of the passphrase of an app state archive */
+export const MIN_STATE_PASSPHRASE_LENGTH = 8;
+
+export type RestoreSummary = {
+	appVersion: string;
+	/** Unix timestamp (seconds) the archive was made at */
+	createdAt: number;
+	/** Archive of the replaced state, in the data directory */
+	previousState: string | null;
+};
+
+/**
+ * Ask where to save an archive of the database, the restic password and the
+ * settings, encrypted with `passphrase`, and write it. The backend is restarted.
+ * Resolves to its path, or null when cancelled.
+ */
+export async function exportAppState(passphrase: string): Promise<string | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<string | null>("export_app_state", { passphrase });
+}
+
+/**
+ * Ask for an archive written by `exportAppState` and replace the app state with it,
+ * restarting the backend. Resolves to null when cancelled.
+ */
+export async function restoreAppState(passphrase: string): Promise<RestoreSummary | null> {
+	if (!isTauri()) {
+		return null;
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<RestoreSummary | null>("restore_app_state", { passphrase });
+}
+
+export type SnapshotReason = "scheduled" | "manual" | "beforeRollback" | "beforeUpgrade";
+
+export interface SnapshotInfo {
+	id: string;
+	reason: SnapshotReason;
+	appVersion: string;
+	/** Unix timestamp (seconds) */
+	createdAt: number;
+	sizeBytes: number;
+}
+
+/**
+ * Local snapshots of the database, restic password and settings, newest first
+ */
+export async function listSnapshots(): Promise<SnapshotInfo[]> {
+	if (!isTauri()) {
+		return [];
+	}
+
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<SnapshotInfo[]>("list_snapshots");
+}
+
+/**
+ * Take a snapshot now. Resolves to null when there is no database yet.
+ */
+export async function takeSnapshot(): Promise<SnapshotInfo | null> {
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<SnapshotInfo | null>("take_snapshot");
+}
+
+/**
+ * Roll the database back to a snapshot, restarting the backend. The replaced state
+ * is snapshotted first.
+ */
+export async function rollbackSnapshot(id: string): Promise<SnapshotInfo> {
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<SnapshotInfo>("rollback_snapshot", { id });
+}
+
+/**
+ * The upgrade whose backend has not started yet, if a snapshot was taken before it
+ */
+export function pendingUpgrade(settings: ShellSettings | null): UpgradeRecord | null {
+	const last = settings?.upgrades.history.at(-1);
+	return last?.outcome === "pending" && last.snapshot ? last : null;
+}
+
+/**
+ * Roll the database back to the snapshot taken before the pending upgrade and start
+ * the backend again, which retries the upgrade from the restored database
+ */
+export async function rollbackUpgrade(): Promise<UpgradeRecord> {
+	const { invoke } = await import("@tauri-apps/api/core");
+	return await invoke<UpgradeRecord>("rollback_upgrade");
 }
@@ -94,8 +94,9 @@ export const CreateRepositoryForm = ({
 			isExistingRepository: form.getValues().isExistingRepository,
 			customPassword: form.getValues().customPassword,
 			...defaultValuesForType[watchedBackend as keyof typeof defaultValuesForType],
+			...(initialValues?.backend === watchedBackend ? initialValues : {}),
 		});
-	}, [watchedBackend, form]);
+	}, [watchedBackend, form, initialValues]);
 
 	return (
 		<Form {...form}>
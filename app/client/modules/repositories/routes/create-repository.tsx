@@ -1,7 +1,7 @@
 import { useMutation } from "@tanstack/react-query";
 import { Database, Plus } from "lucide-react";
-import { useId } from "react";
-import { useNavigate } from "react-router";
+import { useId, useMemo } from "react";
+import { useNavigate, useSearchParams } from "react-router";
 import { toast } from "sonner";
 import { createRepositoryMutation } from "~/client/api-client/@tanstack/react-query.gen";
 import {
@@ -31,6 +31,14 @@ export function meta(_: Route.MetaArgs) {
 export default function CreateRepository() {
 	const navigate = useNavigate();
 	const formId = useId();
+	const [searchParams] = useSearchParams();
+
+	// Prefilled by `c3i-backup-one://repositories/new?url=...` links, which point at a REST server
+	const initialValues = useMemo<Partial<RepositoryFormValues> | undefined>(() => {
+		const url = searchParams.get("url");
+		if (!url) return undefined;
+		return { backend: "rest", url, name: searchParams.get("name") ?? undefined, compressionMode: "auto" };
+	}, [searchParams]);
 
 	const createRepository = useMutation({
 		...createRepositoryMutation(),
@@ -73,6 +81,7 @@ export default function CreateRepository() {
 					)}
 					<CreateRepositoryForm
 						mode="create"
+						initialValues={initialValues}
 						formId={formId}
 						onSubmit={handleSubmit}
 						loading={createRepository.isPending}
@@ -0,0 +1,252 @@
+import { useMutation, useQuery, useQueryClient } from "@tanstack/react-query";
+import { Monitor } from "lucide-react";
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { CardContent, CardDescription, CardTitle } from "~/client/components/ui/card";
+import { Label } from "~/client/components/ui/label";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "~/client/components/ui/select";
+import { Switch } from "~/client/components/ui/switch";
+import {
+	getSettings,
+	isTauri,
+	type LoggingSettings,
+	type LogLevel,
+	onSettingsChanged,
+	type ShellSettings,
+	updateSettings,
+} from "~/client/lib/tauri";
+
+const FILE_SIZES_MB = [5, 10, 50, 100];
+const KEEP_FILES = [3, 7, 14, 30];
+const KEEP_DAYS = [7, 30, 90, 365];
+
+/** The preset options, plus the current value when it was set to something else in the settings file */
+export function withCurrent(options: number[], current: number | null) {
+	return current === null || options.includes(current) ? options : [...options, current].sort((a, b) => a - b);
+}
+
+function LevelSelect({
+	value,
+	onChange,
+	disabled,
+}: {
+	value: LogLevel;
+	onChange: (level: LogLevel) => void;
+	disabled: boolean;
+}) {
+	return (
+		<Select value={value} onValueChange={(level) => onChange(level as LogLevel)} disabled={disabled}>
+			<SelectTrigger className="w-44">
+				<SelectValue />
+			</SelectTrigger>
+			<SelectContent>
+				<SelectItem value="error">Errores</SelectItem>
+				<SelectItem value="warn">Advertencias</SelectItem>
+				<SelectItem value="info">Información</SelectItem>
+				<SelectItem value="debug">Depuración</SelectItem>
+				<SelectItem value="trace">Traza</SelectItem>
+			</SelectContent>
+		</Select>
+	);
+}
+
+export function DesktopSection() {
+	const [isTauriApp, setIsTauriApp] = useState(false);
+	const queryClient = useQueryClient();
+
+	useEffect(() => {
+		setIsTauriApp(isTauri());
+	}, []);
+
+	const { data: settings } = useQuery({
+		queryKey: ["shell-settings"],
+		queryFn: getSettings,
+		enabled: isTauriApp,
+	});
+
+	// Other windows, the tray and the storage section change settings too
+	useEffect(() => {
+		if (!isTauriApp) return;
+		const unlisten = onSettingsChanged((next) => queryClient.setQueryData(["shell-settings"], next));
+		return () => {
+			void unlisten.then((stop) => stop());
+		};
+	}, [isTauriApp, queryClient]);
+
+	const update = useMutation({
+		mutationFn: updateSettings,
+		onSuccess: (next) => queryClient.setQueryData<ShellSettings>(["shell-settings"], next),
+		onError: (error) => {
+			toast.error("Error al guardar la configuración", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+	});
+
+	if (!isTauriApp || !settings) {
+		return null;
+	}
+
+	return (
+		<>
+			<div className="border-t border-border/50 bg-card-header p-6">
+				<CardTitle className="flex items-center gap-2">
+					<Monitor className="size-5" />
+					Aplicación de escritorio
+				</CardTitle>
+				<CardDescription className="mt-1.5">Comportamiento de la ventana, la bandeja del sistema y los registros</CardDescription>
+			</div>
+			<CardContent className="p-6 space-y-6">
+				<div className="flex items-center justify-between max-w-md">
+					<div className="space-y-0.5">
+						<Label htmlFor="close-to-tray-toggle" className="text-base cursor-pointer">
+							Cerrar a la bandeja
+						</Label>
+						<p className="text-sm text-muted-foreground">
+							Al cerrar la ventana la aplicación sigue en la bandeja; si se desactiva, se cierra por completo
+						</p>
+					</div>
+					<Switch
+						id="close-to-tray-toggle"
+						checked={settings.window.closeToTray}
+						onCheckedChange={(closeToTray) => update.mutate({ window: { closeToTray } })}
+						disabled={update.isPending}
+					/>
+				</div>
+
+				<div className="flex items-center justify-between max-w-md gap-4">
+					<div className="space-y-0.5">
+						<Label className="text-base">Clic en el icono de la bandeja</Label>
+						<p className="text-sm text-muted-foreground">Qué ocurre al hacer clic con el botón izquierdo</p>
+					</div>
+					<Select
+						value={settings.tray.leftClick}
+						onValueChange={(leftClick) =>
+							update.mutate({ tray: { leftClick: leftClick as ShellSettings["tray"]["leftClick"] } })
+						}
+						disabled={update.isPending}
+					>
+						<SelectTrigger className="w-44">
+							<SelectValue />
+						</SelectTrigger>
+						<SelectContent>
+							<SelectItem value="showWindow">Mostrar la ventana</SelectItem>
+							<SelectItem value="showMenu">Abrir el menú</SelectItem>
+						</SelectContent>
+					</Select>
+				</div>
+
+				<div className="flex items-center justify-between max-w-md gap-4">
+					<div className="space-y-0.5">
+						<Label className="text-base">Nivel de registro</Label>
+						<p className="text-sm text-muted-foreground">Detalle de los registros de la aplicación</p>
+					</div>
+					<LevelSelect
+						value={settings.logging.level}
+						onChange={(level) => update.mutate({ logging: { level } })}
+						disabled={update.isPending}
+					/>
+				</div>
+
+				<div className="flex items-center justify-between max-w-md gap-4">
+					<div className="space-y-0.5">
+						<Label className="text-base">Nivel de registro del servicio</Label>
+						<p className="text-sm text-muted-foreground">
+							Detalle de los registros del servicio; aumentarlo se aplica por completo al reiniciarlo
+						</p>
+					</div>
+					<LevelSelect
+						value={settings.logging.sidecarLevel}
+						onChange={(sidecarLevel) => update.mutate({ logging: { sidecarLevel } })}
+						disabled={update.isPending}
+					/>
+				</div>
+
+				<div className="flex items-center justify-between max-w-md gap-4">
+					<div className="space-y-0.5">
+						<Label className="text-base">Rotación de registros</Label>
+						<p className="text-sm text-muted-foreground">Cuándo se empieza un archivo de registro nuevo</p>
+					</div>
+					<div className="flex flex-col gap-2">
+						<Select
+							value={settings.logging.rotation}
+							onValueChange={(rotation) =>
+								update.mutate({ logging: { rotation: rotation as LoggingSettings["rotation"] } })
+							}
+							disabled={update.isPending}
+						>
+							<SelectTrigger className="w-44">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								<SelectItem value="never">Solo por tamaño</SelectItem>
+								<SelectItem value="daily">Cada día</SelectItem>
+								<SelectItem value="weekly">Cada semana</SelectItem>
+							</SelectContent>
+						</Select>
+						<Select
+							value={String(settings.logging.maxFileSizeMb)}
+							onValueChange={(size) => update.mutate({ logging: { maxFileSizeMb: Number(size) } })}
+							disabled={update.isPending}
+						>
+							<SelectTrigger className="w-44">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								{withCurrent(FILE_SIZES_MB, settings.logging.maxFileSizeMb).map((size) => (
+									<SelectItem key={size} value={String(size)}>
+										Hasta {size} MB
+									</SelectItem>
+								))}
+							</SelectContent>
+						</Select>
+					</div>
+				</div>
+
+				<div className="flex items-center justify-between max-w-md gap-4">
+					<div className="space-y-0.5">
+						<Label className="text-base">Conservación de registros</Label>
+						<p className="text-sm text-muted-foreground">Archivos antiguos que se guardan de cada registro</p>
+					</div>
+					<div className="flex flex-col gap-2">
+						<Select
+							value={String(settings.logging.keepFiles)}
+							onValueChange={(keepFiles) => update.mutate({ logging: { keepFiles: Number(keepFiles) } })}
+							disabled={update.isPending}
+						>
+							<SelectTrigger className="w-44">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								{withCurrent(KEEP_FILES, settings.logging.keepFiles).map((count) => (
+									<SelectItem key={count} value={String(count)}>
+										{count} archivos
+									</SelectItem>
+								))}
+							</SelectContent>
+						</Select>
+						<Select
+							value={settings.logging.keepDays === null ? "none" : String(settings.logging.keepDays)}
+							onValueChange={(days) =>
+								update.mutate({ logging: { keepDays: days === "none" ? null : Number(days) } })
+							}
+							disabled={update.isPending}
+						>
+							<SelectTrigger className="w-44">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								{withCurrent(KEEP_DAYS, settings.logging.keepDays).map((days) => (
+									<SelectItem key={days} value={String(days)}>
+										Máximo {days} días
+									</SelectItem>
+								))}
+								<SelectItem value="none">Sin límite de días</SelectItem>
+							</SelectContent>
+						</Select>
+					</div>
+				</div>
+			</CardContent>
+		</>
+	);
+}
@@ -0,0 +1,279 @@
+import { useMutation, useQuery } from "@tanstack/react-query";
+import { LifeBuoy, RefreshCw, ScrollText } from "lucide-react";
+import { useEffect, useMemo, useRef, useState } from "react";
+import { toast } from "sonner";
+import { Button } from "~/client/components/ui/button";
+import { CardContent, CardDescription, CardTitle } from "~/client/components/ui/card";
+import { Input } from "~/client/components/ui/input";
+import { Label } from "~/client/components/ui/label";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "~/client/components/ui/select";
+import { Switch } from "~/client/components/ui/switch";
+import {
+	createSupportBundle,
+	isTauri,
+	listLogFiles,
+	type LogEntry,
+	type LogFile,
+	type LogFilter,
+	type LogLevel,
+	type LogSource,
+	readLog,
+	watchLogs,
+} from "~/client/lib/tauri";
+import { cn } from "~/client/lib/utils";
+
+const PAGE_SIZE = 500;
+/** Entries kept while following a log, oldest dropped first */
+const MAX_LIVE_ENTRIES = 5000;
+
+const LEVEL_CLASSES: Record<LogLevel, string> = {
+	error: "text-red-500",
+	warn: "text-yellow-500",
+	info: "text-foreground",
+	debug: "text-muted-foreground",
+	trace: "text-muted-foreground",
+};
+
+function fileLabel(file: LogFile) {
+	const source = file.source === "sidecar" ? "Servicio" : "Aplicación";
+	if (file.current) {
+		return `${source} (actual)`;
+	}
+	const modified = file.modifiedMs ? new Date(file.modifiedMs).toLocaleString() : file.name;
+	return `${source} - ${modified}`;
+}
+
+export function LogsSection() {
+	const [isTauriApp, setIsTauriApp] = useState(false);
+	const [selected, setSelected] = useState<string | null>(null);
+	const [level, setLevel] = useState<LogLevel | "all">("all");
+	const [source, setSource] = useState<LogSource | "all">("all");
+	const [since, setSince] = useState("");
+	const [text, setText] = useState("");
+	const [appliedText, setAppliedText] = useState("");
+	const [live, setLive] = useState(false);
+	const [entries, setEntries] = useState<LogEntry[]>([]);
+	const [hasMore, setHasMore] = useState(false);
+	const [loading, setLoading] = useState(false);
+	const listRef = useRef<HTMLDivElement>(null);
+
+	useEffect(() => {
+		setIsTauriApp(isTauri());
+	}, []);
+
+	const { data: files, refetch: refetchFiles } = useQuery({
+		queryKey: ["log-files"],
+		queryFn: listLogFiles,
+		enabled: isTauriApp,
+	});
+
+	const supportBundle = useMutation({
+		mutationFn: createSupportBundle,
+		onSuccess: (path) => {
+			if (path) {
+				toast.success("Paquete de diagnóstico guardado", { description: path });
+			}
+		},
+		onError: (error) => {
+			toast.error("Error al crear el paquete de diagnóstico", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+	});
+
+	const file = files?.find((f) => f.name === selected) ?? files?.[0];
+
+	const filter = useMemo<LogFilter>(
+		() => ({
+			level: level === "all" ? undefined : level,
+			source: source === "all" ? undefined : source,
+			since: since || undefined,
+			text: appliedText || undefined,
+		}),
+		[level, source, since, appliedText],
+	);
+
+	// Wait for a pause in typing before searching
+	useEffect(() => {
+		const timeout = setTimeout(() => setAppliedText(text.trim()), 300);
+		return () => clearTimeout(timeout);
+	}, [text]);
+
+	const load = async (beforeLine?: number) => {
+		if (!file) return;
+		setLoading(true);
+		try {
+			const page = await readLog(file.name, filter, { beforeLine, limit: PAGE_SIZE });
+			setEntries((current) => (beforeLine ? [...page.entries, ...current] : page.entries));
+			setHasMore(page.hasMore);
+		} catch (error) {
+			toast.error("Error al leer el registro", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		} finally {
+			setLoading(false);
+		}
+	};
+
+	// Reload whenever the file or the filter changes
+	useEffect(() => {
+		void load();
+	}, [file?.name, filter]);
+
+	// Only the files being written to can be followed
+	const following = live && file?.current === true;
+
+	useEffect(() => {
+		if (!following || !file) return;
+		const stop = watchLogs(filter, (changed, added) => {
+			if (changed !== file.name) return;
+			setEntries((current) => [...current, ...added].slice(-MAX_LIVE_ENTRIES));
+		});
+		return () => {
+			void stop.then((unwatch) => unwatch());
+		};
+	}, [following, file, filter]);
+
+	useEffect(() => {
+		// Keep the newest entries in view without scrolling the page
+		if (following && listRef.current) {
+			listRef.current.scrollTop = listRef.current.scrollHeight;
+		}
+	}, [entries, following]);
+
+	if (!isTauriApp || !files) {
+		return null;
+	}
+
+	return (
+		<>
+			<div className="border-t border-border/50 bg-card-header p-6">
+				<CardTitle className="flex items-center gap-2">
+					<ScrollText className="size-5" />
+					Registros
+				</CardTitle>
+				<CardDescription className="mt-1.5">Registros de la aplicación y del servicio</CardDescription>
+			</div>
+			<CardContent className="p-6 space-y-4">
+				<div className="flex flex-wrap items-end gap-3">
+					<div className="space-y-1.5">
+						<Label>Archivo</Label>
+						<Select value={file?.name ?? ""} onValueChange={setSelected}>
+							<SelectTrigger className="w-56">
+								<SelectValue placeholder="Sin registros" />
+							</SelectTrigger>
+							<SelectContent>
+								{files.map((f) => (
+									<SelectItem key={f.name} value={f.name}>
+										{fileLabel(f)}
+									</SelectItem>
+								))}
+							</SelectContent>
+						</Select>
+					</div>
+					<div className="space-y-1.5">
+						<Label>Nivel mínimo</Label>
+						<Select value={level} onValueChange={(value) => setLevel(value as LogLevel | "all")}>
+							<SelectTrigger className="w-40">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								<SelectItem value="all">Todos</SelectItem>
+								<SelectItem value="error">Errores</SelectItem>
+								<SelectItem value="warn">Advertencias</SelectItem>
+								<SelectItem value="info">Información</SelectItem>
+								<SelectItem value="debug">Depuración</SelectItem>
+								<SelectItem value="trace">Traza</SelectItem>
+							</SelectContent>
+						</Select>
+					</div>
+					<div className="space-y-1.5">
+						<Label>Origen</Label>
+						<Select value={source} onValueChange={(value) => setSource(value as LogSource | "all")}>
+							<SelectTrigger className="w-40">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								<SelectItem value="all">Todos</SelectItem>
+								<SelectItem value="shell">Aplicación</SelectItem>
+								<SelectItem value="sidecar">Servicio</SelectItem>
+							</SelectContent>
+						</Select>
+					</div>
+					<div className="space-y-1.5">
+						<Label htmlFor="logs-since">Desde</Label>
+						<Input
+							id="logs-since"
+							type="datetime-local"
+							className="w-52"
+							value={since}
+							onChange={(e) => setSince(e.target.value)}
+						/>
+					</div>
+					<div className="space-y-1.5 flex-1 min-w-40">
+						<Label htmlFor="logs-text">Buscar</Label>
+						<Input id="logs-text" value={text} onChange={(e) => setText(e.target.value)} placeholder="Texto del mensaje" />
+					</div>
+				</div>
+
+				<div className="flex items-center justify-between gap-4">
+					<div className="flex items-center gap-2">
+						<Switch id="logs-live" checked={following} onCheckedChange={setLive} disabled={!file?.current} />
+						<Label htmlFor="logs-live" className="cursor-pointer">
+							En directo
+						</Label>
+					</div>
+					<div className="flex items-center gap-2">
+						<Button
+							variant="outline"
+							size="sm"
+							onClick={() => supportBundle.mutate()}
+							loading={supportBundle.isPending}
+						>
+							<LifeBuoy className="h-4 w-4 mr-2" />
+							Paquete de diagnóstico
+						</Button>
+						<Button
+							variant="outline"
+							size="sm"
+							onClick={() => {
+								void refetchFiles();
+								void load();
+							}}
+							loading={loading}
+						>
+							<RefreshCw className="h-4 w-4 mr-2" />
+							Actualizar
+						</Button>
+					</div>
+				</div>
+
+				<div ref={listRef} className="h-96 overflow-auto border border-border/50 bg-muted/30 p-3 font-mono text-xs">
+					{hasMore && (
+						<Button
+							variant="ghost"
+							size="sm"
+							className="mb-2"
+							onClick={() => void load(entries[0]?.line)}
+							disabled={loading}
+						>
+							Cargar anteriores
+						</Button>
+					)}
+					{entries.length === 0 && !loading && <p className="text-muted-foreground">No hay entradas</p>}
+					{entries.map((entry) => (
+						<div
+							key={`${entry.line}-${entry.source}`}
+							className={cn("whitespace-pre-wrap break-all", entry.level && LEVEL_CLASSES[entry.level])}
+						>
+							<span className="text-muted-foreground">{entry.timestamp}</span>{" "}
+							<span className="uppercase">{entry.level}</span>{" "}
+							{entry.source === "sidecar" && <span className="text-muted-foreground">[servicio] </span>}
+							{entry.message}
+						</div>
+					))}
+				</div>
+			</CardContent>
+		</>
+	);
+}
@@ -0,0 +1,263 @@
+import { useMutation, useQuery, useQueryClient } from "@tanstack/react-query";
+import { Camera, History, RotateCcw } from "lucide-react";
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { ByteSize } from "~/client/components/bytes-size";
+import {
+	AlertDialog,
+	AlertDialogCancel,
+	AlertDialogContent,
+	AlertDialogDescription,
+	AlertDialogFooter,
+	AlertDialogHeader,
+	AlertDialogTitle,
+} from "~/client/components/ui/alert-dialog";
+import { Button } from "~/client/components/ui/button";
+import { CardContent, CardDescription, CardTitle } from "~/client/components/ui/card";
+import { Label } from "~/client/components/ui/label";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "~/client/components/ui/select";
+import { Switch } from "~/client/components/ui/switch";
+import {
+	getSettings,
+	isTauri,
+	listSnapshots,
+	rollbackSnapshot,
+	type ShellSettings,
+	type SnapshotInfo,
+	type SnapshotReason,
+	takeSnapshot,
+	type UpgradeOutcome,
+	updateSettings,
+} from "~/client/lib/tauri";
+import { withCurrent } from "./desktop-section";
+
+const INTERVAL_HOURS = [6, 12, 24, 168];
+const KEEP_SNAPSHOTS = [3, 7, 14, 30];
+
+const REASON_LABELS: Record<SnapshotReason, string> = {
+	scheduled: "Programada",
+	manual: "Manual",
+	beforeRollback: "Antes de restaurar",
+	beforeUpgrade: "Antes de actualizar",
+};
+
+const OUTCOME_LABELS: Record<UpgradeOutcome, string> = {
+	pending: "Sin completar",
+	succeeded: "Completada",
+	rolledBack: "Revertida",
+};
+
+function intervalLabel(hours: number) {
+	if (hours % 168 === 0) return hours === 168 ? "Cada semana" : `Cada ${hours / 168} semanas`;
+	if (hours % 24 === 0) return hours === 24 ? "Cada día" : `Cada ${hours / 24} días`;
+	return `Cada ${hours} horas`;
+}
+
+export function SnapshotsSection() {
+	const [isTauriApp, setIsTauriApp] = useState(false);
+	const [pending, setPending] = useState<SnapshotInfo | null>(null);
+	const queryClient = useQueryClient();
+
+	useEffect(() => {
+		setIsTauriApp(isTauri());
+	}, []);
+
+	const { data: settings } = useQuery({
+		queryKey: ["shell-settings"],
+		queryFn: getSettings,
+		enabled: isTauriApp,
+	});
+
+	const { data: snapshots } = useQuery({
+		queryKey: ["snapshots"],
+		queryFn: listSnapshots,
+		enabled: isTauriApp,
+	});
+
+	const update = useMutation({
+		mutationFn: updateSettings,
+		onSuccess: (next) => queryClient.setQueryData<ShellSettings>(["shell-settings"], next),
+		onError: (error) => {
+			toast.error("Error al guardar la configuración", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+	});
+
+	const take = useMutation({
+		mutationFn: takeSnapshot,
+		onSuccess: (snapshot) => {
+			void queryClient.invalidateQueries({ queryKey: ["snapshots"] });
+			if (snapshot) {
+				toast.success("Instantánea creada");
+			} else {
+				toast.info("Todavía no hay base de datos de la que crear una instantánea");
+			}
+		},
+		onError: (error) => {
+			toast.error("Error al crear la instantánea", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+	});
+
+	const rollback = useMutation({
+		mutationFn: (snapshot: SnapshotInfo) => rollbackSnapshot(snapshot.id),
+		onSuccess: () => {
+			toast.success("Instantánea restaurada. El servicio se ha reiniciado.");
+			// The users and sessions are those of the restored database
+			setTimeout(() => window.location.reload(), 1500);
+		},
+		onError: (error) => {
+			toast.error("Error al restaurar la instantánea", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+		onSettled: () => {
+			setPending(null);
+			void queryClient.invalidateQueries({ queryKey: ["snapshots"] });
+		},
+	});
+
+	if (!isTauriApp || !settings || !snapshots) {
+		return null;
+	}
+
+	const busy = take.isPending || rollback.isPending;
+
+	return (
+		<>
+			<div className="border-t border-border/50 bg-card-header p-6">
+				<CardTitle className="flex items-center gap-2">
+					<History className="size-5" />
+					Instantáneas locales
+				</CardTitle>
+				<CardDescription className="mt-1.5">
+					Copias periódicas de la base de datos y los ajustes en el directorio de datos, para volver a un estado anterior
+				</CardDescription>
+			</div>
+			<CardContent className="p-6 space-y-6">
+				<div className="flex items-center justify-between max-w-md">
+					<div className="space-y-0.5">
+						<Label htmlFor="snapshots-toggle" className="text-base cursor-pointer">
+							Instantáneas automáticas
+						</Label>
+						<p className="text-sm text-muted-foreground">Se crean sin detener el servicio</p>
+					</div>
+					<Switch
+						id="snapshots-toggle"
+						checked={settings.snapshots.enabled}
+						onCheckedChange={(enabled) => update.mutate({ snapshots: { enabled } })}
+						disabled={update.isPending}
+					/>
+				</div>
+
+				<div className="flex items-center justify-between max-w-md gap-4">
+					<div className="space-y-0.5">
+						<Label className="text-base">Frecuencia y conservación</Label>
+						<p className="text-sm text-muted-foreground">Se borran las más antiguas al superar el número</p>
+					</div>
+					<div className="flex flex-col gap-2">
+						<Select
+							value={String(settings.snapshots.intervalHours)}
+							onValueChange={(hours) => update.mutate({ snapshots: { intervalHours: Number(hours) } })}
+							disabled={update.isPending || !settings.snapshots.enabled}
+						>
+							<SelectTrigger className="w-44">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								{withCurrent(INTERVAL_HOURS, settings.snapshots.intervalHours).map((hours) => (
+									<SelectItem key={hours} value={String(hours)}>
+										{intervalLabel(hours)}
+									</SelectItem>
+								))}
+							</SelectContent>
+						</Select>
+						<Select
+							value={String(settings.snapshots.keep)}
+							onValueChange={(keep) => update.mutate({ snapshots: { keep: Number(keep) } })}
+							disabled={update.isPending}
+						>
+							<SelectTrigger className="w-44">
+								<SelectValue />
+							</SelectTrigger>
+							<SelectContent>
+								{withCurrent(KEEP_SNAPSHOTS, settings.snapshots.keep).map((count) => (
+									<SelectItem key={count} value={String(count)}>
+										{count} instantáneas
+									</SelectItem>
+								))}
+							</SelectContent>
+						</Select>
+					</div>
+				</div>
+
+				<div className="space-y-2 max-w-3xl">
+					<div className="flex items-center justify-between">
+						<p className="text-base font-medium">Instantáneas guardadas</p>
+						<Button variant="outline" size="sm" onClick={() => take.mutate()} loading={take.isPending} disabled={busy}>
+							<Camera className="h-4 w-4 mr-2" />
+							Crear ahora
+						</Button>
+					</div>
+					{snapshots.length === 0 && <p className="text-sm text-muted-foreground">Todavía no hay instantáneas</p>}
+					{snapshots.map((snapshot) => (
+						<div key={snapshot.id} className="flex items-center justify-between gap-4 border-t border-border/50 pt-2">
+							<div className="space-y-0.5 min-w-0">
+								<p className="text-sm font-medium">{new Date(snapshot.createdAt * 1000).toLocaleString()}</p>
+								<p className="text-sm text-muted-foreground">
+									{REASON_LABELS[snapshot.reason]} ·{" "}
+									{snapshot.appVersion === "unknown" ? "versión anterior" : `versión ${snapshot.appVersion}`} ·{" "}
+									<ByteSize bytes={snapshot.sizeBytes} />
+								</p>
+							</div>
+							<Button variant="outline" size="sm" disabled={busy} onClick={() => setPending(snapshot)}>
+								<RotateCcw className="h-4 w-4 mr-2" />
+								Restaurar
+							</Button>
+						</div>
+					))}
+				</div>
+
+				{settings.upgrades.history.length > 0 && (
+					<div className="space-y-2 max-w-3xl">
+						<p className="text-base font-medium">Historial de actualizaciones</p>
+						{settings.upgrades.history
+							.slice()
+							.reverse()
+							.map((record) => (
+								<div key={record.startedAt} className="flex items-center justify-between gap-4 border-t border-border/50 pt-2">
+									<p className="text-sm">
+										{record.from ?? "Versión anterior"} → {record.to}
+									</p>
+									<p className="text-sm text-muted-foreground">
+										{new Date(record.startedAt * 1000).toLocaleString()} · {OUTCOME_LABELS[record.outcome]}
+										{!record.snapshot && " · sin instantánea"}
+									</p>
+								</div>
+							))}
+					</div>
+				)}
+			</CardContent>
+			<AlertDialog open={pending !== null} onOpenChange={(open) => !open && !rollback.isPending && setPending(null)}>
+				<AlertDialogContent>
+					<AlertDialogHeader>
+						<AlertDialogTitle>¿Volver a esta instantánea?</AlertDialogTitle>
+						<AlertDialogDescription>
+							El servicio se detendrá y la base de datos y los ajustes se sustituirán por los del{" "}
+							{pending ? new Date(pending.createdAt * 1000).toLocaleString() : ""}. Antes se creará una instantánea del
+							estado actual. No puede haber copias de seguridad en curso.
+						</AlertDialogDescription>
+					</AlertDialogHeader>
+					<AlertDialogFooter>
+						<AlertDialogCancel disabled={rollback.isPending}>Cancelar</AlertDialogCancel>
+						<Button loading={rollback.isPending} onClick={() => pending && rollback.mutate(pending)}>
+							Restaurar
+						</Button>
+					</AlertDialogFooter>
+				</AlertDialogContent>
+			</AlertDialog>
+		</>
+	);
+}
@@ -0,0 +1,131 @@
+import { useMutation } from "@tanstack/react-query";
+import { ArchiveRestore, DatabaseBackup, Save } from "lucide-react";
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import {
+	AlertDialog,
+	AlertDialogCancel,
+	AlertDialogContent,
+	AlertDialogDescription,
+	AlertDialogFooter,
+	AlertDialogHeader,
+	AlertDialogTitle,
+} from "~/client/components/ui/alert-dialog";
+import { Button } from "~/client/components/ui/button";
+import { CardContent, CardDescription, CardTitle } from "~/client/components/ui/card";
+import { Input } from "~/client/components/ui/input";
+import { Label } from "~/client/components/ui/label";
+import { exportAppState, isTauri, MIN_STATE_PASSPHRASE_LENGTH, restoreAppState } from "~/client/lib/tauri";
+
+export function StateBackupSection() {
+	const [isTauriApp, setIsTauriApp] = useState(false);
+	const [passphrase, setPassphrase] = useState("");
+	const [confirmRestore, setConfirmRestore] = useState(false);
+
+	useEffect(() => {
+		setIsTauriApp(isTauri());
+	}, []);
+
+	const exportState = useMutation({
+		mutationFn: () => exportAppState(passphrase),
+		onSuccess: (path) => {
+			if (path) {
+				toast.success("Copia de la configuración guardada", { description: path });
+			}
+		},
+		onError: (error) => {
+			toast.error("Error al guardar la copia de la configuración", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+	});
+
+	const restoreState = useMutation({
+		mutationFn: () => restoreAppState(passphrase),
+		onSuccess: (summary) => {
+			if (!summary) return;
+			toast.success("Configuración restaurada", {
+				description: `Copia de la versión ${summary.appVersion} del ${new Date(summary.createdAt * 1000).toLocaleString()}`,
+			});
+			// The users and sessions are those of the restored database
+			setTimeout(() => window.location.reload(), 1500);
+		},
+		onError: (error) => {
+			toast.error("Error al restaurar la configuración", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+		onSettled: () => setConfirmRestore(false),
+	});
+
+	if (!isTauriApp) {
+		return null;
+	}
+
+	const busy = exportState.isPending || restoreState.isPending;
+	const validPassphrase = passphrase.length >= MIN_STATE_PASSPHRASE_LENGTH;
+
+	return (
+		<>
+			<div className="border-t border-border/50 bg-card-header p-6">
+				<CardTitle className="flex items-center gap-2">
+					<DatabaseBackup className="size-5" />
+					Copia de la configuración
+				</CardTitle>
+				<CardDescription className="mt-1.5">
+					Guarde la base de datos, la contraseña de Restic y los ajustes en un archivo cifrado, para recuperarlos en
+					otro equipo o tras una reinstalación. No incluye la caché, los registros ni los repositorios.
+				</CardDescription>
+			</div>
+			<CardContent className="p-6 space-y-4">
+				<div className="space-y-2 max-w-md">
+					<Label htmlFor="state-passphrase">Frase de cifrado</Label>
+					<Input
+						id="state-passphrase"
+						type="password"
+						value={passphrase}
+						onChange={(e) => setPassphrase(e.target.value)}
+						placeholder={`Al menos ${MIN_STATE_PASSPHRASE_LENGTH} caracteres`}
+						autoComplete="new-password"
+					/>
+					<p className="text-sm text-muted-foreground">
+						Sin esta frase no se puede restaurar la copia. El servicio se reinicia durante la operación.
+					</p>
+				</div>
+				<div className="flex gap-2">
+					<Button
+						variant="outline"
+						disabled={!validPassphrase || busy}
+						loading={exportState.isPending}
+						onClick={() => exportState.mutate()}
+					>
+						<Save className="h-4 w-4 mr-2" />
+						Guardar copia
+					</Button>
+					<Button variant="outline" disabled={!validPassphrase || busy} onClick={() => setConfirmRestore(true)}>
+						<ArchiveRestore className="h-4 w-4 mr-2" />
+						Restaurar copia
+					</Button>
+				</div>
+			</CardContent>
+			<AlertDialog open={confirmRestore} onOpenChange={(open) => !open && !restoreState.isPending && setConfirmRestore(false)}>
+				<AlertDialogContent>
+					<AlertDialogHeader>
+						<AlertDialogTitle>¿Restaurar la configuración?</AlertDialogTitle>
+						<AlertDialogDescription>
+							La base de datos, la contraseña de Restic y los ajustes actuales se sustituirán por los de la copia. Antes se
+							guardará una copia del estado actual, cifrada con la misma frase, en el directorio de datos. No puede haber
+							copias de seguridad en curso.
+						</AlertDialogDescription>
+					</AlertDialogHeader>
+					<AlertDialogFooter>
+						<AlertDialogCancel disabled={restoreState.isPending}>Cancelar</AlertDialogCancel>
+						<Button loading={restoreState.isPending} onClick={() => restoreState.mutate()}>
+							Elegir archivo y restaurar
+						</Button>
+					</AlertDialogFooter>
+				</AlertDialogContent>
+			</AlertDialog>
+		</>
+	);
+}
@@ -0,0 +1,188 @@
+import { useMutation, useQuery, useQueryClient } from "@tanstack/react-query";
+import { FolderOpen, HardDrive, RotateCcw } from "lucide-react";
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import {
+	AlertDialog,
+	AlertDialogCancel,
+	AlertDialogContent,
+	AlertDialogDescription,
+	AlertDialogFooter,
+	AlertDialogHeader,
+	AlertDialogTitle,
+} from "~/client/components/ui/alert-dialog";
+import { Button } from "~/client/components/ui/button";
+import { CardContent, CardDescription, CardTitle } from "~/client/components/ui/card";
+import {
+	getStorageSettings,
+	isTauri,
+	pickDirectory,
+	setStorageLocation,
+	type StorageInfo,
+	type StorageLocation,
+} from "~/client/lib/tauri";
+
+const LOCATIONS: { location: StorageLocation; label: string; description: string }[] = [
+	{ location: "data", label: "Datos", description: "Base de datos y, salvo que se muevan, el resto de ubicaciones" },
+	{ location: "repositories", label: "Repositorios", description: "Repositorios locales de Restic" },
+	{ location: "volumes", label: "Volúmenes", description: "Puntos de montaje de los volúmenes" },
+	{ location: "cache", label: "Caché", description: "Caché de Restic, puede ocupar varios GB por repositorio" },
+	{ location: "password", label: "Contraseña", description: "Archivo con la contraseña de Restic" },
+];
+
+type PendingChange = { location: StorageLocation; dir: string | null };
+
+function currentPath(info: StorageInfo, location: StorageLocation) {
+	switch (location) {
+		case "data":
+			return info.paths.dataDir;
+		case "repositories":
+			return info.paths.repositoriesDir;
+		case "volumes":
+			return info.paths.volumesDir;
+		case "cache":
+			return info.paths.cacheDir;
+		case "password":
+			return info.paths.passwordFile;
+	}
+}
+
+function isCustom(info: StorageInfo, location: StorageLocation) {
+	return info.settings[`${location}Dir`] !== null;
+}
+
+export function StorageSection() {
+	const [isTauriApp, setIsTauriApp] = useState(false);
+	const [pending, setPending] = useState<PendingChange | null>(null);
+	const queryClient = useQueryClient();
+
+	useEffect(() => {
+		setIsTauriApp(isTauri());
+	}, []);
+
+	const { data: storage } = useQuery({
+		queryKey: ["storage-settings"],
+		queryFn: getStorageSettings,
+		enabled: isTauriApp,
+	});
+
+	const changeLocation = useMutation({
+		mutationFn: ({ change, migrate }: { change: PendingChange; migrate: boolean }) =>
+			setStorageLocation(change.location, change.dir, migrate),
+		onSuccess: (info) => {
+			queryClient.setQueryData(["storage-settings"], info);
+			toast.success("Ubicación actualizada. El servicio se ha reiniciado.");
+		},
+		onError: (error) => {
+			toast.error("Error al cambiar la ubicación", {
+				description: error instanceof Error ? error.message : String(error),
+			});
+		},
+		onSettled: () => setPending(null),
+	});
+
+	const choose = async (location: StorageLocation, label: string) => {
+		const dir = await pickDirectory(`Nueva ubicación: ${label}`);
+		if (dir) {
+			setPending({ location, dir });
+		}
+	};
+
+	if (!isTauriApp || !storage) {
+		return null;
+	}
+
+	return (
+		<>
+			<div className="border-t border-border/50 bg-card-header p-6">
+				<CardTitle className="flex items-center gap-2">
+					<HardDrive className="size-5" />
+					Almacenamiento
+				</CardTitle>
+				<CardDescription className="mt-1.5">
+					Dónde se guardan la base de datos, los repositorios locales y la caché de Restic
+				</CardDescription>
+			</div>
+			<CardContent className="p-6 space-y-4">
+				{LOCATIONS.filter(
+					// In the secret store, the password file location is not used
+					({ location }) => location !== "password" || storage.passwordStore !== "keyring",
+				).map(({ location, label, description }) => {
+					const pinned = location === "data" && storage.dataDirPinned;
+					return (
+						<div key={location} className="flex items-center justify-between gap-4 max-w-3xl">
+							<div className="space-y-0.5 min-w-0">
+								<p className="text-base font-medium">{label}</p>
+								<p className="text-sm text-muted-foreground">{description}</p>
+								<p className="text-sm font-mono truncate" title={currentPath(storage, location)}>
+									{currentPath(storage, location)}
+								</p>
+								{pinned && (
+									<p className="text-sm text-muted-foreground">Fijada con --data-dir al iniciar la aplicación</p>
+								)}
+							</div>
+							<div className="flex gap-2 shrink-0">
+								{isCustom(storage, location) && (
+									<Button
+										variant="outline"
+										size="sm"
+										disabled={pinned || changeLocation.isPending}
+										onClick={() => setPending({ location, dir: null })}
+									>
+										<RotateCcw className="h-4 w-4" />
+										Restablecer
+									</Button>
+								)}
+								<Button
+									variant="outline"
+									size="sm"
+									disabled={pinned || changeLocation.isPending}
+									onClick={() => void choose(location, label)}
+								>
+									<FolderOpen className="h-4 w-4" />
+									Cambiar
+								</Button>
+							</div>
+						</div>
+					);
+				})}
+				{storage.passwordStore === "keyring" && (
+					<div className="space-y-0.5 max-w-3xl">
+						<p className="text-base font-medium">Contraseña</p>
+						<p className="text-sm text-muted-foreground">
+							La contraseña de Restic se guarda en el almacén de credenciales del sistema
+						</p>
+					</div>
+				)}
+			</CardContent>
+			<AlertDialog open={pending !== null} onOpenChange={(open) => !open && !changeLocation.isPending && setPending(null)}>
+				<AlertDialogContent>
+					<AlertDialogHeader>
+						<AlertDialogTitle>¿Mover los datos existentes?</AlertDialogTitle>
+						<AlertDialogDescription>
+							El servicio se detendrá mientras se aplica el cambio
+							{pending?.dir ? ` a ${pending.dir}` : " a la ubicación predeterminada"}. Puede mover los datos actuales a la
+							nueva ubicación, o usarla tal cual si ya contiene los datos o quiere empezar de cero.
+						</AlertDialogDescription>
+					</AlertDialogHeader>
+					<AlertDialogFooter>
+						<AlertDialogCancel disabled={changeLocation.isPending}>Cancelar</AlertDialogCancel>
+						<Button
+							variant="outline"
+							disabled={changeLocation.isPending}
+							onClick={() => pending && changeLocation.mutate({ change: pending, migrate: false })}
+						>
+							Usar sin mover
+						</Button>
+						<Button
+							loading={changeLocation.isPending}
+							onClick={() => pending && changeLocation.mutate({ change: pending, migrate: true })}
+						>
+							Mover datos
+						</Button>
+					</AlertDialogFooter>
+				</AlertDialogContent>
+			</AlertDialog>
+		</>
+	);
+}
@@ -21,6 +21,11 @@ import { authClient } from "~/client/lib/auth-client";
 import { appContext } from "~/context";
 import { TwoFactorSection } from "../components/two-factor-section";
 import { AutostartSection } from "../components/autostart-section";
+import { DesktopSection } from "../components/desktop-section";
+import { LogsSection } from "../components/logs-section";
+import { SnapshotsSection } from "../components/snapshots-section";
+import { StateBackupSection } from "../components/state-backup-section";
+import { StorageSection } from "../components/storage-section";
 import type { Route } from "./+types/settings";
 
 export const handle = {
@@ -283,6 +288,16 @@ export default function Settings({ loaderData }: Route.ComponentProps) {
 			<TwoFactorSection twoFactorEnabled={loaderData.user?.twoFactorEnabled} />
 
 		<AutostartSection />
+
+			<DesktopSection />
+
+			<StorageSection />
+
+			<StateBackupSection />
+
+			<SnapshotsSection />
+
+			<LogsSection />
 		</Card>
 	);
 }
@@ -1,5 +1,5 @@
 import { MutationCache, QueryClient, QueryClientProvider } from "@tanstack/react-query";
-import { isRouteErrorResponse, Links, Meta, Outlet, Scripts, ScrollRestoration } from "react-router";
+import { isRouteErrorResponse, Links, Meta, Outlet, Scripts, ScrollRestoration, useNavigate } from "react-router";
 import { useEffect, useState } from "react";
 
 import type { Route } from "./+types/root";
@@ -7,7 +7,18 @@ import "./app.css";
 import { Toaster } from "./client/components/ui/sonner";
 import { ServerEventsProvider } from "./client/contexts/server-events-context";
 import { client } from "./client/api-client/client.gen";
-import { isTauri, waitForBackend } from "./client/lib/tauri";
+import {
+	getBackendConnection,
+	getPreflightReport,
+	getSettings,
+	isTauri,
+	type PreflightCheck,
+	pendingUpgrade,
+	rollbackUpgrade,
+	takePendingRoute,
+	type UpgradeRecord,
+	waitForBackend,
+} from "./client/lib/tauri";
 
 // Initialize client with default baseUrl (will be updated in Tauri mode)
 client.setConfig({
@@ -72,30 +83,92 @@ export default function App() {
 	const isDev = import.meta.env.DEV;
 	const [backendReady, setBackendReady] = useState(!isTauri() || isDev);
 	const [error, setError] = useState<string | null>(null);
+	const [failedChecks, setFailedChecks] = useState<PreflightCheck[]>([]);
+	const [upgrade, setUpgrade] = useState<UpgradeRecord | null>(null);
+	const [rollingBack, setRollingBack] = useState(false);
+	const navigate = useNavigate();
 
 	useEffect(() => {
 		if (isTauri() && !isDev) {
 			waitForBackend()
-				.then((backendUrl) => {
+				.then(async (backendUrl) => {
 					console.info("[App] Backend ready at:", backendUrl);
-					// Set backend URL for API client
-					client.setConfig({ baseUrl: backendUrl });
+					const connection = await getBackendConnection();
+					if (!connection) {
+						throw new Error("Failed to get backend connection details");
+					}
+					// Set backend URL and channel token for API client
+					client.setConfig({ baseUrl: backendUrl, headers: { "X-Channel-Token": connection.channelToken } });
 					// Set global variable for auth client
 					(window as any).__TAURI_BACKEND_URL__ = backendUrl;
 					setBackendReady(true);
 				})
-				.catch((err) => {
+				.catch(async (err) => {
+					// Startup checks that failed explain the error better than its one-line summary
+					const report = await getPreflightReport();
+					setFailedChecks(report?.checks.filter((check) => check.status !== "ok") ?? []);
+					setUpgrade(pendingUpgrade(await getSettings()));
 					setError(err instanceof Error ? err.message : "Failed to connect to backend");
 				});
 		}
 	}, [isDev]);
 
+	// Open the page requested at launch once the app can render it
+	useEffect(() => {
+		if (!backendReady) return;
+
+		void takePendingRoute().then((route) => {
+			if (route) navigate(route);
+		});
+	}, [backendReady, navigate]);
+
 	if (error) {
 		return (
 			<div className="flex items-center justify-center min-h-screen bg-background">
 				<div className="text-center p-8">
 					<h1 className="text-2xl font-bold text-destructive mb-4">Connection Error</h1>
-					<p className="text-muted-foreground">{error}</p>
+					{/* The error only summarizes the failed checks, listed below */}
+					{!failedChecks.some((check) => check.status === "error") && (
+						<p className="text-muted-foreground whitespace-pre-line">{error}</p>
+					)}
+					{failedChecks.length > 0 && (
+						<ul className="mx-auto mt-4 max-w-2xl space-y-3 text-left">
+							{failedChecks.map((check) => (
+								<li key={check.id} className="border border-border/50 p-3">
+									<p className={check.status === "error" ? "text-destructive" : "text-yellow-500"}>{check.message}</p>
+									{check.fix && <p className="mt-1 text-sm text-muted-foreground">{check.fix}</p>}
+								</li>
+							))}
+						</ul>
+					)}
+					{upgrade && (
+						<div className="mx-auto mt-6 max-w-2xl border border-border/50 p-4 text-left">
+							<p>
+								This is the first start after upgrading{upgrade.from ? ` from ${upgrade.from}` : ""} to {upgrade.to}. The
+								database was saved before the upgrade and can be restored if the upgrade caused this error.
+							</p>
+							<p className="mt-1 text-sm text-muted-foreground">
+								The backend will then try the upgrade again. If it keeps failing, reinstall the previous version.
+							</p>
+							<button
+								type="button"
+								className="mt-3 border border-border px-3 py-1.5 text-sm hover:bg-muted disabled:opacity-50"
+								disabled={rollingBack}
+								onClick={() => {
+									setRollingBack(true);
+									rollbackUpgrade()
+										.then(() => window.location.reload())
+										.catch((err) => {
+											setError(err instanceof Error ? err.message : String(err));
+											setUpgrade(null);
+											setRollingBack(false);
+										});
+								}}
+							>
+								{rollingBack ? "Restoring..." : "Restore the database from before the upgrade"}
+							</button>
+						</div>
+					)}
 				</div>
 			</div>
 		);
@@ -6,7 +6,7 @@ import { secureHeaders } from "hono/secure-headers";
 import { rateLimiter } from "hono-rate-limiter";
 import { openAPIRouteHandler } from "hono-openapi";
 import { authController } from "./modules/auth/auth.controller";
-import { requireAuth } from "./modules/auth/auth.middleware";
+import { redactChannelToken, requireAuth, requireChannelToken } from "./modules/auth/auth.middleware";
 import { repositoriesController } from "./modules/repositories/repositories.controller";
 import { systemController } from "./modules/system/system.controller";
 import { volumeController } from "./modules/volumes/volume.controller";
@@ -36,15 +36,34 @@ export const scalarDescriptor = Scalar({
 	url: "/api/v1/openapi.json",
 });
 
+/**
+ * Origins of the desktop webview, which loads the UI from the app bundle
+ * and reaches the sidecar cross-origin on 127.0.0.1
+ */
+const TAURI_WEBVIEW_ORIGINS = ["tauri://localhost", "http://tauri.localhost", "https://tauri.localhost"];
+
+const CORS_ALLOW_HEADERS = ["Content-Type", "Authorization", "X-Channel-Token"];
+
 export const createApp = () => {
 	const app = new Hono().use(secureHeaders());
 
-	if (config.trustedOrigins) {
-		app.use(cors({ origin: config.trustedOrigins }));
+	if (config.tauri) {
+		// Mounted ahead of the channel token check so preflights, which never carry the token, get their CORS headers
+		app.use(
+			cors({
+				origin: [...TAURI_WEBVIEW_ORIGINS, ...(config.trustedOrigins ?? [])],
+				allowHeaders: CORS_ALLOW_HEADERS,
+				credentials: true,
+			}),
+		);
+	} else if (config.trustedOrigins) {
+		app.use(cors({ origin: config.trustedOrigins, allowHeaders: CORS_ALLOW_HEADERS }));
 	}
 
+	app.use(requireChannelToken);
+
 	if (config.environment !== "test") {
-		app.use(honoLogger());
+		app.use(honoLogger((message, ...rest) => console.log(redactChannelToken(message), ...rest)));
 	}
 
 	if (!config.disableRateLimiting) {
@@ -75,7 +94,7 @@ export const createApp = () => {
 	app.get("/api/v1/docs", requireAuth, scalarDescriptor);
 
 	app.onError((err, c) => {
-		logger.error(`${c.req.url}: ${err.message}`);
+		logger.error(`${redactChannelToken(c.req.url)}: ${err.message}`);
 
 		if (err.cause instanceof Error) {
 			logger.error(err.cause.message);
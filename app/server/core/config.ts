@@ -51,6 +51,7 @@ const getResticHostname = () => {
 const envSchema = type({
 	NODE_ENV: type.enumerated("development", "production", "test").default("production"),
 	SERVER_IP: 'string = "localhost"',
+	LISTEN_HOST: "string?",
 	SERVER_IDLE_TIMEOUT: 'string.integer.parse = "60"',
 	RESTIC_HOSTNAME: "string?",
 	PORT: 'string.integer.parse = "4096"',
@@ -58,10 +59,15 @@ const envSchema = type({
 	APP_VERSION: "string = 'dev'",
 	TRUSTED_ORIGINS: "string?",
 	DISABLE_RATE_LIMITING: 'string = "false"',
+	C3I_BACKUP_ONE_SHELL_TOKEN: "string?",
+	C3I_BACKUP_ONE_CHANNEL_TOKEN: "string?",
+	C3I_BACKUP_ONE_UNIX_SOCKET: "string?",
+	C3I_BACKUP_ONE_TAURI: "string?",
 }).pipe((s) => ({
 	__prod__: s.NODE_ENV === "production",
 	environment: s.NODE_ENV,
 	serverIp: s.SERVER_IP,
+	listenHost: s.LISTEN_HOST,
 	serverIdleTimeout: s.SERVER_IDLE_TIMEOUT,
 	resticHostname: s.RESTIC_HOSTNAME || getResticHostname(),
 	port: s.PORT,
@@ -69,6 +75,10 @@ const envSchema = type({
 	appVersion: s.APP_VERSION,
 	trustedOrigins: s.TRUSTED_ORIGINS?.split(",").map((origin) => origin.trim()),
 	disableRateLimiting: s.DISABLE_RATE_LIMITING === "true",
+	shellToken: s.C3I_BACKUP_ONE_SHELL_TOKEN,
+	channelToken: s.C3I_BACKUP_ONE_CHANNEL_TOKEN,
+	unixSocket: s.C3I_BACKUP_ONE_UNIX_SOCKET,
+	tauri: s.C3I_BACKUP_ONE_TAURI === "1",
 }));
 
 const parseConfig = (env: unknown) => {
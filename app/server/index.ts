@@ -8,9 +8,13 @@ import { createApp } from "./app";
 import { config } from "./core/config";
 import { runCLI } from "./cli";
 import { runMigrations } from "./modules/lifecycle/migrations";
+import { receiveShellPassword } from "./utils/master-password";
 
 setSchema(schema);
 
+// Needed by the CLI and the migrations below, so it has to arrive first
+await receiveShellPassword();
+
 const cliRun = await runCLI(Bun.argv);
 if (cliRun) {
 	process.exit(0);
@@ -25,22 +29,32 @@ await startup();
 
 export type AppType = typeof app;
 
-process.on("SIGTERM", async () => {
-	logger.info("SIGTERM received, starting graceful shutdown...");
-	await shutdown();
-	process.exit(0);
-});
+let shuttingDown = false;
+
+const gracefulShutdown = async (reason: string) => {
+	if (shuttingDown) return;
+	shuttingDown = true;
 
-process.on("SIGINT", async () => {
-	logger.info("SIGINT received, starting graceful shutdown...");
+	logger.info(`${reason}, starting graceful shutdown...`);
 	await shutdown();
 	process.exit(0);
-});
+};
+
+process.on("SIGTERM", () => gracefulShutdown("SIGTERM received"));
+process.on("SIGINT", () => gracefulShutdown("SIGINT received"));
+
+// The desktop shell closes our stdin to request a shutdown (Windows has no SIGTERM)
+if (process.env.C3I_BACKUP_ONE_TAURI === "1") {
+	process.stdin.on("end", () => gracefulShutdown("stdin closed by desktop shell"));
+	process.stdin.resume();
+}
 
 export default await createHonoServer({
 	app,
 	port: config.port,
 	customBunServer: {
+		// The desktop shell pins the sidecar to loopback or to a socket only it can reach
+		...(config.unixSocket ? { unix: config.unixSocket } : config.listenHost ? { hostname: config.listenHost } : {}),
 		idleTimeout: config.serverIdleTimeout,
 		error(err) {
 			logger.error(`[Bun.serve] Server error: ${err.message}`);
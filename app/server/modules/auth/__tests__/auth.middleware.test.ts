@@ -0,0 +1,177 @@
+import { afterEach, beforeEach, describe, expect, test } from "bun:test";
+import { Hono } from "hono";
+import { config } from "~/server/core/config";
+import { db } from "~/server/db/db";
+import { usersTable } from "~/server/db/schema";
+import { createTestSession } from "~/test/helpers/auth";
+import { createApp } from "~/server/app";
+import { redactChannelToken, requireAuth, requireChannelToken } from "~/server/modules/auth/auth.middleware";
+
+const SHELL_TOKEN = "shell-token-for-tests";
+const CHANNEL_TOKEN = "channel-token-for-tests";
+
+const app = new Hono().use(requireAuth).get("/whoami", (c) => c.json(c.get("user")));
+
+describe("shell token authentication", () => {
+	beforeEach(() => {
+		config.shellToken = SHELL_TOKEN;
+	});
+
+	afterEach(() => {
+		config.shellToken = undefined;
+	});
+
+	test("should act as the registered user with the exact launch token", async () => {
+		const { user } = await createTestSession();
+
+		const res = await app.request("/whoami", {
+			headers: { Authorization: `Bearer ${SHELL_TOKEN}` },
+		});
+
+		expect(res.status).toBe(200);
+		const body = await res.json();
+		expect(body.id).toBe(user.id);
+		expect(body.username).toBe(user.username);
+	});
+
+	test("should reject any other token", async () => {
+		await createTestSession();
+
+		for (const token of ["wrong-token", `${SHELL_TOKEN}x`, SHELL_TOKEN.slice(0, -1), SHELL_TOKEN.toUpperCase(), ""]) {
+			const res = await app.request("/whoami", {
+				headers: { Authorization: `Bearer ${token}` },
+			});
+			expect(res.status).toBe(401);
+			const body = await res.json();
+			expect(body.message).toBe("Invalid or expired session");
+		}
+	});
+
+	test("should reject the token without the Bearer scheme", async () => {
+		await createTestSession();
+
+		for (const authorization of [SHELL_TOKEN, `Basic ${SHELL_TOKEN}`, `bearer ${SHELL_TOKEN}`]) {
+			const res = await app.request("/whoami", {
+				headers: { Authorization: authorization },
+			});
+			expect(res.status).toBe(401);
+		}
+	});
+
+	test("should reject a Bearer token when the sidecar has no launch token", async () => {
+		await createTestSession();
+		config.shellToken = undefined;
+
+		for (const token of [SHELL_TOKEN, "", "undefined"]) {
+			const res = await app.request("/whoami", {
+				headers: { Authorization: `Bearer ${token}` },
+			});
+			expect(res.status).toBe(401);
+		}
+	});
+
+	test("should return 401 when no user has been registered yet", async () => {
+		await db.delete(usersTable);
+
+		const res = await app.request("/whoami", {
+			headers: { Authorization: `Bearer ${SHELL_TOKEN}` },
+		});
+
+		expect(res.status).toBe(401);
+		const body = await res.json();
+		expect(body.message).toBe("No user has been registered yet");
+	});
+
+	test("should still accept a session cookie", async () => {
+		const { token, user } = await createTestSession();
+
+		const res = await app.request("/whoami", {
+			headers: { Cookie: `better-auth.session_token=${token}` },
+		});
+
+		expect(res.status).toBe(200);
+		const body = await res.json();
+		expect(body.id).toBe(user.id);
+	});
+});
+
+describe("channel token", () => {
+	const channelApp = new Hono().use(requireChannelToken).get("/ping", (c) => c.json({ ok: true }));
+
+	beforeEach(() => {
+		config.channelToken = CHANNEL_TOKEN;
+	});
+
+	afterEach(() => {
+		config.channelToken = undefined;
+		config.tauri = false;
+	});
+
+	test("should reject a request without the token", async () => {
+		const res = await channelApp.request("/ping");
+
+		expect(res.status).toBe(401);
+		const body = await res.json();
+		expect(body.message).toBe("Invalid channel token");
+	});
+
+	test("should reject a wrong token", async () => {
+		for (const token of ["wrong-token", `${CHANNEL_TOKEN}x`, CHANNEL_TOKEN.slice(0, -1)]) {
+			const byHeader = await channelApp.request("/ping", { headers: { "X-Channel-Token": token } });
+			expect(byHeader.status).toBe(401);
+
+			const byQuery = await channelApp.request(`/ping?channel_token=${encodeURIComponent(token)}`);
+			expect(byQuery.status).toBe(401);
+		}
+	});
+
+	test("should accept the token in the header", async () => {
+		const res = await channelApp.request("/ping", { headers: { "X-Channel-Token": CHANNEL_TOKEN } });
+
+		expect(res.status).toBe(200);
+	});
+
+	test("should accept the token in the query string", async () => {
+		const res = await channelApp.request(`/ping?channel_token=${CHANNEL_TOKEN}`);
+
+		expect(res.status).toBe(200);
+	});
+
+	test("should not check anything outside the desktop app", async () => {
+		config.channelToken = undefined;
+
+		const res = await channelApp.request("/ping");
+
+		expect(res.status).toBe(200);
+	});
+
+	test("should answer a preflight from the webview with CORS headers", async () => {
+		config.tauri = true;
+		const app = createApp();
+
+		for (const origin of ["tauri://localhost", "http://tauri.localhost"]) {
+			const res = await app.request("/api/v1/volumes", {
+				method: "OPTIONS",
+				headers: {
+					Origin: origin,
+					"Access-Control-Request-Method": "GET",
+					"Access-Control-Request-Headers": "x-channel-token",
+				},
+			});
+
+			expect(res.status).toBe(204);
+			expect(res.headers.get("Access-Control-Allow-Origin")).toBe(origin);
+			expect(res.headers.get("Access-Control-Allow-Credentials")).toBe("true");
+			expect(res.headers.get("Access-Control-Allow-Headers")).toContain("X-Channel-Token");
+		}
+	});
+
+	test("should redact the token from logged URLs", () => {
+		expect(redactChannelToken(`GET /api/v1/events?channel_token=${CHANNEL_TOKEN}`)).toBe(
+			"GET /api/v1/events?channel_token=[redacted]",
+		);
+		expect(redactChannelToken(`http://127.0.0.1:4096/api/v1/events?a=1&channel_token=${CHANNEL_TOKEN}&b=2`)).toBe(
+			"http://127.0.0.1:4096/api/v1/events?a=1&channel_token=[redacted]&b=2",
+		);
+	});
+});
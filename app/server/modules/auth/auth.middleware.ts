@@ -1,5 +1,8 @@
+import { timingSafeEqual } from "node:crypto";
 import { createMiddleware } from "hono/factory";
 import { auth } from "~/lib/auth";
+import { config } from "../../core/config";
+import { db } from "../../db/db";
 
 declare module "hono" {
 	interface ContextVariableMap {
@@ -11,11 +14,73 @@ declare module "hono" {
 	}
 }
 
+const tokensMatch = (provided: string, expected: string) => {
+	const a = Buffer.from(provided);
+	const b = Buffer.from(expected);
+
+	return a.length === b.length && timingSafeEqual(a, b);
+};
+
+/**
+ * Check the per-launch token the desktop shell passes to the sidecar
+ */
+const isShellRequest = (authorization: string | undefined) => {
+	if (!config.shellToken || !authorization?.startsWith("Bearer ")) {
+		return false;
+	}
+
+	return tokensMatch(authorization.slice("Bearer ".length), config.shellToken);
+};
+
+/**
+ * Hide the channel token in a logged URL or request line
+ * EventSource requests carry it in the query string, so it would otherwise end up in the logs
+ */
+export const redactChannelToken = (text: string) => text.replace(/([?&]channel_token=)[^&\s]*/g, "$1[redacted]");
+
+/**
+ * Middleware to restrict the sidecar to the desktop shell and its webview
+ * Every request must carry the per-launch channel token, either as a header or,
+ * for EventSource which cannot set headers, as the `channel_token` query parameter
+ */
+export const requireChannelToken = createMiddleware(async (c, next) => {
+	if (!config.channelToken || c.req.method === "OPTIONS") {
+		await next();
+		return;
+	}
+
+	const provided = c.req.header("x-channel-token") ?? c.req.query("channel_token");
+
+	if (!provided || !tokensMatch(provided, config.channelToken)) {
+		return c.json<unknown>({ message: "Invalid channel token" }, 401);
+	}
+
+	await next();
+});
+
 /**
  * Middleware to require authentication
  * Verifies the session cookie and attaches user to context
+ * The desktop shell authenticates with its launch token and acts as the single registered user
  */
 export const requireAuth = createMiddleware(async (c, next) => {
+	if (isShellRequest(c.req.header("authorization"))) {
+		const user = await db.query.usersTable.findFirst();
+
+		if (!user) {
+			return c.json<unknown>({ message: "No user has been registered yet" }, 401);
+		}
+
+		c.set("user", {
+			id: user.id,
+			username: user.username,
+			hasDownloadedResticPassword: user.hasDownloadedResticPassword,
+		});
+
+		await next();
+		return;
+	}
+
 	const session = await auth.api.getSession({
 		headers: c.req.raw.headers,
 	});
@@ -71,6 +71,13 @@ export const eventsController = new Hono().use(requireAuth).get("/", (c) => {
 			});
 		};
 
+		const onVolumeStatusChanged = async (data: { volumeName: string; status: string }) => {
+			await stream.writeSSE({
+				data: JSON.stringify(data),
+				event: "volume:status_changed",
+			});
+		};
+
 		const onMirrorStarted = async (data: { scheduleId: number; repositoryId: string; repositoryName: string }) => {
 			await stream.writeSSE({
 				data: JSON.stringify(data),
@@ -97,6 +104,7 @@ export const eventsController = new Hono().use(requireAuth).get("/", (c) => {
 		serverEvents.on("volume:mounted", onVolumeMounted);
 		serverEvents.on("volume:unmounted", onVolumeUnmounted);
 		serverEvents.on("volume:updated", onVolumeUpdated);
+		serverEvents.on("volume:status_changed", onVolumeStatusChanged);
 		serverEvents.on("mirror:started", onMirrorStarted);
 		serverEvents.on("mirror:completed", onMirrorCompleted);
 
@@ -111,6 +119,7 @@ export const eventsController = new Hono().use(requireAuth).get("/", (c) => {
 			serverEvents.off("volume:mounted", onVolumeMounted);
 			serverEvents.off("volume:unmounted", onVolumeUnmounted);
 			serverEvents.off("volume:updated", onVolumeUpdated);
+			serverEvents.off("volume:status_changed", onVolumeStatusChanged);
 			serverEvents.off("mirror:started", onMirrorStarted);
 			serverEvents.off("mirror:completed", onMirrorCompleted);
 		});
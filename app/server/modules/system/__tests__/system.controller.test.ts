@@ -1,6 +1,10 @@
-import { test, describe, expect } from "bun:test";
+import { test, describe, expect, afterEach, mock, spyOn } from "bun:test";
+import { eq } from "drizzle-orm";
 import { createApp } from "~/server/app";
 import { createTestSession } from "~/test/helpers/auth";
+import { db } from "~/server/db/db";
+import { usersTable } from "~/server/db/schema";
+import * as masterPasswordModule from "~/server/utils/master-password";
 
 const app = createApp();
 
@@ -84,4 +88,45 @@ describe("system security", () => {
 			expect(body.message).toBe("Invalid password");
 		});
 	});
+
+	describe("restic password download", () => {
+		afterEach(() => {
+			mock.restore();
+		});
+
+		const download = (token: string) =>
+			app.request("/api/v1/system/restic-password", {
+				method: "POST",
+				headers: {
+					Cookie: `better-auth.session_token=${token}`,
+					"Content-Type": "application/json",
+				},
+				body: JSON.stringify({ password: "password123" }),
+			});
+
+		test("should return the master password and remember the download", async () => {
+			const { token, user } = await createTestSession();
+			await db.update(usersTable).set({ hasDownloadedResticPassword: false }).where(eq(usersTable.id, user.id));
+			spyOn(masterPasswordModule, "getMasterPassword").mockResolvedValue("from-the-secret-store");
+
+			const res = await download(token);
+
+			expect(res.status).toBe(200);
+			expect(res.headers.get("Content-Disposition")).toBe('attachment; filename="restic.pass"');
+			expect(await res.text()).toBe("from-the-secret-store");
+			const updated = await db.query.usersTable.findFirst({ where: eq(usersTable.id, user.id) });
+			expect(updated?.hasDownloadedResticPassword).toBe(true);
+		});
+
+		test("should return 500 when the master password cannot be read", async () => {
+			const { token } = await createTestSession();
+			spyOn(masterPasswordModule, "getMasterPassword").mockRejectedValue(new Error("ENOENT"));
+
+			const res = await download(token);
+
+			expect(res.status).toBe(500);
+			const body = await res.json();
+			expect(body.message).toBe("Failed to read the Restic password");
+		});
+	});
 });
@@ -10,7 +10,7 @@ import {
 } from "./system.dto";
 import { systemService } from "./system.service";
 import { requireAuth } from "../auth/auth.middleware";
-import { RESTIC_PASS_FILE } from "../../core/constants";
+import { getMasterPassword } from "../../utils/master-password";
 import { db } from "../../db/db";
 import { usersTable } from "../../db/schema";
 import { eq } from "drizzle-orm";
@@ -42,8 +42,7 @@ export const systemController = new Hono()
 			}
 
 			try {
-				const file = Bun.file(RESTIC_PASS_FILE);
-				const content = await file.text();
+				const content = await getMasterPassword();
 
 				await db.update(usersTable).set({ hasDownloadedResticPassword: true }).where(eq(usersTable.id, user.id));
 
@@ -52,7 +51,7 @@ export const systemController = new Hono()
 
 				return c.text(content);
 			} catch (_error) {
-				return c.json({ message: "Failed to read Restic password file" }, 500);
+				return c.json({ message: "Failed to read the Restic password" }, 500);
 			}
 		},
 	);
@@ -0,0 +1,101 @@
+import { afterEach, beforeEach, describe, expect, mock, spyOn, test } from "bun:test";
+import * as masterPasswordModule from "~/server/utils/master-password";
+
+// The test setup stubs this module out for everything else; the query string loads the real one
+const realCrypto = "../crypto.ts?real";
+const { cryptoUtils } = (await import(realCrypto)) as typeof import("~/server/utils/crypto");
+
+const useMasterPassword = (password: string) => {
+	spyOn(masterPasswordModule, "getMasterPassword").mockResolvedValue(password);
+};
+
+beforeEach(() => {
+	useMasterPassword("master-password");
+});
+
+afterEach(() => {
+	mock.restore();
+});
+
+describe("sealed secrets", () => {
+	test("should encrypt a secret and decrypt it back", async () => {
+		const sealed = await cryptoUtils.sealSecret("s3cr3t-access-key");
+
+		expect(sealed).toStartWith("encv1:");
+		expect(sealed).not.toContain("s3cr3t-access-key");
+		expect(await cryptoUtils.resolveSecret(sealed)).toBe("s3cr3t-access-key");
+	});
+
+	test("should use a new salt for every secret", async () => {
+		const first = await cryptoUtils.sealSecret("same value");
+		const second = await cryptoUtils.sealSecret("same value");
+
+		expect(first).not.toBe(second);
+	});
+
+	test("should not decrypt with another master password", async () => {
+		const sealed = await cryptoUtils.sealSecret("s3cr3t-access-key");
+
+		useMasterPassword("another-password");
+
+		await expect(cryptoUtils.resolveSecret(sealed)).rejects.toThrow();
+	});
+
+	test("should decrypt with the master password sent by the shell", async () => {
+		useMasterPassword("contraseña del almacén");
+		const sealed = await cryptoUtils.sealSecret("s3cr3t-access-key");
+
+		expect(await cryptoUtils.resolveSecret(sealed)).toBe("s3cr3t-access-key");
+	});
+
+	test("should keep references, sealed and empty values as they are", async () => {
+		const sealed = await cryptoUtils.sealSecret("value");
+
+		for (const value of ["env://AWS_SECRET", "file://aws-secret", sealed, ""]) {
+			expect(await cryptoUtils.sealSecret(value)).toBe(value);
+		}
+	});
+});
+
+describe("secret references", () => {
+	afterEach(() => {
+		delete process.env.C3I_TEST_SECRET;
+	});
+
+	test("should read env:// references from the environment", async () => {
+		process.env.C3I_TEST_SECRET = "from-the-environment";
+
+		expect(await cryptoUtils.resolveSecret("env://C3I_TEST_SECRET")).toBe("from-the-environment");
+		await expect(cryptoUtils.resolveSecret("env://C3I_MISSING_SECRET")).rejects.toThrow(
+			"Environment variable not set: C3I_MISSING_SECRET",
+		);
+	});
+
+	test("should only read file:// references from the secrets directory", async () => {
+		for (const reference of ["file://../etc/passwd", "file://nested/secret", "file://a\\b"]) {
+			await expect(cryptoUtils.resolveSecret(reference)).rejects.toThrow("must be a single path segment");
+		}
+	});
+
+	test("should return plain values unchanged", async () => {
+		expect(await cryptoUtils.resolveSecret("plain-value")).toBe("plain-value");
+	});
+});
+
+describe("derived secrets", () => {
+	test("should derive the same key for the same label and master password", async () => {
+		const key = await cryptoUtils.deriveSecret("better-auth");
+
+		expect(key).toMatch(/^[0-9a-f]{64}$/);
+		expect(await cryptoUtils.deriveSecret("better-auth")).toBe(key);
+		expect(await cryptoUtils.deriveSecret("another-label")).not.toBe(key);
+	});
+
+	test("should derive another key from another master password", async () => {
+		const key = await cryptoUtils.deriveSecret("better-auth");
+
+		useMasterPassword("another-password");
+
+		expect(await cryptoUtils.deriveSecret("better-auth")).not.toBe(key);
+	});
+});
@@ -0,0 +1,58 @@
+import { afterEach, describe, expect, test } from "bun:test";
+import { PassThrough } from "node:stream";
+import {
+	decodeShellPassword,
+	isShellPassword,
+	readFirstLine,
+	receiveShellPassword,
+} from "~/server/utils/master-password";
+
+describe("master password", () => {
+	afterEach(() => {
+		delete process.env.C3I_BACKUP_ONE_PASSWORD_STDIN;
+	});
+
+	test("should read the first line even when it arrives in pieces", async () => {
+		const input = new PassThrough();
+		const line = readFirstLine(input);
+
+		input.write("6869");
+		input.write("6a6b\nnot part of");
+		input.write(" the password\n");
+
+		expect(await line).toBe("68696a6b");
+	});
+
+	test("should fail when the input closes before a full line", async () => {
+		const input = new PassThrough();
+		const line = readFirstLine(input);
+
+		input.end("6869");
+
+		await expect(line).rejects.toThrow("stdin was closed before the master password was received");
+	});
+
+	test("should stop listening once the line is read", async () => {
+		const input = new PassThrough();
+		const line = readFirstLine(input);
+
+		input.write("6869\n");
+		await line;
+
+		expect(input.listenerCount("data")).toBe(0);
+		expect(input.listenerCount("end")).toBe(0);
+	});
+
+	test("should decode the hex encoded password exactly", () => {
+		const password = " contraseña con espacios \t";
+
+		expect(decodeShellPassword(Buffer.from(password, "utf8").toString("hex"))).toBe(password);
+		expect(decodeShellPassword(`${Buffer.from("secret").toString("hex")}\r`)).toBe("secret");
+	});
+
+	test("should not wait for stdin unless the shell sends the password", async () => {
+		await receiveShellPassword();
+
+		expect(isShellPassword()).toBe(false);
+	});
+});
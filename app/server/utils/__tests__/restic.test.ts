@@ -0,0 +1,96 @@
+import { afterEach, beforeEach, describe, expect, mock, spyOn, test } from "bun:test";
+import type { RepositoryConfig } from "~/schemas/restic";
+import { RESTIC_PASS_FILE } from "~/server/core/constants";
+import { buildEnv, cleanupTemporaryKeys, restic } from "~/server/utils/restic";
+import * as masterPasswordModule from "~/server/utils/master-password";
+import * as spawnModule from "~/server/utils/spawn";
+
+const localRepository = (name: string, extra: Partial<RepositoryConfig> = {}) =>
+	({ backend: "local", name, path: "/srv/repositories", ...extra }) as RepositoryConfig;
+
+const spawnMock = mock(() => Promise.resolve({ exitCode: 0, stdout: "", stderr: "" }));
+
+const spawnedEnv = () => {
+	const [options] = spawnMock.mock.calls[0] as unknown as [{ env: Record<string, string> }];
+	return options.env;
+};
+
+beforeEach(() => {
+	spawnMock.mockClear();
+	spyOn(spawnModule, "safeSpawn").mockImplementation(spawnMock);
+});
+
+afterEach(() => {
+	mock.restore();
+});
+
+describe("restic password", () => {
+	test("should point restic to the password file", async () => {
+		const env = await buildEnv(localRepository("docs"));
+
+		expect(env.RESTIC_PASSWORD_FILE).toBe(RESTIC_PASS_FILE);
+		expect(env.RESTIC_PASSWORD).toBeUndefined();
+	});
+
+	test("should pass the password from the desktop shell in the environment", async () => {
+		spyOn(masterPasswordModule, "isShellPassword").mockReturnValue(true);
+		spyOn(masterPasswordModule, "getMasterPassword").mockResolvedValue("from-the-secret-store\n");
+
+		const env = await buildEnv(localRepository("docs"));
+
+		expect(env.RESTIC_PASSWORD).toBe("from-the-secret-store");
+		expect(env.RESTIC_PASSWORD_FILE).toBeUndefined();
+	});
+
+	test("should prefer the custom password of an existing repository", async () => {
+		spyOn(masterPasswordModule, "isShellPassword").mockReturnValue(true);
+
+		const env = await buildEnv(
+			localRepository("imported", { isExistingRepository: true, customPassword: "imported-password" }),
+		);
+
+		expect(env.RESTIC_PASSWORD).toBeUndefined();
+		expect(await Bun.file(env.RESTIC_PASSWORD_FILE).text()).toBe("imported-password");
+
+		await cleanupTemporaryKeys(env);
+	});
+});
+
+describe("restic copy", () => {
+	test("should pass the source password only in the RESTIC_FROM_ variables", async () => {
+		spyOn(masterPasswordModule, "isShellPassword").mockReturnValue(true);
+		spyOn(masterPasswordModule, "getMasterPassword").mockResolvedValue("master-password");
+
+		await restic.copy(localRepository("source"), localRepository("mirror"), {});
+
+		const env = spawnedEnv();
+		expect(env.RESTIC_FROM_PASSWORD).toBe("master-password");
+		expect(env.RESTIC_PASSWORD).toBe("master-password");
+		expect(env.RESTIC_FROM_PASSWORD_FILE).toBeUndefined();
+		expect(env.RESTIC_PASSWORD_FILE).toBeUndefined();
+	});
+
+	test("should keep a custom source password apart from the destination's", async () => {
+		spyOn(masterPasswordModule, "isShellPassword").mockReturnValue(true);
+		spyOn(masterPasswordModule, "getMasterPassword").mockResolvedValue("master-password");
+
+		const source = localRepository("imported", { isExistingRepository: true, customPassword: "imported-password" });
+		await restic.copy(source, localRepository("mirror"), {});
+
+		const env = spawnedEnv();
+		expect(env.RESTIC_FROM_PASSWORD_FILE).toStartWith("/tmp/c3i-backup-one-pass-");
+		expect(env.RESTIC_FROM_PASSWORD).toBeUndefined();
+		expect(env.RESTIC_PASSWORD).toBe("master-password");
+		expect(env.RESTIC_PASSWORD_FILE).toBeUndefined();
+	});
+
+	test("should use the password files of both repositories", async () => {
+		await restic.copy(localRepository("source"), localRepository("mirror"), {});
+
+		const env = spawnedEnv();
+		expect(env.RESTIC_FROM_PASSWORD_FILE).toBe(RESTIC_PASS_FILE);
+		expect(env.RESTIC_PASSWORD_FILE).toBe(RESTIC_PASS_FILE);
+		expect(env.RESTIC_FROM_PASSWORD).toBeUndefined();
+		expect(env.RESTIC_PASSWORD).toBeUndefined();
+	});
+});
@@ -1,8 +1,8 @@
 import crypto from "node:crypto";
 import fs from "node:fs/promises";
 import path from "node:path";
-import { RESTIC_PASS_FILE } from "../core/constants";
 import { isNodeJSErrnoException } from "./fs";
+import { getMasterPassword } from "./master-password";
 import { promisify } from "node:util";
 
 const hkdf = promisify(crypto.hkdf);
@@ -96,7 +96,7 @@ const encrypt = async (data: string) => {
 		return data;
 	}
 
-	const secret = await Bun.file(RESTIC_PASS_FILE).text();
+	const secret = await getMasterPassword();
 
 	const salt = crypto.randomBytes(16);
 	const key = crypto.pbkdf2Sync(secret, salt, 100000, keyLength, "sha256");
@@ -118,7 +118,7 @@ const decrypt = async (encryptedData: string) => {
 		return encryptedData;
 	}
 
-	const secret = (await Bun.file(RESTIC_PASS_FILE).text()).trim();
+	const secret = (await getMasterPassword()).trim();
 
 	const parts = encryptedData.split(":").slice(1); // Remove prefix
 	const saltHex = parts.shift() as string;
@@ -187,7 +187,7 @@ const sealSecret = async (value: string): Promise<string> => {
 };
 
 async function deriveSecret(label: string) {
-	const masterSecret = await Bun.file(RESTIC_PASS_FILE).text();
+	const masterSecret = await getMasterPassword();
 
 	const derivedKey = await hkdf("sha256", masterSecret, "", label, 32);
 
@@ -50,10 +50,14 @@ const getDefaultLevel = () => {
 	return isProd ? "info" : "debug";
 };
 
+// Under the desktop shell, stdout is parsed line by line and written to the shell's
+// rotated sidecar log, so it gets the JSON file format and no files of its own
+const isTauri = process.env.C3I_BACKUP_ONE_TAURI === "1";
+
 // Get logs directory based on environment
 const getLogsDir = (): string => {
 	// Tauri mode - Tauri passes the platform-specific logs directory
-	if (process.env.C3I_BACKUP_ONE_TAURI === "1" && process.env.C3I_BACKUP_ONE_LOGS_DIR) {
+	if (isTauri && process.env.C3I_BACKUP_ONE_LOGS_DIR) {
 		return process.env.C3I_BACKUP_ONE_LOGS_DIR;
 	}
 
@@ -82,30 +86,34 @@ const logTransports: any[] = [
 	// Console transport (always enabled)
 	new transports.Console({
 		level: process.env.LOG_LEVEL || getDefaultLevel(),
-		format: consoleFormat,
-	}),
-
-	// File transport for all logs (with rotation)
-	new transports.File({
-		filename: path.join(logsDir, "combined.log"),
-		level: process.env.LOG_LEVEL || getDefaultLevel(),
-		format: fileFormat,
-		maxsize: 10 * 1024 * 1024, // 10MB
-		maxFiles: 5,
-		tailable: true,
-	}),
-
-	// Separate file for errors only
-	new transports.File({
-		filename: path.join(logsDir, "error.log"),
-		level: "error",
-		format: fileFormat,
-		maxsize: 10 * 1024 * 1024, // 10MB
-		maxFiles: 5,
-		tailable: true,
+		format: isTauri ? fileFormat : consoleFormat,
 	}),
 ];
 
+if (!isTauri) {
+	logTransports.push(
+		// File transport for all logs (with rotation)
+		new transports.File({
+			filename: path.join(logsDir, "combined.log"),
+			level: process.env.LOG_LEVEL || getDefaultLevel(),
+			format: fileFormat,
+			maxsize: 10 * 1024 * 1024, // 10MB
+			maxFiles: 5,
+			tailable: true,
+		}),
+
+		// Separate file for errors only
+		new transports.File({
+			filename: path.join(logsDir, "error.log"),
+			level: "error",
+			format: fileFormat,
+			maxsize: 10 * 1024 * 1024, // 10MB
+			maxFiles: 5,
+			tailable: true,
+		}),
+	);
+}
+
 const winstonLogger = createLogger({
 	level: process.env.LOG_LEVEL || getDefaultLevel(),
 	format: format.json(),
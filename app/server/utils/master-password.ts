@@ -0,0 +1,72 @@
+import { RESTIC_PASS_FILE } from "../core/constants";
+
+/**
+ * The master password: the restic password of the repositories created here and the
+ * secret the encryption keys of stored credentials are derived from.
+ *
+ * The desktop shell keeps it in the system secret store and sends it, hex encoded,
+ * as the first line of our stdin. Otherwise it lives in RESTIC_PASS_FILE.
+ */
+let shellPassword: string | null = null;
+
+/**
+ * First line of `input`, without its line break
+ */
+export const readFirstLine = (input: NodeJS.ReadableStream) =>
+	new Promise<string>((resolve, reject) => {
+		let buffered = "";
+
+		const cleanup = () => {
+			input.off("data", onData);
+			input.off("end", onEnd);
+			input.pause();
+		};
+		const onData = (chunk: Buffer) => {
+			buffered += chunk.toString("utf8");
+			const newline = buffered.indexOf("\n");
+			if (newline !== -1) {
+				cleanup();
+				resolve(buffered.slice(0, newline));
+			}
+		};
+		const onEnd = () => {
+			cleanup();
+			reject(new Error("stdin was closed before the master password was received"));
+		};
+
+		input.on("data", onData);
+		input.on("end", onEnd);
+		input.resume();
+	});
+
+/**
+ * The password in a line sent by the desktop shell
+ */
+export const decodeShellPassword = (line: string) => Buffer.from(line.trim(), "hex").toString("utf8");
+
+/**
+ * Wait for the password the desktop shell sends over stdin, when it said it would
+ */
+export const receiveShellPassword = async () => {
+	if (process.env.C3I_BACKUP_ONE_PASSWORD_STDIN !== "1") {
+		return;
+	}
+
+	shellPassword = decodeShellPassword(await readFirstLine(process.stdin));
+};
+
+/**
+ * Whether the password came from the desktop shell, so there is no password file
+ */
+export const isShellPassword = () => shellPassword !== null;
+
+/**
+ * The master password, exactly as stored (the password file is not trimmed)
+ */
+export const getMasterPassword = async () => {
+	if (shellPassword !== null) {
+		return shellPassword;
+	}
+
+	return Bun.file(RESTIC_PASS_FILE).text();
+};
@@ -8,6 +8,7 @@ import { REPOSITORY_BASE, RESTIC_PASS_FILE, DEFAULT_EXCLUDES, RESTIC_CACHE_DIR }
 import { config as appConfig } from "../core/config";
 import { logger } from "./logger";
 import { cryptoUtils } from "./crypto";
+import { getMasterPassword, isShellPassword } from "./master-password";
 import type { RetentionPolicy } from "../modules/backups/backups.dto";
 import { safeSpawn } from "./spawn";
 import type { CompressionMode, RepositoryConfig, OverwriteMode } from "~/schemas/restic";
@@ -63,6 +64,11 @@ const snapshotInfoSchema = type({
 });
 
 const ensurePassfile = async () => {
+	// The desktop shell keeps the password in the system secret store
+	if (isShellPassword()) {
+		return;
+	}
+
 	await fs.mkdir(path.dirname(RESTIC_PASS_FILE), { recursive: true });
 
 	try {
@@ -118,6 +124,9 @@ export const buildEnv = async (config: RepositoryConfig) => {
 
 		await fs.writeFile(passwordFilePath, decryptedPassword, { mode: 0o600 });
 		env.RESTIC_PASSWORD_FILE = passwordFilePath;
+	} else if (isShellPassword()) {
+		// restic trims the password file, but not the variable
+		env.RESTIC_PASSWORD = (await getMasterPassword()).trim();
 	} else {
 		env.RESTIC_PASSWORD_FILE = RESTIC_PASS_FILE;
 	}
@@ -829,10 +838,12 @@ const copy = async (
 	const sourceEnv = await buildEnv(sourceConfig);
 	const destEnv = await buildEnv(destConfig);
 
+	// The source password goes in the RESTIC_FROM_ variables, never in the destination's
+	const { RESTIC_PASSWORD: fromPassword, RESTIC_PASSWORD_FILE: fromPasswordFile, ...sourceOptions } = sourceEnv;
 	const env: Record<string, string> = {
-		...sourceEnv,
+		...sourceOptions,
 		...destEnv,
-		RESTIC_FROM_PASSWORD_FILE: sourceEnv.RESTIC_PASSWORD_FILE,
+		...(fromPasswordFile ? { RESTIC_FROM_PASSWORD_FILE: fromPasswordFile } : { RESTIC_FROM_PASSWORD: fromPassword }),
 	};
 
 	const args: string[] = ["--repo", destRepoUrl, "copy", "--from-repo", sourceRepoUrl];
@@ -0,0 +1,66 @@
+//! Tauri's per-app directories, resolved without a running `tauri::App` for the
+//! headless mode and the command-line interface.
+
+use std::path::PathBuf;
+use tauri::{Env, PackageInfo};
+
+#[derive(Clone)]
+pub struct AppDirs {
+    identifier: String,
+    package_info: PackageInfo,
+}
+
+impl AppDirs {
+    pub fn new(identifier: String, package_info: PackageInfo) -> Self {
+        Self {
+            identifier,
+            package_info,
+        }
+    }
+
+    pub fn package_info(&self) -> &PackageInfo {
+        &self.package_info
+    }
+
+    /// `base` joined with the app identifier, like Tauri's `app_*_dir` paths
+    fn app_dir(&self, base: Option<PathBuf>) -> Result<PathBuf, String> {
+        base.map(|dir| crate::normalize_windows_path(dir).join(&self.identifier))
+            .ok_or_else(|| "Failed to resolve the user's directories".to_string())
+    }
+
+    pub fn config_dir(&self) -> Result<PathBuf, String> {
+        self.app_dir(dirs::config_dir())
+    }
+
+    pub fn data_dir(&self) -> Result<PathBuf, String> {
+        self.app_dir(dirs::data_dir())
+    }
+
+    pub fn local_data_dir(&self) -> Result<PathBuf, String> {
+        self.app_dir(dirs::data_local_dir())
+    }
+
+    pub fn log_dir(&self) -> Result<PathBuf, String> {
+        if cfg!(target_os = "macos") {
+            dirs::home_dir()
+                .map(|home| home.join("Library").join("Logs").join(&self.identifier))
+                .ok_or_else(|| "Failed to resolve the user's home directory".to_string())
+        } else {
+            self.local_data_dir().map(|dir| dir.join("logs"))
+        }
+    }
+
+    pub fn resource_dir(&self) -> Result<PathBuf, String> {
+        tauri::utils::platform::resource_dir(&self.package_info, &Env::default()).map_err(|e| e.to_string())
+    }
+
+    /// The shell's log file, named after the product
+    pub fn log_file(&self) -> Result<PathBuf, String> {
+        self.log_dir().map(|dir| dir.join(format!("{}.log", self.package_info.name)))
+    }
+
+    /// The backend's output, as written by the shell
+    pub fn sidecar_log_file(&self) -> Result<PathBuf, String> {
+        self.log_dir().map(|dir| dir.join(crate::logging::SIDECAR_LOG_FILE))
+    }
+}
@@ -0,0 +1,834 @@
+//! Typed client for the sidecar's REST API (see `app/server/app.ts`), shared by
+//! the tray, health monitoring and the backend event subscription.
+//!
+//! Response types only carry the fields the shell uses; unknown fields are ignored
+//! so the backend can grow without breaking the shell.
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::TokioIo;
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN, AUTHORIZATION, HOST, ORIGIN,
+};
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::http::Request;
+use tauri::{AppHandle, Manager};
+
+use crate::AppState;
+
+/// Routes this client calls, checked against `/api/v1/openapi.json` after startup
+const ROUTES: &[(&str, &str)] = &[
+    ("get", "/api/v1/volumes"),
+    ("post", "/api/v1/volumes/{name}/mount"),
+    ("post", "/api/v1/volumes/{name}/unmount"),
+    ("get", "/api/v1/repositories"),
+    ("get", "/api/v1/repositories/{id}"),
+    ("get", "/api/v1/backups"),
+    ("get", "/api/v1/backups/{scheduleId}"),
+    ("post", "/api/v1/backups/{scheduleId}/run"),
+    ("post", "/api/v1/backups/{scheduleId}/stop"),
+    ("get", "/api/v1/notifications/destinations"),
+    ("post", "/api/v1/notifications/destinations/{id}/test"),
+    ("get", "/api/v1/system/info"),
+    ("get", "/api/v1/system/updates"),
+    ("get", "/api/v1/events"),
+];
+
+#[derive(Debug)]
+pub enum BackendError {
+    /// The sidecar has no URL yet (starting, crashed or stopped)
+    NotRunning,
+    /// The request never got an answer
+    Transport(String),
+    /// The backend answered with an error status and its `{ message }` body
+    Status { status: u16, message: String },
+    /// The response did not have the expected shape
+    Decode(String),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::NotRunning => write!(f, "backend is not running"),
+            BackendError::Transport(e) => write!(f, "failed to reach backend: {}", e),
+            BackendError::Status { status, message } => write!(f, "backend error {}: {}", status, message),
+            BackendError::Decode(e) => write!(f, "unexpected backend response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl From<reqwest::Error> for BackendError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            BackendError::Decode(e.to_string())
+        } else {
+            BackendError::Transport(e.to_string())
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Volume {
+    pub id: i64,
+    pub short_id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub status: String,
+    pub last_error: Option<String>,
+    pub auto_remount: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Repository {
+    pub id: String,
+    pub short_id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub status: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub last_checked: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSchedule {
+    pub id: i64,
+    pub short_id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub cron_expression: String,
+    /// Unix timestamp in milliseconds
+    pub last_backup_at: Option<u64>,
+    /// One of `success`, `error`, `in_progress` or `warning`
+    pub last_backup_status: Option<String>,
+    pub last_backup_error: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub next_backup_at: Option<u64>,
+    pub volume: Volume,
+    pub repository: Repository,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationDestination {
+    pub id: i64,
+    pub name: String,
+    pub enabled: bool,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub rclone: bool,
+    pub sys_admin: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SystemInfo {
+    pub capabilities: Capabilities,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub current_version: String,
+    pub latest_version: String,
+    pub has_update: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupEvent {
+    pub schedule_id: i64,
+    pub volume_name: String,
+    pub repository_name: String,
+}
+
+/// restic status fields are forwarded in snake_case next to the camelCase ids
+#[derive(Clone, Debug, Deserialize)]
+pub struct BackupProgressEvent {
+    #[serde(rename = "scheduleId")]
+    pub schedule_id: i64,
+    #[serde(rename = "volumeName")]
+    pub volume_name: String,
+    #[serde(rename = "repositoryName")]
+    pub repository_name: String,
+    pub seconds_elapsed: f64,
+    /// Fraction between 0 and 1
+    pub percent_done: f64,
+    pub total_files: u64,
+    pub files_done: u64,
+    pub total_bytes: u64,
+    pub bytes_done: u64,
+    #[serde(default)]
+    pub current_files: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupCompletedEvent {
+    pub schedule_id: i64,
+    pub volume_name: String,
+    pub repository_name: String,
+    /// One of `success`, `error`, `stopped` or `warning`
+    pub status: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorEvent {
+    pub schedule_id: i64,
+    pub repository_id: String,
+    pub repository_name: String,
+    /// Only set on `mirror:completed`: `success` or `error`
+    pub status: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeEvent {
+    pub volume_name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeStatusEvent {
+    pub volume_name: String,
+    pub status: String,
+}
+
+/// Events broadcast on `/api/v1/events` (see `app/server/core/events.ts`)
+#[derive(Clone, Debug)]
+pub enum ServerEvent {
+    Connected,
+    Heartbeat,
+    BackupStarted(BackupEvent),
+    BackupProgress(BackupProgressEvent),
+    BackupCompleted(BackupCompletedEvent),
+    MirrorStarted(MirrorEvent),
+    MirrorCompleted(MirrorEvent),
+    VolumeMounted(VolumeEvent),
+    VolumeUnmounted(VolumeEvent),
+    VolumeUpdated(VolumeEvent),
+    VolumeStatusChanged(VolumeStatusEvent),
+    /// An event this shell does not know about yet
+    Other(String),
+}
+
+impl ServerEvent {
+    fn parse(event: &str, data: &str) -> Result<Self, BackendError> {
+        fn payload<T: DeserializeOwned>(event: &str, data: &str) -> Result<T, BackendError> {
+            serde_json::from_str(data).map_err(|e| BackendError::Decode(format!("{} event: {}", event, e)))
+        }
+
+        Ok(match event {
+            "connected" => ServerEvent::Connected,
+            "heartbeat" => ServerEvent::Heartbeat,
+            "backup:started" => ServerEvent::BackupStarted(payload(event, data)?),
+            "backup:progress" => ServerEvent::BackupProgress(payload(event, data)?),
+            "backup:completed" => ServerEvent::BackupCompleted(payload(event, data)?),
+            "mirror:started" => ServerEvent::MirrorStarted(payload(event, data)?),
+            "mirror:completed" => ServerEvent::MirrorCompleted(payload(event, data)?),
+            "volume:mounted" => ServerEvent::VolumeMounted(payload(event, data)?),
+            "volume:unmounted" => ServerEvent::VolumeUnmounted(payload(event, data)?),
+            "volume:updated" => ServerEvent::VolumeUpdated(payload(event, data)?),
+            "volume:status_changed" => ServerEvent::VolumeStatusChanged(payload(event, data)?),
+            other => ServerEvent::Other(other.to_string()),
+        })
+    }
+}
+
+/// A backend event as it came off the wire, before decoding its payload
+#[derive(Clone, Debug, Serialize)]
+pub struct RawServerEvent {
+    pub event: String,
+    pub data: String,
+}
+
+impl RawServerEvent {
+    pub fn parse(&self) -> Result<ServerEvent, BackendError> {
+        ServerEvent::parse(&self.event, &self.data)
+    }
+}
+
+/// An open `/api/v1/events` subscription
+pub struct EventStream {
+    body: ResponseBody,
+    parser: SseParser,
+    pending: VecDeque<RawServerEvent>,
+}
+
+impl EventStream {
+    /// Wait for the next event; `Ok(None)` means the backend closed the stream.
+    /// Events with a malformed payload are reported as `Err` without ending the stream.
+    pub async fn next(&mut self) -> Result<Option<ServerEvent>, BackendError> {
+        match self.next_raw().await? {
+            Some(raw) => raw.parse().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `next`, but without decoding the payload
+    pub async fn next_raw(&mut self) -> Result<Option<RawServerEvent>, BackendError> {
+        loop {
+            if let Some(raw) = self.pending.pop_front() {
+                return Ok(Some(raw));
+            }
+
+            match self.body.chunk().await? {
+                Some(chunk) => {
+                    let messages = self.parser.push(&chunk);
+                    self.pending
+                        .extend(messages.into_iter().map(|(event, data)| RawServerEvent { event, data }));
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Incremental parser for `text/event-stream` bodies that may be split at any byte
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    event: String,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut messages = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let raw: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    messages.push((std::mem::take(&mut self.event), self.data.join("\n")));
+                    self.data.clear();
+                }
+                self.event.clear();
+            } else if let Some(value) = line.strip_prefix("event:") {
+                self.event = value.trim_start().to_string();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+            // Comments and the id/retry fields are not used by the backend
+        }
+
+        messages
+    }
+}
+
+/// Where the sidecar listens
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackendEndpoint {
+    /// HTTP on the loopback interface
+    Tcp(u16),
+    /// HTTP over a Unix domain socket, or a named pipe on Windows
+    LocalSocket(PathBuf),
+}
+
+impl BackendEndpoint {
+    /// Base URL the webview uses. Local sockets are out of the webview's reach, so
+    /// its requests go through the shell's `c3i-backend` URI scheme instead.
+    pub fn webview_url(&self) -> String {
+        match self {
+            Self::Tcp(port) => format!("http://127.0.0.1:{}", port),
+            Self::LocalSocket(_) if cfg!(windows) => format!("http://{}.localhost", PROXY_SCHEME),
+            Self::LocalSocket(_) => format!("{}://localhost", PROXY_SCHEME),
+        }
+    }
+}
+
+impl fmt::Display for BackendEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(port) => write!(f, "127.0.0.1:{}", port),
+            Self::LocalSocket(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// URI scheme the webview uses to reach a sidecar listening on a local socket
+pub const PROXY_SCHEME: &str = "c3i-backend";
+
+/// Header carrying the per-launch channel token the sidecar requires on every request
+const CHANNEL_TOKEN_HEADER: &str = "x-channel-token";
+
+#[derive(Clone)]
+enum Transport {
+    Tcp { http: reqwest::Client, origin: String },
+    LocalSocket(PathBuf),
+}
+
+struct Response {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: ResponseBody,
+}
+
+enum ResponseBody {
+    Tcp(reqwest::Response),
+    LocalSocket(hyper::body::Incoming),
+}
+
+impl ResponseBody {
+    async fn chunk(&mut self) -> Result<Option<Bytes>, BackendError> {
+        match self {
+            Self::Tcp(response) => Ok(response.chunk().await?),
+            Self::LocalSocket(body) => {
+                while let Some(frame) = body.frame().await {
+                    let frame = frame.map_err(|e| BackendError::Transport(e.to_string()))?;
+                    if let Ok(data) = frame.into_data() {
+                        return Ok(Some(data));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    async fn bytes(mut self) -> Result<Vec<u8>, BackendError> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes)
+    }
+
+    async fn json<T: DeserializeOwned>(self) -> Result<T, BackendError> {
+        let bytes = self.bytes().await?;
+        serde_json::from_slice(&bytes).map_err(|e| BackendError::Decode(e.to_string()))
+    }
+}
+
+#[derive(Clone)]
+pub struct BackendClient {
+    transport: Transport,
+    token: String,
+    channel_token: String,
+}
+
+impl BackendClient {
+    pub fn new(endpoint: &BackendEndpoint, token: &str, channel_token: &str) -> Self {
+        let transport = match endpoint {
+            BackendEndpoint::Tcp(port) => Transport::Tcp {
+                http: reqwest::Client::new(),
+                origin: format!("http://127.0.0.1:{}", port),
+            },
+            BackendEndpoint::LocalSocket(path) => Transport::LocalSocket(path.clone()),
+        };
+
+        Self {
+            transport,
+            token: token.to_string(),
+            channel_token: channel_token.to_string(),
+        }
+    }
+
+    /// Client for the sidecar managed by this app, if it is up
+    pub fn from_app(app: &AppHandle) -> Result<Self, BackendError> {
+        Self::from_state(&app.state::<AppState>())
+    }
+
+    pub(crate) fn from_state(state: &AppState) -> Result<Self, BackendError> {
+        let endpoint = crate::backend_endpoint(state).ok_or(BackendError::NotRunning)?;
+        Ok(Self::new(&endpoint, &state.shell_token, &state.channel_token))
+    }
+
+    /// Build a request path from path segments, percent-encoding each of them
+    fn path(segments: &[&str]) -> String {
+        let mut url = reqwest::Url::parse("http://localhost/").expect("static URL is valid");
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url.path().to_string()
+    }
+
+    /// Send a request with the channel token. `timeout` covers the wait for the
+    /// response headers, not the body, so event streams stay open.
+    async fn execute(
+        &self,
+        method: Method,
+        path: &str,
+        mut headers: HeaderMap,
+        body: Vec<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<Response, BackendError> {
+        let channel_token =
+            HeaderValue::from_str(&self.channel_token).map_err(|e| BackendError::Transport(e.to_string()))?;
+        headers.insert(CHANNEL_TOKEN_HEADER, channel_token);
+
+        let send = async {
+            match &self.transport {
+                Transport::Tcp { http, origin } => send_over_tcp(http, origin, method, path, headers, body).await,
+                Transport::LocalSocket(socket) => send_over_local_socket(socket, method, path, headers, body).await,
+            }
+        };
+
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, send)
+                .await
+                .map_err(|_| BackendError::Transport("request timed out".to_string()))?,
+            None => send.await,
+        }
+    }
+
+    /// Send a request as the shell and turn error statuses into `BackendError::Status`
+    async fn send(&self, method: Method, segments: &[&str], timeout: Option<Duration>) -> Result<Response, BackendError> {
+        let mut headers = HeaderMap::new();
+        let authorization = HeaderValue::from_str(&format!("Bearer {}", self.token))
+            .map_err(|e| BackendError::Transport(e.to_string()))?;
+        headers.insert(AUTHORIZATION, authorization);
+
+        let resp = self.execute(method, &Self::path(segments), headers, Vec::new(), timeout).await?;
+        if resp.status.is_success() {
+            return Ok(resp);
+        }
+
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            message: String,
+        }
+
+        let status = resp.status;
+        let message = match resp.body.json::<ErrorBody>().await {
+            Ok(body) => body.message,
+            Err(_) => status.canonical_reason().unwrap_or("unknown error").to_string(),
+        };
+
+        Err(BackendError::Status {
+            status: status.as_u16(),
+            message,
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T, BackendError> {
+        let resp = self.send(Method::GET, segments, None).await?;
+        resp.body.json().await
+    }
+
+    async fn post(&self, segments: &[&str]) -> Result<(), BackendError> {
+        self.send(Method::POST, segments, None).await?;
+        Ok(())
+    }
+
+    /// Relay a webview request to the sidecar. Only the channel token is added:
+    /// the webview authenticates with its own session, never as the shell.
+    pub async fn forward(&self, request: Request<Vec<u8>>) -> Result<tauri::http::Response<Vec<u8>>, BackendError> {
+        let (parts, body) = request.into_parts();
+        let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        let origin = parts.headers.get(ORIGIN).cloned();
+
+        let mut headers = parts.headers;
+        headers.remove(HOST);
+        headers.remove(CHANNEL_TOKEN_HEADER);
+
+        let resp = self.execute(parts.method, path, headers, body, None).await?;
+        let mut response = tauri::http::Response::new(resp.body.bytes().await?);
+        *response.status_mut() = resp.status;
+        *response.headers_mut() = resp.headers;
+
+        // The scheme is only reachable from our own webview, whose origin differs from it
+        if let Some(origin) = origin {
+            let headers = response.headers_mut();
+            headers.entry(ACCESS_CONTROL_ALLOW_ORIGIN).or_insert(origin);
+            headers
+                .entry(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .or_insert(HeaderValue::from_static("true"));
+        }
+        Ok(response)
+    }
+
+    pub async fn healthcheck(&self, timeout: Duration) -> Result<(), BackendError> {
+        self.send(Method::GET, &["healthcheck"], Some(timeout)).await?;
+        Ok(())
+    }
+
+    pub async fn list_volumes(&self) -> Result<Vec<Volume>, BackendError> {
+        self.get(&["api", "v1", "volumes"]).await
+    }
+
+    pub async fn mount_volume(&self, name: &str) -> Result<(), BackendError> {
+        self.post(&["api", "v1", "volumes", name, "mount"]).await
+    }
+
+    pub async fn unmount_volume(&self, name: &str) -> Result<(), BackendError> {
+        self.post(&["api", "v1", "volumes", name, "unmount"]).await
+    }
+
+    pub async fn list_repositories(&self) -> Result<Vec<Repository>, BackendError> {
+        self.get(&["api", "v1", "repositories"]).await
+    }
+
+    pub async fn get_repository(&self, id: &str) -> Result<Repository, BackendError> {
+        self.get(&["api", "v1", "repositories", id]).await
+    }
+
+    pub async fn list_backup_schedules(&self) -> Result<Vec<BackupSchedule>, BackendError> {
+        self.get(&["api", "v1", "backups"]).await
+    }
+
+    pub async fn get_backup_schedule(&self, schedule_id: i64) -> Result<BackupSchedule, BackendError> {
+        self.get(&["api", "v1", "backups", &schedule_id.to_string()]).await
+    }
+
+    pub async fn run_backup(&self, schedule_id: i64) -> Result<(), BackendError> {
+        self.post(&["api", "v1", "backups", &schedule_id.to_string(), "run"]).await
+    }
+
+    pub async fn stop_backup(&self, schedule_id: i64) -> Result<(), BackendError> {
+        self.post(&["api", "v1", "backups", &schedule_id.to_string(), "stop"]).await
+    }
+
+    pub async fn list_notification_destinations(&self) -> Result<Vec<NotificationDestination>, BackendError> {
+        self.get(&["api", "v1", "notifications", "destinations"]).await
+    }
+
+    pub async fn test_notification_destination(&self, id: i64) -> Result<(), BackendError> {
+        self.post(&["api", "v1", "notifications", "destinations", &id.to_string(), "test"])
+            .await
+    }
+
+    pub async fn system_info(&self) -> Result<SystemInfo, BackendError> {
+        self.get(&["api", "v1", "system", "info"]).await
+    }
+
+    pub async fn check_updates(&self) -> Result<UpdateInfo, BackendError> {
+        self.get(&["api", "v1", "system", "updates"]).await
+    }
+
+    pub async fn subscribe_events(&self) -> Result<EventStream, BackendError> {
+        let response = self.send(Method::GET, &["api", "v1", "events"], None).await?;
+
+        Ok(EventStream {
+            body: response.body,
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Compare the routes this client relies on with the backend's OpenAPI document.
+    /// Returns the ones the running backend does not declare.
+    pub async fn missing_routes(&self) -> Result<Vec<String>, BackendError> {
+        let resp = self.send(Method::GET, &["api", "v1", "openapi.json"], None).await?;
+        let document: serde_json::Value = resp.body.json().await?;
+        let paths = document
+            .get("paths")
+            .and_then(|p| p.as_object())
+            .ok_or_else(|| BackendError::Decode("OpenAPI document has no paths".to_string()))?;
+
+        // Path parameter names may differ between the spec and this list
+        let normalize = |path: &str| {
+            path.split('/')
+                .map(|segment| if segment.starts_with('{') || segment.starts_with(':') { "{}" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+
+        let declared: Vec<(String, String)> = paths
+            .iter()
+            .flat_map(|(path, item)| {
+                let path = normalize(path);
+                item.as_object()
+                    .into_iter()
+                    .flat_map(|methods| methods.keys())
+                    .map(move |method| (method.to_lowercase(), path.clone()))
+            })
+            .collect();
+
+        Ok(ROUTES
+            .iter()
+            .filter(|(method, path)| {
+                let path = normalize(path);
+                !declared.iter().any(|(m, p)| m == method && *p == path)
+            })
+            .map(|(method, path)| format!("{} {}", method.to_uppercase(), path))
+            .collect())
+    }
+}
+
+/// Log any routes the running backend no longer declares, so a shell/sidecar
+/// mismatch shows up in the logs instead of as silent tray failures.
+pub async fn check_api_compatibility(client: BackendClient) {
+    match client.missing_routes().await {
+        Ok(missing) if missing.is_empty() => log::info!("Backend API matches the shell's client"),
+        Ok(missing) => log::warn!("Backend API is missing routes used by the shell: {}", missing.join(", ")),
+        Err(e) => log::warn!("Failed to check backend API compatibility: {}", e),
+    }
+}
+
+async fn send_over_tcp(
+    http: &reqwest::Client,
+    origin: &str,
+    method: Method,
+    path: &str,
+    headers: HeaderMap,
+    body: Vec<u8>,
+) -> Result<Response, BackendError> {
+    let response = http
+        .request(method, format!("{}{}", origin, path))
+        .headers(headers)
+        .body(body)
+        .send()
+        .await?;
+
+    Ok(Response {
+        status: response.status(),
+        headers: response.headers().clone(),
+        body: ResponseBody::Tcp(response),
+    })
+}
+
+/// Open a fresh connection to the sidecar's socket (or pipe) and send one HTTP/1.1 request over it
+async fn send_over_local_socket(
+    socket: &Path,
+    method: Method,
+    path: &str,
+    mut headers: HeaderMap,
+    body: Vec<u8>,
+) -> Result<Response, BackendError> {
+    let transport = |e: std::io::Error| BackendError::Transport(format!("{}: {}", socket.display(), e));
+
+    #[cfg(unix)]
+    let stream = tokio::net::UnixStream::connect(socket).await.map_err(transport)?;
+    #[cfg(windows)]
+    let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(socket)
+        .map_err(transport)?;
+
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| BackendError::Transport(e.to_string()))?;
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = connection.await {
+            log::debug!("Backend socket connection closed: {}", e);
+        }
+    });
+
+    headers.insert(HOST, HeaderValue::from_static("localhost"));
+    let mut request = hyper::Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Full::new(Bytes::from(body)))
+        .map_err(|e| BackendError::Transport(e.to_string()))?;
+    *request.headers_mut() = headers;
+
+    let response = sender
+        .send_request(request)
+        .await
+        .map_err(|e| BackendError::Transport(e.to_string()))?;
+    let (parts, body) = response.into_parts();
+
+    Ok(Response {
+        status: parts.status,
+        headers: parts.headers,
+        body: ResponseBody::LocalSocket(body),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(event: &str, data: &str) -> (String, String) {
+        (event.to_string(), data.to_string())
+    }
+
+    fn parse(chunks: &[&[u8]]) -> Vec<(String, String)> {
+        let mut parser = SseParser::default();
+        chunks.iter().flat_map(|chunk| parser.push(chunk)).collect()
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        let messages = parse(&[b"event: backup:started\ndata: {\"scheduleId\":\ndata: 1}\n\n"]);
+        assert_eq!(messages, vec![message("backup:started", "{\"scheduleId\":\n1}")]);
+    }
+
+    #[test]
+    fn ignores_comments_and_unused_fields() {
+        let stream = b": keep-alive\n\nid: 7\nretry: 1000\nevent: heartbeat\n: between fields\ndata: {}\n\n";
+        let messages = parse(&[stream]);
+        assert_eq!(messages, vec![message("heartbeat", "{}")]);
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings() {
+        let messages = parse(&[b"event: connected\r\ndata: {}\r\n\r\nevent: heartbeat\r\ndata: {}\r\n\r\n"]);
+        assert_eq!(messages, vec![message("connected", "{}"), message("heartbeat", "{}")]);
+    }
+
+    #[test]
+    fn reassembles_messages_split_across_chunks() {
+        let stream = concat!(
+            "event: volume:status_changed\r\ndata: {\"volumeName\":\"données\"}\r\n\r\n",
+            "event: heartbeat\ndata: {}\n\n",
+        );
+        let expected = vec![
+            message("volume:status_changed", "{\"volumeName\":\"données\"}"),
+            message("heartbeat", "{}"),
+        ];
+
+        // Including splits inside the CRLF pair and inside the multi-byte character
+        let bytes = stream.as_bytes();
+        for at in 0..=bytes.len() {
+            let (head, tail) = bytes.split_at(at);
+            assert_eq!(parse(&[head, tail]), expected, "split at byte {}", at);
+        }
+
+        let single_bytes: Vec<&[u8]> = bytes.chunks(1).collect();
+        assert_eq!(parse(&single_bytes), expected);
+    }
+
+    #[test]
+    fn holds_a_message_until_its_blank_line() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: heartbeat\ndata: {}\n").is_empty());
+        assert_eq!(parser.push(b"\n"), vec![message("heartbeat", "{}")]);
+    }
+
+    #[test]
+    fn drops_messages_without_data_and_resets_the_event_name() {
+        let messages = parse(&[b"event: connected\n\ndata: {}\n\n"]);
+        assert_eq!(messages, vec![message("", "{}")]);
+    }
+
+    #[test]
+    fn decodes_volume_and_unknown_events() {
+        let mounted = ServerEvent::parse("volume:mounted", r#"{"volumeName":"docs"}"#).unwrap();
+        assert!(matches!(mounted, ServerEvent::VolumeMounted(e) if e.volume_name == "docs"));
+
+        let unknown = ServerEvent::parse("backup:queued", "{}").unwrap();
+        assert!(matches!(unknown, ServerEvent::Other(name) if name == "backup:queued"));
+
+        assert!(ServerEvent::parse("volume:updated", "{}").is_err());
+    }
+
+    #[test]
+    fn strips_a_single_leading_space_from_data() {
+        let messages = parse(&[b"data:  indented\ndata:unspaced\n\n"]);
+        assert_eq!(messages, vec![message("", " indented\nunspaced")]);
+    }
+}
@@ -0,0 +1,647 @@
+//! Command-line interface of the desktop binary.
+//!
+//! `status`, `backup` and `logs` run in the invoking process and reach the running
+//! instance's backend through `instance.json`. `open` and `quit` act on the window,
+//! so they are forwarded to the running instance by the single-instance plugin and
+//! applied in `handle_forwarded`. `export-state` and `restore-state` work on the
+//! data directory itself, so they refuse to run while the app does.
+
+use clap::{Parser, Subcommand};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::app_dirs::AppDirs;
+use crate::backend_client::{BackendClient, BackupSchedule};
+use crate::instance::{self, InstanceInfo};
+use crate::{headless, settings, state_backup, tray, SidecarState};
+
+/// Exit code of `status` when no instance is running, as in LSB init scripts
+const EXIT_NOT_RUNNING: i32 = 3;
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const TIMESTAMP_FORMAT: &[time::format_description::BorrowedFormatItem<'static>] =
+    time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]");
+
+#[derive(Parser)]
+#[command(name = "c3i-backup-one", version, about = "C3i Backup ONE desktop app")]
+pub struct Cli {
+    /// Supervise the backend without a window or tray (for servers without a display)
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Start hidden in the tray (passed by autostart)
+    #[arg(long)]
+    pub minimized: bool,
+
+    /// Open the main window on a page, e.g. `--page=backups`
+    #[arg(long, value_name = "ROUTE")]
+    pub page: Option<String>,
+
+    /// Keep the database, repositories and volumes in this directory instead of the app data directory
+    #[arg(long, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Run the backend on this loopback port instead of a free one
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub port: Option<u16>,
+
+    /// `c3i-backup-one://` link to open, as passed by the OS
+    #[arg(value_name = "URL", hide = true)]
+    pub link: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum CliCommand {
+    /// Show whether the app is running and the state of its backend (exits with 3 when not running)
+    Status {
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List or run backup schedules
+    #[command(subcommand)]
+    Backup(BackupCommand),
+    /// Print the app log
+    Logs {
+        /// Keep printing lines as they are written
+        #[arg(short, long)]
+        follow: bool,
+        /// Print the backend's log instead of the shell's
+        #[arg(long)]
+        sidecar: bool,
+        /// Number of lines to print from the end of the log
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: usize,
+    },
+    /// Open the main window on a page, e.g. `backups` or `repositories/3`
+    Open { page: String },
+    /// Stop the backend and exit the running app
+    Quit,
+    /// Save the database, restic password and settings to a passphrase-encrypted archive
+    ExportState {
+        /// Archive to write
+        output: PathBuf,
+        /// Read the passphrase from the first line of this file instead of asking for it
+        #[arg(long, value_name = "FILE")]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Replace the database, restic password and settings with those in an archive
+    RestoreState {
+        /// Archive written by `export-state` or the settings page
+        archive: PathBuf,
+        /// Read the passphrase from the first line of this file instead of asking for it
+        #[arg(long, value_name = "FILE")]
+        passphrase_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Subcommand)]
+pub enum BackupCommand {
+    /// List backup schedules
+    List {
+        /// Print the schedules as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Start a backup now
+    Run {
+        /// Schedule id or name
+        schedule: String,
+    },
+}
+
+/// Launch arguments that decide how the backend is started. They only apply at
+/// startup; a running instance keeps its own.
+#[derive(Clone, Default)]
+pub struct LaunchOptions {
+    pub data_dir: Option<PathBuf>,
+    pub port: Option<u16>,
+}
+
+impl Cli {
+    /// Parse the process arguments, printing usage and exiting on errors
+    pub fn parse_args() -> Self {
+        if std::env::args_os().len() > 1 {
+            attach_console();
+        }
+        Self::parse()
+    }
+
+    pub fn launch_options(&self) -> LaunchOptions {
+        LaunchOptions {
+            data_dir: self.data_dir.as_ref().map(|dir| {
+                // Relative to where the user launched us, not to wherever the sidecar runs
+                std::env::current_dir().map(|cwd| cwd.join(dir)).unwrap_or_else(|_| dir.clone())
+            }),
+            port: self.port,
+        }
+    }
+
+    /// Route to open on, from `open <page>` or `--page`
+    pub fn initial_route(&self) -> Option<String> {
+        match &self.command {
+            Some(CliCommand::Open { page }) => Some(route(page)),
+            _ => self.page.as_deref().map(route),
+        }
+    }
+}
+
+/// Run a command that does not need this process to become the app. Returns the
+/// exit code, or `None` when the app should start (or the command be forwarded to it).
+pub fn run_local(command: &CliCommand, dirs: &AppDirs, launch: &LaunchOptions) -> Option<i32> {
+    let result = match command {
+        CliCommand::Status { json } => status(dirs, *json),
+        CliCommand::Backup(BackupCommand::List { json }) => {
+            tauri::async_runtime::block_on(list_backups(dirs, *json))
+        }
+        CliCommand::Backup(BackupCommand::Run { schedule }) => {
+            tauri::async_runtime::block_on(run_backup(dirs, schedule))
+        }
+        CliCommand::Logs { follow, lines, sidecar } => {
+            let path = if *sidecar { dirs.sidecar_log_file() } else { dirs.log_file() };
+            path.and_then(|path| logs(&path, *lines, *follow))
+        }
+        CliCommand::Quit => match running_instance(dirs) {
+            Ok(info) if info.headless => stop_headless(&info),
+            // The single-instance plugin hands `quit` to the running app
+            Ok(_) => return None,
+            Err(e) => Err(e),
+        },
+        CliCommand::Open { .. } => match running_instance(dirs) {
+            Ok(info) if info.headless => Err(format!(
+                "C3i Backup ONE is running headless (pid {}), there is no window to open",
+                info.pid
+            )),
+            _ => return None,
+        },
+        CliCommand::ExportState { output, passphrase_file } => {
+            export_state(dirs, launch, output, passphrase_file.as_deref())
+        }
+        CliCommand::RestoreState { archive, passphrase_file } => {
+            restore_state(dirs, launch, archive, passphrase_file.as_deref())
+        }
+    };
+
+    Some(match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    })
+}
+
+/// Apply the arguments of a second launch to this, the running instance
+pub fn handle_forwarded(app: &AppHandle, args: Vec<String>) {
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            log::warn!("Ignoring arguments of another launch: {}", e);
+            tray::show_main_window(app);
+            return;
+        }
+    };
+
+    // The deep-link plugin hands links to `on_open_url` as well, which dispatches them
+    if cli.link.is_some() {
+        return;
+    }
+
+    if cli.data_dir.is_some() || cli.port.is_some() {
+        log::warn!("--data-dir and --port only apply at startup, quit the running app to change them");
+    }
+
+    if matches!(cli.command, Some(CliCommand::Quit)) {
+        log::info!("Quitting as requested from the command line");
+        crate::quit_app(app);
+    } else if let Some(route) = cli.initial_route() {
+        log::info!("Opening {} as requested from the command line", route);
+        tray::navigate(app, &route);
+    } else if cli.minimized {
+        // Autostart raced a manual launch; leave the window as the user has it
+        log::info!("Another instance was started minimized, leaving the window as it is");
+    } else {
+        log::info!("Another instance attempted to start, focusing existing window");
+        tray::show_main_window(app);
+    }
+}
+
+/// Frontend route for a page given on the command line
+pub fn route(page: &str) -> String {
+    format!("/{}", page.trim().trim_start_matches('/'))
+}
+
+fn running_instance(dirs: &AppDirs) -> Result<InstanceInfo, String> {
+    instance::read(&dirs.local_data_dir()?)?.ok_or_else(|| "C3i Backup ONE is not running".to_string())
+}
+
+fn backend_client(dirs: &AppDirs) -> Result<BackendClient, String> {
+    let info = running_instance(dirs)?;
+    match &info.endpoint {
+        Some(endpoint) if info.backend.state.has_url() => {
+            Ok(BackendClient::new(endpoint, &info.shell_token, &info.channel_token))
+        }
+        _ => Err(format!("The backend is not available ({})", state_label(&info.backend.state))),
+    }
+}
+
+/// Headless instances stop gracefully on SIGTERM, like under a service manager
+#[cfg(unix)]
+fn stop_headless(info: &InstanceInfo) -> Result<i32, String> {
+    if unsafe { libc::kill(info.pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(format!(
+            "Failed to stop pid {}: {}",
+            info.pid,
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    println!("Asked the headless instance (pid {}) to stop", info.pid);
+    Ok(0)
+}
+
+#[cfg(windows)]
+fn stop_headless(info: &InstanceInfo) -> Result<i32, String> {
+    Err(format!(
+        "C3i Backup ONE is running headless (pid {}); stop it through its service manager",
+        info.pid
+    ))
+}
+
+/// The data directory can only be archived or replaced while no backend uses it
+fn ensure_not_running(dirs: &AppDirs) -> Result<(), String> {
+    match instance::read(&dirs.local_data_dir()?)? {
+        Some(info) => Err(format!(
+            "C3i Backup ONE is running (pid {}); quit it first, or use the settings page of the app",
+            info.pid
+        )),
+        None => Ok(()),
+    }
+}
+
+fn export_state(
+    dirs: &AppDirs,
+    launch: &LaunchOptions,
+    output: &Path,
+    passphrase_file: Option<&Path>,
+) -> Result<i32, String> {
+    ensure_not_running(dirs)?;
+    let passphrase = read_passphrase(passphrase_file, true)?;
+
+    let host = headless::offline_host(dirs.clone(), launch.clone());
+    state_backup::export(&host, output, &passphrase)?;
+    println!("Saved the app state to {}", output.display());
+
+    Ok(0)
+}
+
+fn restore_state(
+    dirs: &AppDirs,
+    launch: &LaunchOptions,
+    archive: &Path,
+    passphrase_file: Option<&Path>,
+) -> Result<i32, String> {
+    ensure_not_running(dirs)?;
+    let passphrase = read_passphrase(passphrase_file, false)?;
+
+    let host = headless::offline_host(dirs.clone(), launch.clone());
+    let restored = state_backup::restore(&host, archive, &passphrase)?;
+    if let Some(settings) = &restored.settings {
+        settings::save_to(&dirs.config_dir()?, settings)?;
+    }
+
+    println!("Restored the app state saved by version {}", restored.manifest.app_version);
+    if let Some(previous) = &restored.previous_state {
+        println!("The replaced state was saved to {}", previous.display());
+    }
+
+    Ok(0)
+}
+
+/// Passphrase from the first line of `file`, or typed on the terminal (`confirm` asks twice)
+fn read_passphrase(file: Option<&Path>, confirm: bool) -> Result<String, String> {
+    if let Some(file) = file {
+        let contents = std::fs::read_to_string(file).map_err(|e| format!("Failed to read {:?}: {}", file, e))?;
+        return Ok(contents.lines().next().unwrap_or_default().to_string());
+    }
+
+    let prompt = |label: &str| -> Result<String, String> {
+        eprint!("{}: ", label);
+        std::io::stderr().flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map_err(|e| e.to_string())?;
+        Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+    };
+
+    let passphrase = prompt("Passphrase")?;
+    if confirm && prompt("Repeat the passphrase")? != passphrase {
+        return Err("The passphrases do not match".to_string());
+    }
+    Ok(passphrase)
+}
+
+fn status(dirs: &AppDirs, json: bool) -> Result<i32, String> {
+    let Some(info) = instance::read(&dirs.local_data_dir()?)? else {
+        if json {
+            println!("{}", serde_json::json!({ "running": false }));
+        } else {
+            println!("C3i Backup ONE is not running");
+        }
+        return Ok(EXIT_NOT_RUNNING);
+    };
+
+    let responding = match &info.endpoint {
+        Some(endpoint) if info.backend.state.has_url() => {
+            let client = BackendClient::new(endpoint, &info.shell_token, &info.channel_token);
+            tauri::async_runtime::block_on(client.healthcheck(Duration::from_secs(5))).is_ok()
+        }
+        _ => false,
+    };
+
+    if json {
+        let status = serde_json::json!({
+            "running": true,
+            "pid": info.pid,
+            "headless": info.headless,
+            "backend": info.backend,
+            "responding": responding,
+        });
+        println!("{}", status);
+    } else {
+        let mode = if info.headless { " headless" } else { "" };
+        println!("C3i Backup ONE is running{} (pid {})", mode, info.pid);
+        println!("Backend: {}", state_label(&info.backend.state));
+        if let Some(url) = &info.backend.url {
+            println!("URL: {}", url);
+        }
+        println!("Responding: {}", if responding { "yes" } else { "no" });
+    }
+
+    Ok(0)
+}
+
+async fn list_backups(dirs: &AppDirs, json: bool) -> Result<i32, String> {
+    let schedules = backend_client(dirs)?
+        .list_backup_schedules()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&schedules).map_err(|e| e.to_string())?);
+        return Ok(0);
+    }
+
+    if schedules.is_empty() {
+        println!("No backup schedules");
+        return Ok(0);
+    }
+
+    for schedule in &schedules {
+        println!(
+            "{:>4}  {:<24}  {:<12}  last: {:<16}  next: {}{}",
+            schedule.id,
+            schedule.name,
+            schedule.last_backup_status.as_deref().unwrap_or("never run"),
+            format_timestamp(schedule.last_backup_at),
+            format_timestamp(schedule.next_backup_at),
+            if schedule.enabled { "" } else { "  (disabled)" },
+        );
+    }
+
+    Ok(0)
+}
+
+async fn run_backup(dirs: &AppDirs, schedule: &str) -> Result<i32, String> {
+    let client = backend_client(dirs)?;
+    let schedules = client.list_backup_schedules().await.map_err(|e| e.to_string())?;
+    let schedule = find_schedule(&schedules, schedule)?;
+
+    client.run_backup(schedule.id).await.map_err(|e| e.to_string())?;
+    println!("Started backup \"{}\" (schedule {})", schedule.name, schedule.id);
+
+    Ok(0)
+}
+
+/// Match a schedule by id, short id or (case-insensitive) name
+fn find_schedule<'a>(schedules: &'a [BackupSchedule], query: &str) -> Result<&'a BackupSchedule, String> {
+    let by_id = query.parse::<i64>().ok();
+    let matches: Vec<&BackupSchedule> = schedules
+        .iter()
+        .filter(|s| Some(s.id) == by_id || s.short_id == query || s.name.eq_ignore_ascii_case(query))
+        .collect();
+
+    match matches.as_slice() {
+        [schedule] => Ok(schedule),
+        [] => Err(format!("No backup schedule matches \"{}\"", query)),
+        _ => Err(format!("\"{}\" matches several schedules, use its id instead", query)),
+    }
+}
+
+fn logs(path: &Path, lines: usize, follow: bool) -> Result<i32, String> {
+    let mut out = std::io::stdout().lock();
+
+    let contents = std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let text = String::from_utf8_lossy(&contents);
+    let tail: Vec<&str> = text.lines().rev().take(lines).collect();
+    for line in tail.iter().rev() {
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    }
+
+    if !follow {
+        return Ok(0);
+    }
+
+    let mut position = contents.len() as u64;
+    loop {
+        out.flush().map_err(|e| e.to_string())?;
+        std::thread::sleep(LOG_POLL_INTERVAL);
+
+        let len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if len < position {
+            // Truncated or rotated: start over from the new file
+            position = 0;
+        }
+        if len == position {
+            continue;
+        }
+
+        let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        file.seek(SeekFrom::Start(position)).map_err(|e| e.to_string())?;
+        let mut chunk = Vec::new();
+        file.read_to_end(&mut chunk).map_err(|e| e.to_string())?;
+        position += chunk.len() as u64;
+        out.write_all(&chunk).map_err(|e| e.to_string())?;
+    }
+}
+
+fn state_label(state: &SidecarState) -> String {
+    match state {
+        SidecarState::Starting => "starting".to_string(),
+        SidecarState::Healthy => "healthy".to_string(),
+        SidecarState::Degraded => "degraded (not answering health checks)".to_string(),
+        SidecarState::Crashed { exit, .. } => format!("crashed ({})", exit),
+        SidecarState::Stopping => "stopping".to_string(),
+        SidecarState::Stopped => "stopped".to_string(),
+    }
+}
+
+fn format_timestamp(timestamp_ms: Option<u64>) -> String {
+    let Some(timestamp_ms) = timestamp_ms else {
+        return "-".to_string();
+    };
+
+    let Ok(utc) = time::OffsetDateTime::from_unix_timestamp((timestamp_ms / 1000) as i64) else {
+        return "-".to_string();
+    };
+    let local = time::UtcOffset::current_local_offset()
+        .map(|offset| utc.to_offset(offset))
+        .unwrap_or(utc);
+
+    local.format(TIMESTAMP_FORMAT).unwrap_or_else(|_| "-".to_string())
+}
+
+/// Release builds on Windows use the GUI subsystem and have no console of their own
+#[cfg(windows)]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("c3i-backup-one").chain(args.iter().copied())).unwrap()
+    }
+
+    fn schedule(id: i64, short_id: &str, name: &str) -> BackupSchedule {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "shortId": short_id,
+            "name": name,
+            "enabled": true,
+            "cronExpression": "0 3 * * *",
+            "volume": {
+                "id": 1,
+                "shortId": "v1",
+                "name": "docs",
+                "type": "directory",
+                "status": "mounted",
+                "autoRemount": true,
+            },
+            "repository": { "id": "r1", "shortId": "r1", "name": "nas", "type": "local" },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn routes_are_absolute() {
+        assert_eq!(route("backups"), "/backups");
+        assert_eq!(route(" /repositories/3 "), "/repositories/3");
+    }
+
+    #[test]
+    fn open_takes_precedence_over_page() {
+        assert_eq!(
+            parse(&["--page", "settings"]).initial_route().as_deref(),
+            Some("/settings")
+        );
+        assert_eq!(
+            parse(&["--page", "settings", "open", "backups/2"])
+                .initial_route()
+                .as_deref(),
+            Some("/backups/2")
+        );
+        assert_eq!(parse(&["--minimized"]).initial_route(), None);
+    }
+
+    #[test]
+    fn subcommands_are_parsed() {
+        let cli = parse(&["logs", "-f", "--sidecar", "-n", "20"]);
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::Logs {
+                follow: true,
+                sidecar: true,
+                lines: 20
+            })
+        ));
+
+        let cli = parse(&["backup", "run", "nightly"]);
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::Backup(BackupCommand::Run { schedule })) if schedule == "nightly"
+        ));
+
+        let cli = parse(&["export-state", "state.c3i", "--passphrase-file", "pass.txt"]);
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::ExportState { passphrase_file: Some(file), .. }) if file == Path::new("pass.txt")
+        ));
+    }
+
+    #[test]
+    fn port_zero_is_rejected() {
+        assert_eq!(parse(&["--port", "8080"]).port, Some(8080));
+        assert!(Cli::try_parse_from(["c3i-backup-one", "--port", "0"]).is_err());
+    }
+
+    #[test]
+    fn data_dir_is_relative_to_the_working_directory() {
+        let launch = parse(&["--data-dir", "data"]).launch_options();
+
+        assert_eq!(launch.data_dir, Some(std::env::current_dir().unwrap().join("data")));
+    }
+
+    #[test]
+    fn schedules_are_found_by_id_short_id_or_name() {
+        let schedules = [schedule(1, "a1b2", "Nightly"), schedule(2, "c3d4", "Weekly")];
+
+        assert_eq!(find_schedule(&schedules, "2").unwrap().id, 2);
+        assert_eq!(find_schedule(&schedules, "a1b2").unwrap().id, 1);
+        assert_eq!(find_schedule(&schedules, "weekly").unwrap().id, 2);
+        assert!(find_schedule(&schedules, "monthly")
+            .unwrap_err()
+            .starts_with("No backup schedule"));
+    }
+
+    #[test]
+    fn ambiguous_schedule_names_are_rejected() {
+        let schedules = [schedule(1, "a1b2", "Nightly"), schedule(2, "c3d4", "nightly")];
+
+        assert!(find_schedule(&schedules, "Nightly")
+            .unwrap_err()
+            .contains("matches several schedules"));
+        assert_eq!(find_schedule(&schedules, "1").unwrap().id, 1);
+    }
+
+    #[test]
+    fn passphrase_file_is_read_up_to_the_first_line_break() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("passphrase");
+        std::fs::write(&file, "correct horse\nsecond line\n").unwrap();
+
+        assert_eq!(read_passphrase(Some(&file), true).unwrap(), "correct horse");
+        assert!(read_passphrase(Some(&dir.path().join("missing")), false).is_err());
+    }
+
+    #[test]
+    fn missing_timestamps_are_a_dash() {
+        assert_eq!(format_timestamp(None), "-");
+        assert_eq!(
+            format_timestamp(Some(1_700_000_000_000)).len(),
+            "2023-11-14 22:13".len()
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! `c3i-backup-one://` links, for desktop shortcuts, scripts and notification clicks.
+//!
+//! A link either opens a page in the main window or acts through the backend API
+//! without showing it:
+//!
+//! - `c3i-backup-one://backups/12/run` (or `/stop`) starts or stops schedule 12
+//! - `c3i-backup-one://repositories/new?url=...` opens the new repository form, prefilled
+//! - any other `c3i-backup-one://<route>` opens that page
+//!
+//! On Linux and Windows the OS launches a second instance with the link as its
+//! argument; the single-instance plugin forwards it here through `on_open_url`.
+
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, Url};
+
+use crate::tray::{self, ScheduleAction};
+use crate::AppState;
+
+pub const SCHEME: &str = "c3i-backup-one";
+
+/// How long an action from a link waits for a backend that is still starting
+const BACKEND_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+const BACKEND_WAIT_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, PartialEq)]
+enum DeepLink {
+    /// Frontend route, with its query string
+    Open(String),
+    Schedule(i64, ScheduleAction),
+}
+
+/// Handle a link. At startup the window has not loaded yet, so pages are queued
+/// as the pending route instead of navigated to.
+pub fn dispatch(app: &AppHandle, url: &str, startup: bool) {
+    let link = match Url::parse(url).map_err(|e| e.to_string()).and_then(|url| parse(&url)) {
+        Ok(link) => link,
+        Err(e) => {
+            log::warn!("Ignoring link {}: {}", url, e);
+            return;
+        }
+    };
+
+    match link {
+        DeepLink::Open(route) if startup => {
+            log::info!("Opening {} from a link", route);
+            let state = app.state::<AppState>();
+            *state.pending_route.lock().unwrap_or_else(|e| e.into_inner()) = Some(route);
+        }
+        DeepLink::Open(route) => {
+            log::info!("Opening {} from a link", route);
+            tray::navigate(app, &route);
+        }
+        DeepLink::Schedule(schedule_id, action) => {
+            log::info!("{:?} schedule {} from a link", action, schedule_id);
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                wait_for_backend(&app).await;
+                tray::schedule_action(&app, schedule_id, action);
+            });
+        }
+    }
+}
+
+fn parse(url: &Url) -> Result<DeepLink, String> {
+    if url.scheme() != SCHEME {
+        return Err(format!("unsupported scheme {}", url.scheme()));
+    }
+
+    // `c3i-backup-one://backups/12` parses with `backups` as the host
+    let segments: Vec<&str> = url
+        .host_str()
+        .into_iter()
+        .chain(url.path().split('/'))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let query = url.query().map(|q| format!("?{}", q)).unwrap_or_default();
+
+    match segments.as_slice() {
+        ["backups", id, action @ ("run" | "stop")] => {
+            let schedule_id = id.parse().map_err(|_| format!("invalid schedule id {}", id))?;
+            let action = if *action == "run" { ScheduleAction::Run } else { ScheduleAction::Stop };
+            Ok(DeepLink::Schedule(schedule_id, action))
+        }
+        // The frontend names its creation forms `create`
+        [section, "new"] => Ok(DeepLink::Open(format!("/{}/create{}", section, query))),
+        _ => Ok(DeepLink::Open(format!("/{}{}", segments.join("/"), query))),
+    }
+}
+
+async fn wait_for_backend(app: &AppHandle) {
+    let started = Instant::now();
+    while started.elapsed() < BACKEND_WAIT_TIMEOUT {
+        if crate::backend_status(&app.state::<AppState>()).state.has_url() {
+            return;
+        }
+        tokio::time::sleep(BACKEND_WAIT_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(link: &str) -> Result<DeepLink, String> {
+        parse(&Url::parse(link).unwrap())
+    }
+
+    #[test]
+    fn schedule_links_run_or_stop_a_schedule() {
+        assert_eq!(parse_str("c3i-backup-one://backups/12/run"), Ok(DeepLink::Schedule(12, ScheduleAction::Run)));
+        assert_eq!(
+            parse_str("c3i-backup-one://backups/12/stop"),
+            Ok(DeepLink::Schedule(12, ScheduleAction::Stop))
+        );
+        assert_eq!(parse_str("c3i-backup-one:///backups/3/run/"), Ok(DeepLink::Schedule(3, ScheduleAction::Run)));
+    }
+
+    #[test]
+    fn schedule_links_need_a_numeric_id() {
+        assert!(parse_str("c3i-backup-one://backups/nightly/run").unwrap_err().contains("invalid schedule id"));
+    }
+
+    #[test]
+    fn new_opens_the_creation_form_with_its_query() {
+        assert_eq!(
+            parse_str("c3i-backup-one://repositories/new?url=s3%3A%2F%2Fbucket"),
+            Ok(DeepLink::Open("/repositories/create?url=s3%3A%2F%2Fbucket".to_string()))
+        );
+    }
+
+    #[test]
+    fn other_links_open_their_route() {
+        assert_eq!(parse_str("c3i-backup-one://backups/12"), Ok(DeepLink::Open("/backups/12".to_string())));
+        assert_eq!(parse_str("c3i-backup-one://settings?tab=logs"), Ok(DeepLink::Open("/settings?tab=logs".to_string())));
+        assert_eq!(parse_str("c3i-backup-one://"), Ok(DeepLink::Open("/".to_string())));
+    }
+
+    #[test]
+    fn other_schemes_are_refused() {
+        assert!(parse_str("https://backups/12/run").unwrap_err().contains("unsupported scheme"));
+    }
+}
@@ -0,0 +1,426 @@
+//! Support bundles: a zip with the logs, paths, versions, system information,
+//! startup checks and recent health of the app, for attaching to problem reports.
+//!
+//! Tokens and the restic password never leave the machine: secret variables are
+//! redacted and their values are scrubbed from every file in the bundle.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::{log_viewer, secrets};
+use crate::{storage, SidecarHost};
+
+/// Tools the backend runs from the resources `bin` directory
+pub const BUNDLED_TOOLS: &[&str] = &["restic", "rclone", "shoutrrr"];
+const TOOL_TIMEOUT: Duration = Duration::from_secs(10);
+const REDACTED: &str = "<redacted>";
+/// Variable names containing any of these hold secrets rather than paths or flags
+const SECRET_MARKERS: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "API_KEY"];
+
+/// Path of a bundled tool in `bin_dir`, looked up the way the backend's binary
+/// resolver does: directly in the directory, then in its `<os>-<arch>` subdirectory
+pub fn tool_path(bin_dir: &Path, tool: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", tool, std::env::consts::EXE_SUFFIX);
+    [bin_dir.join(&file_name), bin_dir.join(platform_dir()).join(&file_name)]
+        .into_iter()
+        .find(|path| path.exists())
+}
+
+/// First line of a bundled tool's version output
+pub fn tool_version(bin_dir: &Path, tool: &str) -> Result<String, String> {
+    let path = tool_path(bin_dir, tool).ok_or_else(|| format!("{} not found in {:?}", tool, bin_dir))?;
+    program_version(&path, tool)
+}
+
+/// First line of the version output of `program`, a path or a name looked up in `PATH`
+pub fn program_version(program: &Path, tool: &str) -> Result<String, String> {
+    let args: &[&str] = match tool {
+        "restic" | "rclone" => &["version"],
+        _ => &["--version"],
+    };
+    let output = run_with_timeout(Command::new(program).args(args), TOOL_TIMEOUT)
+        .map_err(|e| format!("Failed to run {:?}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{:?} exited with {}", program, output.status));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| format!("{:?} printed no version", program))
+}
+
+/// Subdirectory of the resources `bin` directory with binaries for this platform,
+/// named as the backend's `getPlatformDir` names it
+fn platform_dir() -> String {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    format!("{}-{}", os, arch)
+}
+
+/// Run a command to completion, killing it after `timeout`
+fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<std::process::Output, String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // Read while waiting: a tool that fills a pipe buffer blocks until it is read
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("did not finish within {} s", timeout.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+
+    Ok(std::process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Read a child's pipe to the end on its own thread
+fn drain(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemInfo {
+    app_version: &'static str,
+    os: &'static str,
+    os_version: Option<String>,
+    arch: &'static str,
+    headless: bool,
+}
+
+#[derive(Serialize)]
+struct ToolVersion {
+    version: Option<String>,
+    error: Option<String>,
+}
+
+impl From<Result<String, String>> for ToolVersion {
+    fn from(result: Result<String, String>) -> Self {
+        match result {
+            Ok(version) => Self {
+                version: Some(version),
+                error: None,
+            },
+            Err(error) => Self {
+                version: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PathInfo {
+    path: Option<PathBuf>,
+    exists: bool,
+}
+
+impl PathInfo {
+    fn of(path: Option<PathBuf>) -> Self {
+        Self {
+            exists: path.as_ref().is_some_and(|p| p.exists()),
+            path,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HealthReport {
+    status: crate::BackendStatus,
+    supervisor: crate::SupervisorStatus,
+    /// Oldest first
+    recent_checks: Vec<crate::HealthCheck>,
+}
+
+/// Values that must not appear anywhere in the bundle
+struct Scrubber {
+    secrets: Vec<String>,
+}
+
+impl Scrubber {
+    fn scrub(&self, text: &str) -> String {
+        self.secrets
+            .iter()
+            .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), REDACTED))
+    }
+}
+
+fn is_secret(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+/// Write a support bundle for the app whose shell log is `shell_log` to `output`
+pub fn create_support_bundle(host: &impl SidecarHost, shell_log: &Path, output: &Path) -> Result<(), String> {
+    let state = host.app_state();
+    let paths = storage::resolve(host)?;
+
+    let mut secrets = vec![state.shell_token.clone(), state.channel_token.clone()];
+    // Only used to scrub it, in case something ever logged it
+    if let Some(password) = secrets::peek(&paths) {
+        let password = password.trim();
+        if !password.is_empty() {
+            secrets.push(password.to_string());
+        }
+    }
+    let scrubber = Scrubber { secrets };
+
+    let file = std::fs::File::create(output).map_err(|e| format!("Failed to create {:?}: {}", output, e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut add = |name: &str, contents: &str| -> Result<(), String> {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(scrubber.scrub(contents).as_bytes())
+            .map_err(|e| format!("Failed to write {} to the bundle: {}", name, e))
+    };
+
+    let system = SystemInfo {
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        os_version: os_version(),
+        arch: std::env::consts::ARCH,
+        headless: host.is_headless(),
+    };
+    add("system.json", &to_json(&system)?)?;
+
+    let bin_dir = crate::get_resources_path(host);
+    let tools: BTreeMap<&str, ToolVersion> = BUNDLED_TOOLS
+        .iter()
+        .map(|tool| {
+            let version = match &bin_dir {
+                Some(dir) => tool_version(dir, tool),
+                None => Err("Resources directory could not be resolved".to_string()),
+            };
+            (*tool, ToolVersion::from(version))
+        })
+        .collect();
+    add("versions.json", &to_json(&tools)?)?;
+
+    let mut path_report: BTreeMap<&str, PathInfo> = BTreeMap::new();
+    path_report.insert("sidecar", PathInfo::of(crate::get_sidecar_path(host)));
+    path_report.insert("resources", PathInfo::of(bin_dir.clone()));
+    path_report.insert("migrations", PathInfo::of(crate::get_migrations_path(host)));
+    path_report.insert("resourceDir", PathInfo::of(host.resource_dir().ok()));
+    path_report.insert("logs", PathInfo::of(host.log_dir().ok()));
+    path_report.insert("dataDir", PathInfo::of(Some(paths.data_dir.clone())));
+    path_report.insert("database", PathInfo::of(Some(paths.database.clone())));
+    path_report.insert("repositoriesDir", PathInfo::of(Some(paths.repositories_dir.clone())));
+    path_report.insert("volumesDir", PathInfo::of(Some(paths.volumes_dir.clone())));
+    path_report.insert("cacheDir", PathInfo::of(Some(paths.cache_dir.clone())));
+    path_report.insert("passwordFile", PathInfo::of(Some(paths.password_file.clone())));
+    add("paths.json", &to_json(&path_report)?)?;
+
+    let logs_dir = crate::normalize_windows_path(host.log_dir()?);
+    let mut env = crate::backend_endpoint(state)
+        .map(|endpoint| crate::endpoint_env(&endpoint))
+        .unwrap_or_default();
+    env.extend(crate::sidecar_env(host, &paths, &logs_dir)?);
+    let env: BTreeMap<&str, String> = env
+        .into_iter()
+        .map(|(key, value)| (key, if is_secret(key) { REDACTED.to_string() } else { value }))
+        .collect();
+    add("environment.json", &to_json(&env)?)?;
+
+    let health = HealthReport {
+        status: crate::backend_status(state),
+        supervisor: state.supervisor.lock().map_err(|e| e.to_string())?.clone(),
+        recent_checks: state
+            .health_checks
+            .lock()
+            .map_err(|e| e.to_string())?
+            .iter()
+            .cloned()
+            .collect(),
+    };
+    add("health.json", &to_json(&health)?)?;
+
+    let preflight = state.preflight.lock().map_err(|e| e.to_string())?.clone();
+    add("preflight.json", &to_json(&preflight)?)?;
+
+    let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    add("settings.json", &to_json(&settings)?)?;
+
+    for log_file in log_viewer::list(shell_log)? {
+        let path = shell_log.with_file_name(&log_file.name);
+        match std::fs::read(&path) {
+            Ok(contents) => add(&format!("logs/{}", log_file.name), &String::from_utf8_lossy(&contents))?,
+            Err(e) => log::warn!("Leaving {:?} out of the support bundle: {}", path, e),
+        }
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn to_json(value: &impl Serialize) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+}
+
+/// Human-readable OS release, where the platform makes it easy to get
+fn os_version() -> Option<String> {
+    if cfg!(target_os = "linux") {
+        let release = std::fs::read_to_string("/etc/os-release").ok()?;
+        release
+            .lines()
+            .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+            .map(|name| name.trim_matches('"').to_string())
+    } else if cfg!(target_os = "macos") {
+        let output = run_with_timeout(Command::new("sw_vers").arg("-productVersion"), TOOL_TIMEOUT).ok()?;
+        Some(format!("macOS {}", String::from_utf8_lossy(&output.stdout).trim()))
+    } else if cfg!(target_os = "windows") {
+        let output = run_with_timeout(Command::new("cmd").args(["/C", "ver"]), TOOL_TIMEOUT).ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn write_script(path: &Path, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, script).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn tools_are_found_directly_or_in_the_platform_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_name = format!("rclone{}", std::env::consts::EXE_SUFFIX);
+        assert_eq!(tool_path(dir.path(), "rclone"), None);
+
+        let nested = dir.path().join(platform_dir()).join(&file_name);
+        std::fs::create_dir_all(nested.parent().unwrap()).unwrap();
+        std::fs::write(&nested, "").unwrap();
+        assert_eq!(tool_path(dir.path(), "rclone"), Some(nested));
+
+        let direct = dir.path().join(&file_name);
+        std::fs::write(&direct, "").unwrap();
+        assert_eq!(tool_path(dir.path(), "rclone"), Some(direct));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn version_is_the_first_line_printed() {
+        let dir = tempfile::tempdir().unwrap();
+        // restic and rclone take a `version` subcommand, the others `--version`
+        write_script(
+            &dir.path().join("restic"),
+            "#!/bin/sh\n[ \"$1\" = version ] || exit 1\nprintf '\\n  restic 0.17.3 compiled with go1.23\\nmore\\n'\n",
+        );
+        write_script(&dir.path().join("shoutrrr"), "#!/bin/sh\n[ \"$1\" = --version ] || exit 1\necho 0.8.0\n");
+
+        assert_eq!(tool_version(dir.path(), "restic").unwrap(), "restic 0.17.3 compiled with go1.23");
+        assert_eq!(tool_version(dir.path(), "shoutrrr").unwrap(), "0.8.0");
+        assert!(tool_version(dir.path(), "rclone").unwrap_err().starts_with("rclone not found"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_or_silent_tools_have_no_version() {
+        let dir = tempfile::tempdir().unwrap();
+        write_script(&dir.path().join("restic"), "#!/bin/sh\nexit 3\n");
+        write_script(&dir.path().join("rclone"), "#!/bin/sh\n");
+
+        assert!(tool_version(dir.path(), "restic").unwrap_err().contains("exited with"));
+        assert!(tool_version(dir.path(), "rclone").unwrap_err().ends_with("printed no version"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_are_killed_after_the_timeout() {
+        let started = Instant::now();
+
+        let result = run_with_timeout(Command::new("sleep").arg("10"), Duration::from_millis(200));
+
+        assert!(result.unwrap_err().starts_with("did not finish within"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_larger_than_a_pipe_buffer_is_read_in_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("verbose");
+        // 256 KiB on each stream, well past the usual 64 KiB pipe buffer
+        write_script(&script, "#!/bin/sh
+head -c 262144 /dev/zero
+head -c 262144 /dev/zero >&2
+");
+
+        let output = run_with_timeout(&mut Command::new(&script), Duration::from_secs(5)).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), 262144);
+        assert_eq!(output.stderr.len(), 262144);
+    }
+
+    #[test]
+    fn secrets_are_scrubbed_everywhere() {
+        let scrubber = Scrubber {
+            secrets: vec!["s3cr3t".to_string(), "token-123".to_string()],
+        };
+
+        assert_eq!(
+            scrubber.scrub("password=s3cr3t token=token-123 again s3cr3t"),
+            "password=<redacted> token=<redacted> again <redacted>"
+        );
+    }
+
+    #[test]
+    fn variables_named_like_secrets_are_secret() {
+        for key in ["RESTIC_PASSWORD", "aws_secret_access_key", "GITHUB_TOKEN", "B2_API_KEY"] {
+            assert!(is_secret(key), "{}", key);
+        }
+        for key in ["RESTIC_REPOSITORY", "PATH", "RCLONE_CONFIG"] {
+            assert!(!is_secret(key), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! `--headless`: supervise the backend without a window, tray or webview, for
+//! servers with no display.
+//!
+//! The Tauri runtime needs a display to start, so this mode never builds a
+//! `tauri::App`. Directories are resolved the same way Tauri's path resolver does,
+//! so both modes share the database, settings and logs.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::app_dirs::AppDirs;
+use crate::cli::LaunchOptions;
+use crate::{settings, AppState, BackendStatus, SidecarHost};
+
+#[derive(Clone)]
+struct HeadlessHost(Arc<HeadlessInner>);
+
+struct HeadlessInner {
+    state: AppState,
+    dirs: AppDirs,
+}
+
+impl SidecarHost for HeadlessHost {
+    fn app_state(&self) -> &AppState {
+        &self.0.state
+    }
+
+    fn resource_dir(&self) -> Result<PathBuf, String> {
+        self.0.dirs.resource_dir()
+    }
+
+    fn data_dir(&self) -> Result<PathBuf, String> {
+        match &self.0.state.launch.data_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => self.0.dirs.data_dir(),
+        }
+    }
+
+    fn local_data_dir(&self) -> Result<PathBuf, String> {
+        self.0.dirs.local_data_dir()
+    }
+
+    fn log_dir(&self) -> Result<PathBuf, String> {
+        self.0.dirs.log_dir()
+    }
+
+    fn config_dir(&self) -> Result<PathBuf, String> {
+        self.0.dirs.config_dir()
+    }
+
+    // Nothing to refresh without a tray or webview; transitions are already logged
+    fn sidecar_state_changed(&self, _status: &BackendStatus) {}
+
+    fn backend_ready(&self, _url: &str) {}
+
+    fn backend_failed(&self, error: &str) {
+        log::error!("Backend failed: {}", error);
+    }
+
+    fn notify(&self, title: &str, body: &str) -> Result<(), String> {
+        // No desktop to notify; the log is what the server's monitoring reads
+        log::warn!("{}: {}", title, body);
+        Ok(())
+    }
+
+    fn is_headless(&self) -> bool {
+        true
+    }
+}
+
+impl HeadlessHost {
+    /// Host with the settings file loaded
+    fn new(dirs: AppDirs, launch: LaunchOptions) -> Self {
+        let host = HeadlessHost(Arc::new(HeadlessInner {
+            state: AppState::new(launch),
+            dirs,
+        }));
+
+        match host.0.dirs.config_dir() {
+            Ok(dir) => {
+                let (loaded, problem) = settings::load_from(&dir);
+                *host.0.state.settings.lock().unwrap_or_else(|e| e.into_inner()) = loaded;
+                *host.0.state.settings_problem.lock().unwrap_or_else(|e| e.into_inner()) = problem;
+            }
+            Err(e) => log::warn!("Failed to resolve settings path, using defaults: {}", e),
+        }
+        host
+    }
+}
+
+/// Host for command-line commands that work on the app's data while it is not running
+pub fn offline_host(dirs: AppDirs, launch: LaunchOptions) -> impl SidecarHost {
+    HeadlessHost::new(dirs, launch)
+}
+
+/// Run until SIGTERM or SIGINT, then stop the backend gracefully
+pub fn run(dirs: AppDirs, launch: LaunchOptions) {
+    let host = HeadlessHost::new(dirs, launch);
+    crate::logging::apply(&host.0.state.settings.lock().unwrap_or_else(|e| e.into_inner()).logging);
+
+    log::info!("Starting in headless mode (version {})", host.0.dirs.package_info().version);
+
+    tauri::async_runtime::block_on(async {
+        if let Err(e) = crate::start_sidecar(&host).await {
+            log::error!("Failed to start sidecar: {}", e);
+            crate::mark_sidecar_failed(&host, &e);
+        }
+
+        tauri::async_runtime::spawn(crate::monitor_backend_health(host.clone()));
+        tauri::async_runtime::spawn(crate::snapshots::schedule(host.clone()));
+        let supervisor = tauri::async_runtime::spawn(crate::supervise_sidecar(host.clone()));
+
+        wait_for_shutdown_signal().await;
+        log::info!("Shutting down");
+
+        let stopping = host.clone();
+        if let Err(e) = tauri::async_runtime::spawn_blocking(move || crate::stop_sidecar(&stopping)).await {
+            log::error!("Failed to stop sidecar: {}", e);
+        }
+        let _ = supervisor.await;
+    });
+
+    log::info!("Headless shell exited");
+}
+
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = sigterm.recv() => log::info!("SIGTERM received"),
+                _ = tokio::signal::ctrl_c() => log::info!("SIGINT received"),
+            },
+            Err(e) => {
+                log::warn!("Failed to listen for SIGTERM, only SIGINT will stop the shell: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                log::info!("SIGINT received");
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        log::info!("Ctrl+C received");
+    }
+}
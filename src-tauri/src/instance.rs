@@ -0,0 +1,211 @@
+//! `instance.json`: how to reach the running shell's backend, rewritten on every
+//! sidecar state change so command-line invocations can find it.
+//!
+//! The file carries the per-launch tokens, so on Unix it is only readable by the
+//! user running the app.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::backend_client::BackendEndpoint;
+use crate::{BackendStatus, SidecarHost};
+
+const INSTANCE_FILE: &str = "instance.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceInfo {
+    pub pid: u32,
+    /// Headless instances have no window and do not take part in single-instance forwarding
+    #[serde(default)]
+    pub headless: bool,
+    pub backend: BackendStatus,
+    pub endpoint: Option<BackendEndpoint>,
+    pub shell_token: String,
+    pub channel_token: String,
+}
+
+fn instance_path(local_data_dir: &Path) -> PathBuf {
+    local_data_dir.join(INSTANCE_FILE)
+}
+
+/// Write the current state of this instance
+pub fn record(host: &impl SidecarHost) {
+    let state = host.app_state();
+    let info = InstanceInfo {
+        pid: std::process::id(),
+        headless: host.is_headless(),
+        backend: crate::backend_status(state),
+        endpoint: state.backend_endpoint.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        shell_token: state.shell_token.clone(),
+        channel_token: state.channel_token.clone(),
+    };
+
+    if let Err(e) = host.local_data_dir().and_then(|dir| write(&dir, &info)) {
+        log::warn!("Failed to record instance state: {}", e);
+    }
+}
+
+fn write(local_data_dir: &Path, info: &InstanceInfo) -> Result<(), String> {
+    std::fs::create_dir_all(local_data_dir).map_err(|e| e.to_string())?;
+
+    let path = instance_path(local_data_dir);
+    let temp = path.with_extension("json.tmp");
+    let contents = serde_json::to_vec_pretty(info).map_err(|e| e.to_string())?;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    {
+        use std::io::Write;
+        let mut file = options.open(&temp).map_err(|e| format!("Failed to open {:?}: {}", temp, e))?;
+        file.write_all(&contents).map_err(|e| format!("Failed to write {:?}: {}", temp, e))?;
+    }
+
+    // Readers never see a half-written file
+    std::fs::rename(&temp, &path).map_err(|e| format!("Failed to replace {:?}: {}", path, e))
+}
+
+/// Forget this instance once its backend is stopped for good
+pub fn remove(host: &impl SidecarHost) {
+    let Ok(dir) = host.local_data_dir() else {
+        return;
+    };
+
+    let path = instance_path(&dir);
+    match std::fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log::warn!("Failed to remove {:?}: {}", path, e),
+    }
+}
+
+/// The running instance, if there is one. A file left behind by a process that
+/// no longer exists is ignored.
+pub fn read(local_data_dir: &Path) -> Result<Option<InstanceInfo>, String> {
+    let path = instance_path(local_data_dir);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+
+    let info: InstanceInfo =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid instance file {:?}: {}", path, e))?;
+
+    Ok(Some(info).filter(|info| is_process_alive(info.pid)))
+}
+
+#[cfg(unix)]
+pub fn is_process_alive(pid: u32) -> bool {
+    // Signal 0 only checks for existence; EPERM means it exists but belongs to another user
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn is_process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return false;
+        }
+
+        let mut code = 0u32;
+        let queried = GetExitCodeProcess(handle, &mut code) != 0;
+        CloseHandle(handle);
+        queried && code == STILL_ACTIVE as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SidecarState;
+
+    fn info(pid: u32) -> InstanceInfo {
+        InstanceInfo {
+            pid,
+            headless: true,
+            backend: BackendStatus {
+                state: SidecarState::Healthy,
+                url: Some("http://127.0.0.1:4096".to_string()),
+            },
+            endpoint: Some(BackendEndpoint::Tcp(4096)),
+            shell_token: "shell-token".to_string(),
+            channel_token: "channel-token".to_string(),
+        }
+    }
+
+    #[test]
+    fn running_instance_is_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), &info(std::process::id())).unwrap();
+
+        let read = read(dir.path()).unwrap().unwrap();
+
+        assert_eq!(read.pid, std::process::id());
+        assert!(read.headless);
+        assert_eq!(read.backend.state, SidecarState::Healthy);
+        assert_eq!(read.endpoint, Some(BackendEndpoint::Tcp(4096)));
+        assert_eq!(read.channel_token, "channel-token");
+        assert!(!dir.path().join("instance.json.tmp").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn instance_file_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), &info(std::process::id())).unwrap();
+
+        let mode = std::fs::metadata(instance_path(dir.path())).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_left_by_a_process_that_exited_is_ignored() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), &info(child.id())).unwrap();
+
+        assert!(!is_process_alive(child.id()));
+        assert!(read(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn no_file_is_no_instance() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(read(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(instance_path(dir.path()), "{").unwrap();
+
+        assert!(read(dir.path()).is_err_and(|e| e.starts_with("Invalid instance file")));
+    }
+
+    #[test]
+    fn instances_from_before_headless_mode_are_not_headless() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut json = serde_json::to_value(info(std::process::id())).unwrap();
+        json.as_object_mut().unwrap().remove("headless");
+        std::fs::write(instance_path(dir.path()), json.to_string()).unwrap();
+
+        assert!(!read(dir.path()).unwrap().unwrap().headless);
+    }
+}
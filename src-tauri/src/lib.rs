@@ -1,38 +1,370 @@
 use std::process::{Child, Command, Stdio};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
 use std::sync::Mutex;
-use std::io::{BufRead, BufReader};
-use tauri::{AppHandle, Manager, State};
-use tauri::menu::{MenuBuilder, MenuItemBuilder};
-use tauri::tray::{TrayIconBuilder, TrayIconEvent};
-use tauri::image::Image;
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_deep_link::DeepLinkExt;
+
+mod app_dirs;
+mod backend_client;
+mod cli;
+mod deep_link;
+mod diagnostics;
+mod headless;
+mod instance;
+mod log_viewer;
+mod logging;
+mod preflight;
+mod secrets;
+mod server_events;
+mod settings;
+mod sidecar_process;
+mod snapshots;
+mod state_backup;
+mod storage;
+mod tray;
+mod upgrade;
+
+use settings::{NotificationSettings, ShellSettings, SidecarTransport, StorageSettings, UpgradeRecord};
+use storage::{StorageLocation, StoragePaths};
+use log_viewer::{LogFile, LogFilter, LogPage, LogRange};
+use preflight::PreflightReport;
+use secrets::{MasterPassword, PasswordStore};
+use snapshots::{SnapshotInfo, SnapshotReason};
+use tray::RunningBackup;
+
+/// Typed client for the backend API, also usable by code embedding the shell
+pub use backend_client::{
+    BackendClient, BackendEndpoint, BackendError, BackupCompletedEvent, BackupEvent, BackupProgressEvent,
+    BackupSchedule, Capabilities, EventStream, MirrorEvent, NotificationDestination, RawServerEvent, Repository,
+    ServerEvent, SystemInfo, UpdateInfo, Volume, VolumeEvent, VolumeStatusEvent,
+};
+
+/// Delay before the first restart of a crashed sidecar, doubled on each consecutive crash
+const RESTART_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// A sidecar that stayed up at least this long is considered stable again
+const SIDECAR_STABLE_UPTIME: Duration = Duration::from_secs(300);
+/// Stop restarting after this many consecutive short-lived runs (crash loop)
+const MAX_CONSECUTIVE_CRASHES: u32 = 5;
+const SUPERVISOR_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Health check results kept for support bundles
+const HEALTH_HISTORY_LEN: usize = 20;
+const FILE_NAME_TIMESTAMP_FORMAT: &[time::format_description::BorrowedFormatItem<'static>] =
+    time::macros::format_description!("[year][month][day]-[hour][minute]");
+
+/// Emitted to the webview with a `BackendStatus` payload on every state transition
+const BACKEND_STATUS_EVENT: &str = "backend://status";
+/// Emitted by `start_sidecar` once the backend answers its healthcheck
+const BACKEND_READY_EVENT: &str = "backend://ready";
+/// Emitted when a (re)start of the backend fails
+const BACKEND_FAILED_EVENT: &str = "backend://failed";
+/// Backend events relayed to the webview when it cannot subscribe itself (local socket transport)
+const BACKEND_EVENT: &str = "backend://event";
+/// Emitted with the full `ShellSettings` whenever they are saved
+const SETTINGS_CHANGED_EVENT: &str = "settings://changed";
 
 struct AppState {
-    backend_port: Mutex<Option<u16>>,
-    sidecar_running: Mutex<bool>,
+    backend_endpoint: Mutex<Option<BackendEndpoint>>,
+    sidecar_state: Mutex<SidecarState>,
     sidecar_process: Mutex<Option<Child>>,
+    sidecar_started_at: Mutex<Option<Instant>>,
+    sidecar_stopping: Mutex<bool>,
+    /// Set while the sidecar is down on purpose (e.g. its data is being moved) and
+    /// will be started again; the supervisor waits instead of restarting it
+    sidecar_suspended: Mutex<bool>,
+    supervisor: Mutex<SupervisorStatus>,
+    /// The last `HEALTH_HISTORY_LEN` health checks, oldest first
+    health_checks: Mutex<VecDeque<HealthCheck>>,
+    /// Result of the checks run before the last sidecar start
+    preflight: Mutex<Option<PreflightReport>>,
+    /// Where the restic password of the last sidecar start came from
+    password_store: Mutex<Option<PasswordStore>>,
+    settings: Mutex<ShellSettings>,
+    /// Why the settings file was not loaded, reported by the preflight checks
+    settings_problem: Mutex<Option<settings::LoadProblem>>,
+    /// Backups currently in progress, keyed by schedule id
+    running_backups: Mutex<BTreeMap<i64, RunningBackup>>,
+    /// Schedules listed in the tray's backups submenu
+    backup_schedules: Mutex<Vec<BackupSchedule>>,
+    /// Per-launch secret the sidecar accepts as the shell's own credentials
+    shell_token: String,
+    /// Per-launch secret the sidecar requires on every request, shared with the webview
+    channel_token: String,
+    /// Route the webview should open once it has loaded, consumed by `take_pending_route`
+    pending_route: Mutex<Option<String>>,
+    /// Task emitting new log entries to the log viewer, started by `watch_logs`
+    log_tail: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    /// `--data-dir` and `--port` given at startup
+    launch: cli::LaunchOptions,
+}
+
+impl AppState {
+    fn new(launch: cli::LaunchOptions) -> Self {
+        Self {
+            backend_endpoint: Mutex::new(None),
+            sidecar_state: Mutex::new(SidecarState::Stopped),
+            sidecar_process: Mutex::new(None),
+            sidecar_started_at: Mutex::new(None),
+            sidecar_stopping: Mutex::new(false),
+            sidecar_suspended: Mutex::new(false),
+            supervisor: Mutex::new(SupervisorStatus::default()),
+            health_checks: Mutex::new(VecDeque::with_capacity(HEALTH_HISTORY_LEN)),
+            preflight: Mutex::new(None),
+            password_store: Mutex::new(None),
+            settings: Mutex::new(ShellSettings::default()),
+            settings_problem: Mutex::new(None),
+            running_backups: Mutex::new(BTreeMap::new()),
+            backup_schedules: Mutex::new(Vec::new()),
+            shell_token: generate_token(),
+            channel_token: generate_token(),
+            pending_route: Mutex::new(None),
+            log_tail: Mutex::new(None),
+            launch,
+        }
+    }
+}
+
+/// Lifecycle of the backend sidecar as seen by the shell
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+enum SidecarState {
+    /// Spawned (or respawned) and waiting for the first successful healthcheck
+    Starting,
+    Healthy,
+    /// Process is alive but not answering its healthcheck
+    Degraded,
+    /// Process exited or could not be started
+    Crashed { exit: String, code: Option<i32> },
+    Stopping,
+    Stopped,
+}
+
+impl SidecarState {
+    /// Whether the backend process is up and its URL can be handed out
+    fn has_url(&self) -> bool {
+        matches!(self, SidecarState::Healthy | SidecarState::Degraded)
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendStatus {
+    #[serde(flatten)]
+    state: SidecarState,
+    url: Option<String>,
+}
+
+/// What the webview needs to talk to the backend, handed out by `get_backend_connection`
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendConnection {
+    url: String,
+    channel_token: String,
+    transport: SidecarTransport,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct BackendReadyPayload {
+    url: String,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct BackendFailedPayload {
+    error: String,
+}
+
+/// Restart bookkeeping exposed to the frontend through `get_sidecar_status`
+#[derive(Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SupervisorStatus {
+    restart_count: u32,
+    consecutive_crashes: u32,
+    last_exit_status: Option<String>,
+    last_exit_code: Option<i32>,
+    /// Unix timestamp (seconds) of the last unexpected exit
+    last_exit_at: Option<u64>,
+    /// Set once the crash-loop ceiling is reached, or a start failed in a way a retry
+    /// cannot fix, and automatic restarts are disabled until the next successful start
+    gave_up: bool,
+}
+
+/// Why `start_sidecar` failed
+#[derive(Debug)]
+enum StartError {
+    /// Starting again cannot help until something outside the shell changes: the
+    /// install is broken (the sidecar or its migrations are missing), or another
+    /// instance runs the backend of the data directory
+    Fatal(String),
+    /// The sidecar could not be spawned or did not come up; another try may work
+    Failed(String),
+}
+
+impl StartError {
+    fn is_fatal(&self) -> bool {
+        matches!(self, StartError::Fatal(_))
+    }
+}
+
+impl std::fmt::Display for StartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartError::Fatal(e) | StartError::Failed(e) => f.write_str(e),
+        }
+    }
+}
+
+impl From<String> for StartError {
+    fn from(e: String) -> Self {
+        StartError::Failed(e)
+    }
+}
+
+/// Outcome of one periodic health check
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HealthCheck {
+    /// Unix timestamp (seconds)
+    at: u64,
+    duration_ms: u64,
+    error: Option<String>,
+}
+
+/// What the sidecar lifecycle needs from its surroundings, so the same start,
+/// stop and supervision code runs in the desktop app and in `--headless` mode
+trait SidecarHost: Clone + Send + Sync + 'static {
+    fn app_state(&self) -> &AppState;
+    fn resource_dir(&self) -> Result<PathBuf, String>;
+    fn data_dir(&self) -> Result<PathBuf, String>;
+    fn local_data_dir(&self) -> Result<PathBuf, String>;
+    fn log_dir(&self) -> Result<PathBuf, String>;
+    /// Where the settings file lives
+    fn config_dir(&self) -> Result<PathBuf, String>;
+    /// Called after every lifecycle transition
+    fn sidecar_state_changed(&self, status: &BackendStatus);
+    fn backend_ready(&self, url: &str);
+    fn backend_failed(&self, error: &str);
+    fn notify(&self, title: &str, body: &str) -> Result<(), String>;
+
+    /// Whether this process runs without a window (`--headless`)
+    fn is_headless(&self) -> bool {
+        false
+    }
+}
+
+impl SidecarHost for AppHandle {
+    fn app_state(&self) -> &AppState {
+        self.state::<AppState>().inner()
+    }
+
+    fn resource_dir(&self) -> Result<PathBuf, String> {
+        self.path().resource_dir().map_err(|e| e.to_string())
+    }
+
+    fn data_dir(&self) -> Result<PathBuf, String> {
+        match &self.app_state().launch.data_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => self.path().app_data_dir().map_err(|e| e.to_string()),
+        }
+    }
+
+    fn local_data_dir(&self) -> Result<PathBuf, String> {
+        self.path().app_local_data_dir().map_err(|e| e.to_string())
+    }
+
+    fn log_dir(&self) -> Result<PathBuf, String> {
+        self.path().app_log_dir().map_err(|e| e.to_string())
+    }
+
+    fn config_dir(&self) -> Result<PathBuf, String> {
+        self.path()
+            .app_config_dir()
+            .map(normalize_windows_path)
+            .map_err(|e| e.to_string())
+    }
+
+    fn sidecar_state_changed(&self, status: &BackendStatus) {
+        tray::refresh(self);
+
+        if let Err(e) = self.emit(BACKEND_STATUS_EVENT, status) {
+            log::warn!("Failed to emit backend status: {}", e);
+        }
+    }
+
+    fn backend_ready(&self, url: &str) {
+        let payload = BackendReadyPayload { url: url.to_string() };
+        if let Err(e) = self.emit(BACKEND_READY_EVENT, payload) {
+            log::warn!("Failed to emit backend ready event: {}", e);
+        }
+    }
+
+    fn backend_failed(&self, error: &str) {
+        let payload = BackendFailedPayload {
+            error: error.to_string(),
+        };
+        if let Err(e) = self.emit(BACKEND_FAILED_EVENT, payload) {
+            log::warn!("Failed to emit backend failed event: {}", e);
+        }
+    }
+
+    fn notify(&self, title: &str, body: &str) -> Result<(), String> {
+        show_notification(self, title, body)
+    }
+}
+
+struct SidecarExit {
+    description: String,
+    code: Option<i32>,
+    uptime: Duration,
 }
 
 #[tauri::command]
 fn get_backend_url(state: State<AppState>) -> Result<String, String> {
-    let port = state.backend_port.lock().map_err(|e| e.to_string())?;
-    match *port {
-        Some(p) => Ok(format!("http://localhost:{}", p)),
-        None => Err("Backend not started yet".to_string()),
-    }
+    backend_status(&state)
+        .url
+        .ok_or_else(|| "Backend not started yet".to_string())
+}
+
+/// The only way the webview learns the channel token; it is never put in the page. The events stream carries it in
+/// its query string since EventSource cannot set headers, so the sidecar redacts it from its request logs
+#[tauri::command]
+fn get_backend_connection(state: State<AppState>) -> Result<BackendConnection, String> {
+    let endpoint = backend_endpoint(&state).ok_or_else(|| "Backend not started yet".to_string())?;
+    let transport = match endpoint {
+        BackendEndpoint::Tcp(_) => SidecarTransport::Tcp,
+        BackendEndpoint::LocalSocket(_) => SidecarTransport::LocalSocket,
+    };
+
+    Ok(BackendConnection {
+        url: endpoint.webview_url(),
+        channel_token: state.channel_token.clone(),
+        transport,
+    })
+}
+
+#[tauri::command]
+fn get_backend_status(state: State<AppState>) -> BackendStatus {
+    backend_status(&state)
+}
+
+/// Route requested at launch (`open <page>`), handed to the webview once
+#[tauri::command]
+fn take_pending_route(state: State<AppState>) -> Option<String> {
+    state.pending_route.lock().ok().and_then(|mut route| route.take())
 }
 
 #[tauri::command]
 fn get_data_dir(app: AppHandle) -> Result<String, String> {
-    app.path()
-        .app_data_dir()
-        .map(|p| normalize_windows_path(p).to_string_lossy().to_string())
-        .map_err(|e| e.to_string())
+    storage::resolve(&app).map(|paths| paths.data_dir.to_string_lossy().to_string())
 }
 
 #[tauri::command]
 fn open_data_dir(app: AppHandle) -> Result<(), String> {
-    let data_dir = normalize_windows_path(app.path().app_data_dir().map_err(|e| e.to_string())?);
+    let data_dir = storage::resolve(&app)?.data_dir;
 
     #[cfg(target_os = "linux")]
     {
@@ -92,9 +424,431 @@ fn open_logs_dir(app: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+/// The shell's own log file, which the sidecar log sits next to
+fn shell_log_file(app: &AppHandle) -> Result<PathBuf, String> {
+    let logs_dir = normalize_windows_path(app.path().app_log_dir().map_err(|e| e.to_string())?);
+    Ok(logs_dir.join(format!("{}.log", app.package_info().name)))
+}
+
+/// Shell and sidecar log files, current ones first
+#[tauri::command]
+fn list_log_files(app: AppHandle) -> Result<Vec<LogFile>, String> {
+    log_viewer::list(&shell_log_file(&app)?)
+}
+
+/// Entries of a log file from `list_log_files` that match `filter`, within `range`
+#[tauri::command]
+async fn read_log(app: AppHandle, file: String, filter: LogFilter, range: LogRange) -> Result<LogPage, String> {
+    let shell_log = shell_log_file(&app)?;
+    tauri::async_runtime::spawn_blocking(move || log_viewer::read(&shell_log, &file, &filter, &range))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Emit entries matching `filter` as they are appended to the current logs, replacing
+/// any previous watch
+#[tauri::command]
+fn watch_logs(app: AppHandle, filter: LogFilter) -> Result<(), String> {
+    let shell_log = shell_log_file(&app)?;
+    let task = tauri::async_runtime::spawn(log_viewer::tail(app.clone(), shell_log, filter));
+
+    let state = app.state::<AppState>();
+    if let Some(previous) = state.log_tail.lock().map_err(|e| e.to_string())?.replace(task) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn unwatch_logs(state: State<AppState>) -> Result<(), String> {
+    if let Some(task) = state.log_tail.lock().map_err(|e| e.to_string())?.take() {
+        task.abort();
+    }
+    Ok(())
+}
+
+/// Let the user choose where to save a support bundle and write it there. Returns
+/// the path, or `None` when the dialog was cancelled.
+#[tauri::command]
+async fn create_support_bundle(app: AppHandle) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+    let file_name = format!(
+        "c3i-backup-one-soporte-{}.zip",
+        now.format(FILE_NAME_TIMESTAMP_FORMAT).unwrap_or_default()
+    );
+    let Some(path) = app
+        .dialog()
+        .file()
+        .set_title("Guardar paquete de diagnóstico")
+        .set_file_name(file_name)
+        .add_filter("ZIP", &["zip"])
+        .blocking_save_file()
+    else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| e.to_string())?;
+
+    let shell_log = shell_log_file(&app)?;
+    let (host, output) = (app.clone(), path.clone());
+    tauri::async_runtime::spawn_blocking(move || diagnostics::create_support_bundle(&host, &shell_log, &output))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    log::info!("Support bundle written to {:?}", path);
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+/// What `restore_app_state` restored
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreSummary {
+    app_version: String,
+    /// Unix timestamp (seconds) the archive was made at
+    created_at: u64,
+    /// Archive of the replaced state, in the data directory
+    previous_state: Option<String>,
+}
+
+/// Refuse to stop the backend for a state archive while it is backing up, or when
+/// there is no backend of ours to stop (development server)
+fn ensure_state_can_be_archived(state: &AppState) -> Result<(), String> {
+    if cfg!(debug_assertions) {
+        return Err("The app state cannot be archived while using the development server".to_string());
+    }
+    if !state.running_backups.lock().map_err(|e| e.to_string())?.is_empty() {
+        return Err("A backup is running; wait for it to finish".to_string());
+    }
+    Ok(())
+}
+
+/// Let the user choose where to save an encrypted archive of the database, restic
+/// password and settings, and write it there while the backend is stopped. Returns
+/// the path, or `None` when the dialog was cancelled.
+#[tauri::command]
+async fn export_app_state(app: AppHandle, passphrase: String) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    if passphrase.chars().count() < state_backup::MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "The passphrase must have at least {} characters",
+            state_backup::MIN_PASSPHRASE_LEN
+        ));
+    }
+    ensure_state_can_be_archived(app.app_state())?;
+
+    let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+    let file_name = format!(
+        "c3i-backup-one-configuracion-{}.{}",
+        now.format(FILE_NAME_TIMESTAMP_FORMAT).unwrap_or_default(),
+        state_backup::ARCHIVE_EXTENSION
+    );
+    let Some(path) = app
+        .dialog()
+        .file()
+        .set_title("Guardar copia de la configuración")
+        .set_file_name(file_name)
+        .add_filter("Copia de la configuración", &[state_backup::ARCHIVE_EXTENSION])
+        .blocking_save_file()
+    else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| e.to_string())?;
+
+    let (host, output) = (app.clone(), path.clone());
+    with_sidecar_suspended(&app, move || state_backup::export(&host, &output, &passphrase)).await?;
+
+    log::info!("App state archived to {:?}", path);
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+/// Let the user choose a state archive and restore it while the backend is
+/// stopped, keeping this machine's storage locations. Returns `None` when the
+/// dialog was cancelled.
+#[tauri::command]
+async fn restore_app_state(app: AppHandle, passphrase: String) -> Result<Option<RestoreSummary>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    ensure_state_can_be_archived(app.app_state())?;
+
+    let Some(path) = app
+        .dialog()
+        .file()
+        .set_title("Restaurar copia de la configuración")
+        .add_filter("Copia de la configuración", &[state_backup::ARCHIVE_EXTENSION])
+        .blocking_pick_file()
+    else {
+        return Ok(None);
+    };
+    let archive = path.into_path().map_err(|e| e.to_string())?;
+
+    log::info!("Restoring the app state from {:?}", archive);
+    let host = app.clone();
+    let restored = with_sidecar_suspended(&app, move || {
+        let restored = state_backup::restore(&host, &archive, &passphrase)?;
+        // Before the backend starts again, so it runs with the restored settings
+        if let Some(settings) = restored.settings.clone() {
+            apply_settings(&host, settings)?;
+        }
+        Ok(restored)
+    })
+    .await?;
+
+    Ok(Some(RestoreSummary {
+        app_version: restored.manifest.app_version,
+        created_at: restored.manifest.created_at,
+        previous_state: restored.previous_state.map(|path| path.to_string_lossy().to_string()),
+    }))
+}
+
+#[tauri::command]
+fn list_snapshots(app: AppHandle) -> Result<Vec<SnapshotInfo>, String> {
+    snapshots::list(&app)
+}
+
+/// Snapshot the current state now, then apply the retention setting
+#[tauri::command]
+async fn take_snapshot(app: AppHandle) -> Result<Option<SnapshotInfo>, String> {
+    let keep = app.app_state().settings.lock().map_err(|e| e.to_string())?.snapshots.keep;
+    let host = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let snapshot = snapshots::take(&host, SnapshotReason::Manual)?;
+        snapshots::prune(&host, keep)?;
+        Ok(snapshot)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Roll the database back to snapshot `id` while the backend is stopped
+#[tauri::command]
+async fn rollback_snapshot(app: AppHandle, id: String) -> Result<SnapshotInfo, String> {
+    ensure_state_can_be_archived(app.app_state())?;
+
+    log::info!("Rolling back to snapshot {}", id);
+    let host = app.clone();
+    with_sidecar_suspended(&app, move || {
+        let rolled_back = snapshots::rollback(&host, &id)?;
+        // Before the backend starts again, so it runs with the snapshot's settings
+        if let Some(settings) = rolled_back.settings {
+            apply_settings(&host, settings)?;
+        }
+        Ok(rolled_back.snapshot)
+    })
+    .await
+}
+
+/// Roll the database back to the snapshot taken before the upgrade whose backend
+/// has not started yet, then start the backend again
+#[tauri::command]
+async fn rollback_upgrade(app: AppHandle) -> Result<UpgradeRecord, String> {
+    ensure_state_can_be_archived(app.app_state())?;
+
+    let host = app.clone();
+    with_sidecar_suspended(&app, move || {
+        let (record, settings) = upgrade::rollback(&host)?;
+        apply_settings(&host, settings)?;
+        Ok(record)
+    })
+    .await
+}
+
 #[tauri::command]
 fn is_backend_ready(state: State<AppState>) -> bool {
-    *state.sidecar_running.lock().unwrap_or_else(|e| e.into_inner())
+    *state.sidecar_state.lock().unwrap_or_else(|e| e.into_inner()) == SidecarState::Healthy
+}
+
+#[tauri::command]
+fn get_sidecar_status(state: State<AppState>) -> Result<SupervisorStatus, String> {
+    state.supervisor.lock().map(|s| s.clone()).map_err(|e| e.to_string())
+}
+
+/// Checks run before the last sidecar start, `None` until the first start
+#[tauri::command]
+fn get_preflight_report(state: State<AppState>) -> Result<Option<PreflightReport>, String> {
+    state.preflight.lock().map(|p| p.clone()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_notification_settings(state: State<AppState>) -> Result<NotificationSettings, String> {
+    state
+        .settings
+        .lock()
+        .map(|s| s.notifications.clone())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_notification_settings(
+    app: AppHandle,
+    state: State<AppState>,
+    notifications: NotificationSettings,
+) -> Result<(), String> {
+    let mut settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    settings.notifications = notifications;
+    apply_settings(&app, settings)
+}
+
+#[tauri::command]
+fn get_settings(state: State<AppState>) -> Result<ShellSettings, String> {
+    state.settings.lock().map(|s| s.clone()).map_err(|e| e.to_string())
+}
+
+/// Merge `patch` (a partial `ShellSettings` object) into the current settings,
+/// validate the result, save it and apply what can change at runtime. Sidecar
+/// settings take effect on its next start.
+#[tauri::command]
+fn update_settings(app: AppHandle, state: State<AppState>, patch: serde_json::Value) -> Result<ShellSettings, String> {
+    let current = state.settings.lock().map_err(|e| e.to_string())?.clone();
+
+    let mut merged = serde_json::to_value(&current).map_err(|e| e.to_string())?;
+    merge_json(&mut merged, patch);
+    let updated: ShellSettings = serde_json::from_value(merged).map_err(|e| format!("Invalid settings: {}", e))?;
+
+    if updated.version != current.version {
+        return Err("The settings version cannot be changed".to_string());
+    }
+    if updated.storage != current.storage {
+        return Err("Storage locations are changed with set_storage_location, which moves the data".to_string());
+    }
+    if updated.upgrades != current.upgrades {
+        return Err("The upgrade history cannot be changed".to_string());
+    }
+
+    apply_settings(&app, updated.clone())?;
+    Ok(updated)
+}
+
+/// Recursively merge `patch` into `target`, as in JSON Merge Patch but keeping `null`s
+fn merge_json(target: &mut serde_json::Value, patch: serde_json::Value) {
+    match (target, patch) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+/// Validate, persist and apply new settings, then tell the webview
+fn apply_settings(app: &AppHandle, settings: ShellSettings) -> Result<(), String> {
+    settings.validate()?;
+    settings::save(app, &settings)?;
+
+    logging::apply(&settings.logging);
+    *app.state::<AppState>().settings.lock().map_err(|e| e.to_string())? = settings.clone();
+    tray::apply_settings(app, &settings);
+
+    if let Err(e) = app.emit(SETTINGS_CHANGED_EVENT, settings) {
+        log::warn!("Failed to emit settings changed event: {}", e);
+    }
+    Ok(())
+}
+
+/// Storage settings as the settings page shows them
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StorageInfo {
+    /// Locations chosen in the settings; unset ones follow the data directory
+    settings: StorageSettings,
+    /// Where everything currently is
+    paths: StoragePaths,
+    /// `--data-dir` was given, so the data directory setting does not apply
+    data_dir_pinned: bool,
+    /// Where the restic password is kept, once the sidecar has started
+    password_store: Option<PasswordStore>,
+}
+
+fn storage_info(app: &AppHandle) -> Result<StorageInfo, String> {
+    let state = app.state::<AppState>();
+    Ok(StorageInfo {
+        settings: state.settings.lock().map_err(|e| e.to_string())?.storage.clone(),
+        paths: storage::resolve(app)?,
+        data_dir_pinned: state.launch.data_dir.is_some(),
+        password_store: *state.password_store.lock().map_err(|e| e.to_string())?,
+    })
+}
+
+#[tauri::command]
+fn get_storage_settings(app: AppHandle) -> Result<StorageInfo, String> {
+    storage_info(&app)
+}
+
+/// Move one storage location to `dir` (or back to its default with `None`). With
+/// `migrate`, the existing data is moved there while the backend is stopped;
+/// otherwise the backend starts over with whatever the new location holds.
+#[tauri::command]
+async fn set_storage_location(
+    app: AppHandle,
+    location: StorageLocation,
+    dir: Option<String>,
+    migrate: bool,
+) -> Result<StorageInfo, String> {
+    if cfg!(debug_assertions) {
+        return Err("Storage locations cannot be changed while using the development server".to_string());
+    }
+
+    let state = app.state::<AppState>();
+    if location == StorageLocation::Data && state.launch.data_dir.is_some() {
+        return Err("The data directory was set with --data-dir for this launch".to_string());
+    }
+
+    let mut updated = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    updated.storage.set(location, dir.map(PathBuf::from));
+
+    let from = storage::resolve(&app)?;
+    let to = storage::resolve_with(&app, &updated.storage)?;
+    if from == to {
+        return storage_info(&app);
+    }
+
+    storage::ensure_writable(to.dir(location))?;
+    if migrate {
+        storage::check_migration(&from, &to)?;
+    }
+
+    // A secret store entry belongs to a data directory, so it follows the data
+    let move_password = migrate
+        && from.data_dir != to.data_dir
+        && *state.password_store.lock().map_err(|e| e.to_string())? == Some(PasswordStore::Keyring);
+
+    log::info!("Moving {:?} storage to {:?} (migrate data: {})", location, to.dir(location), migrate);
+    let host = app.clone();
+    with_sidecar_suspended(&app, move || {
+        if move_password {
+            secrets::copy(&from, &to)?;
+        }
+        if migrate {
+            storage::migrate(&from, &to)?;
+        }
+        if move_password {
+            secrets::forget(&from);
+        }
+        apply_settings(&host, updated)
+    })
+    .await?;
+
+    storage_info(&app)
+}
+
+/// Let the user choose a directory with the native dialog
+#[tauri::command]
+async fn pick_directory(app: AppHandle, title: String) -> Option<String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    app.dialog()
+        .file()
+        .set_title(title)
+        .blocking_pick_folder()
+        .and_then(|path| path.into_path().ok())
+        .map(|path| path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
@@ -114,8 +868,25 @@ async fn set_autostart_enabled(app: tauri::AppHandle, enable: bool) -> Result<()
     }
 }
 
+/// Debug builds run against the Vite dev server, which the shell cannot subscribe
+/// to without the launch tokens, so the webview hands over the events it receives
+#[tauri::command]
+fn handle_backend_event(app: AppHandle, event: String, data: String) -> Result<(), String> {
+    if !cfg!(debug_assertions) {
+        return Err("The shell subscribes to backend events itself".to_string());
+    }
+
+    let event = RawServerEvent { event, data }.parse().map_err(|e| e.to_string())?;
+    server_events::handle_event(&app, &event);
+    Ok(())
+}
+
 #[tauri::command]
 async fn send_notification(app: tauri::AppHandle, title: String, body: String) -> Result<(), String> {
+    show_notification(&app, &title, &body)
+}
+
+fn show_notification(app: &AppHandle, title: &str, body: &str) -> Result<(), String> {
     use tauri_plugin_notification::NotificationExt;
 
     app.notification()
@@ -126,6 +897,17 @@ async fn send_notification(app: tauri::AppHandle, title: String, body: String) -
         .map_err(|e| e.to_string())
 }
 
+fn generate_token() -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::Rng;
+
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(48)
+        .map(char::from)
+        .collect()
+}
+
 fn normalize_windows_path(path: std::path::PathBuf) -> std::path::PathBuf {
     // Remove Windows UNC prefix (\\?\) if present
     if cfg!(target_os = "windows") {
@@ -140,9 +922,9 @@ fn normalize_windows_path(path: std::path::PathBuf) -> std::path::PathBuf {
     path
 }
 
-fn get_sidecar_path(app: &AppHandle) -> Option<std::path::PathBuf> {
+fn get_sidecar_path(host: &impl SidecarHost) -> Option<std::path::PathBuf> {
     // First check if there's a compiled sidecar in resources
-    if let Ok(resource_dir) = app.path().resource_dir() {
+    if let Ok(resource_dir) = host.resource_dir() {
         // Normalize Windows UNC paths
         let resource_dir = normalize_windows_path(resource_dir);
 
@@ -187,9 +969,8 @@ fn get_sidecar_path(app: &AppHandle) -> Option<std::path::PathBuf> {
     None
 }
 
-fn get_resources_path(app: &AppHandle) -> Option<std::path::PathBuf> {
-    app.path()
-        .resource_dir()
+fn get_resources_path(host: &impl SidecarHost) -> Option<std::path::PathBuf> {
+    host.resource_dir()
         .ok()
         .map(|p| {
             let base = normalize_windows_path(p);
@@ -204,9 +985,8 @@ fn get_resources_path(app: &AppHandle) -> Option<std::path::PathBuf> {
         })
 }
 
-fn get_migrations_path(app: &AppHandle) -> Option<std::path::PathBuf> {
-    app.path()
-        .resource_dir()
+fn get_migrations_path(host: &impl SidecarHost) -> Option<std::path::PathBuf> {
+    host.resource_dir()
         .ok()
         .map(|p| {
             let base = normalize_windows_path(p);
@@ -221,134 +1001,218 @@ fn get_migrations_path(app: &AppHandle) -> Option<std::path::PathBuf> {
         })
 }
 
-async fn start_sidecar_async(app: AppHandle) -> Result<(), String> {
+fn backend_status(state: &AppState) -> BackendStatus {
+    let sidecar_state = state.sidecar_state.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let url = backend_endpoint(state).map(|e| e.webview_url());
+
+    BackendStatus {
+        state: sidecar_state,
+        url,
+    }
+}
+
+/// Where the sidecar can be reached, while it is up
+fn backend_endpoint(state: &AppState) -> Option<BackendEndpoint> {
+    if !state.sidecar_state.lock().unwrap_or_else(|e| e.into_inner()).has_url() {
+        return None;
+    }
+    state.backend_endpoint.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Move the sidecar to a new lifecycle state and notify the host (tray and webview)
+fn set_sidecar_state(host: &impl SidecarHost, next: SidecarState) {
+    let state = host.app_state();
+    {
+        let mut current = state.sidecar_state.lock().unwrap_or_else(|e| e.into_inner());
+        if *current == next {
+            return;
+        }
+        log::info!("Sidecar state: {:?} -> {:?}", *current, next);
+        *current = next;
+    }
+
+    instance::record(host);
+    host.sidecar_state_changed(&backend_status(state));
+}
+
+/// Record a failed (re)start and tell the webview about it. The state is only
+/// changed if `start_sidecar` has not already classified the failure. A fatal
+/// failure also stops the supervisor from restarting the sidecar.
+fn mark_sidecar_failed(host: &impl SidecarHost, error: &StartError) {
+    let state = host.app_state();
+    if error.is_fatal() {
+        state.supervisor.lock().unwrap_or_else(|e| e.into_inner()).gave_up = true;
+    }
+
+    let starting = *state.sidecar_state.lock().unwrap_or_else(|e| e.into_inner()) == SidecarState::Starting;
+    if starting {
+        set_sidecar_state(
+            host,
+            SidecarState::Crashed {
+                exit: error.to_string(),
+                code: None,
+            },
+        );
+    }
+
+    host.backend_failed(&error.to_string());
+}
+
+async fn start_sidecar_async(app: AppHandle) -> Result<(), StartError> {
     // In debug mode (tauri dev), the Vite server is already running on port 4096
     // No need to spawn a separate sidecar
     if cfg!(debug_assertions) {
         log::info!("Development mode: using Vite dev server on port 4096");
         let state = app.state::<AppState>();
+        let endpoint = BackendEndpoint::Tcp(4096);
         {
-            let mut port_lock = state.backend_port.lock().map_err(|e| e.to_string())?;
-            *port_lock = Some(4096);
-        }
-        {
-            let mut running = state.sidecar_running.lock().map_err(|e| e.to_string())?;
-            *running = true;
+            let mut endpoint_lock = state.backend_endpoint.lock().map_err(|e| e.to_string())?;
+            *endpoint_lock = Some(endpoint.clone());
         }
+        set_sidecar_state(&app, SidecarState::Healthy);
+        app.backend_ready(&endpoint.webview_url());
         return Ok(());
     }
 
-    let state = app.state::<AppState>();
-    start_sidecar(app.clone(), &state).await
+    start_sidecar(&app).await
 }
 
-async fn start_sidecar(app: AppHandle, state: &AppState) -> Result<(), String> {
-    let port = portpicker::pick_unused_port().ok_or("Failed to find available port")?;
+async fn start_sidecar(host: &impl SidecarHost) -> Result<(), StartError> {
+    let state = host.app_state();
+    set_sidecar_state(host, SidecarState::Starting);
+
+    run_preflight(host).await?;
+
+    let paths = storage::resolve(host)?;
+    let recovering = paths.clone();
+    let grace = shutdown_grace(state);
+    tauri::async_runtime::spawn_blocking(move || sidecar_process::recover(&recovering, grace))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(StartError::Fatal)?;
+
+    // A new version must not migrate the database without a snapshot to roll back to
+    let upgrading = host.clone();
+    tauri::async_runtime::spawn_blocking(move || upgrade::prepare(&upgrading))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| {
+            log::error!("Failed to prepare the upgrade: {}", e);
+            StartError::Failed(e)
+        })?;
+
+    let endpoint = pick_endpoint(host)?;
 
     // Get paths and normalize them
-    let data_dir = normalize_windows_path(app.path().app_data_dir().map_err(|e| e.to_string())?);
-    let resources_path = get_resources_path(&app)
+    let resources_path = get_resources_path(host)
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_default();
-    let migrations_path = get_migrations_path(&app)
+    let migrations_path = get_migrations_path(host)
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_default();
 
-    // Ensure data directory exists
-    std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
-
-    let db_path = data_dir.join("c3i-backup-one.db");
-    let repos_dir = data_dir.join("repositories");
-    let volumes_dir = data_dir.join("volumes");
-    let cache_dir = data_dir.join("cache");
-    let passfile = data_dir.join("restic-pass");
-
     // Get the proper logs directory (platform-specific)
-    let logs_dir = normalize_windows_path(app.path().app_log_dir().map_err(|e| e.to_string())?);
-
-    // Create subdirectories
-    std::fs::create_dir_all(&repos_dir).map_err(|e| e.to_string())?;
-    std::fs::create_dir_all(&volumes_dir).map_err(|e| e.to_string())?;
-    std::fs::create_dir_all(&cache_dir).map_err(|e| e.to_string())?;
+    let logs_dir = normalize_windows_path(host.log_dir()?);
     std::fs::create_dir_all(&logs_dir).map_err(|e| e.to_string())?;
 
-    log::info!("Starting sidecar on port {}", port);
-    log::info!("Data directory: {:?}", data_dir);
+    log::info!("Starting sidecar on {}", endpoint);
+    log::info!("Data directory: {:?}", paths.data_dir);
+    if paths.repositories_dir != paths.data_dir.join("repositories") {
+        log::info!("Repositories directory: {:?}", paths.repositories_dir);
+    }
+    if paths.cache_dir != paths.data_dir.join("cache") {
+        log::info!("Cache directory: {:?}", paths.cache_dir);
+    }
     log::info!("Logs directory: {:?}", logs_dir);
     log::info!("Resources path: {}", resources_path);
     log::info!("Migrations path: {}", migrations_path);
 
+    let password_paths = paths.clone();
+    let password = tauri::async_runtime::spawn_blocking(move || secrets::load(&password_paths))
+        .await
+        .map_err(|e| e.to_string())??;
+    *state.password_store.lock().map_err(|e| e.to_string())? = Some(password.store());
+
     // Try to find the sidecar binary
-    let sidecar_path = get_sidecar_path(&app);
+    let sidecar_path = get_sidecar_path(host);
+    let launched = sidecar_path.clone().unwrap_or_else(|| PathBuf::from("bun"));
+
+    let mut env = endpoint_env(&endpoint);
+    env.extend(sidecar_env(host, &paths, &logs_dir)?);
+    if matches!(password, MasterPassword::Keyring(_)) {
+        env.push(("C3I_BACKUP_ONE_PASSWORD_STDIN", "1".to_string()));
+    }
 
     let mut child = if let Some(path) = sidecar_path {
         log::info!("Using compiled sidecar: {:?}", path);
         log::info!("Sidecar exists: {}", path.exists());
 
         // Verify migrations exist
-        if let Some(migrations) = get_migrations_path(&app) {
+        if let Some(migrations) = get_migrations_path(host) {
             log::info!("Migrations path: {:?}, exists: {}", migrations, migrations.exists());
         }
 
-        Command::new(&path)
-            .env("PORT", port.to_string())
-            .env("C3I_BACKUP_ONE_TAURI", "1")
-            .env("C3I_BACKUP_ONE_RESOURCES_PATH", &resources_path)
-            .env("C3I_BACKUP_ONE_LOGS_DIR", logs_dir.to_string_lossy().to_string())
-            .env("MIGRATIONS_PATH", &migrations_path)
-            .env("DATABASE_URL", db_path.to_string_lossy().to_string())
-            .env("C3I_BACKUP_ONE_REPOSITORIES_DIR", repos_dir.to_string_lossy().to_string())
-            .env("C3I_BACKUP_ONE_VOLUMES_DIR", volumes_dir.to_string_lossy().to_string())
-            .env("RESTIC_CACHE_DIR", cache_dir.to_string_lossy().to_string())
-            .env("RESTIC_PASS_FILE", passfile.to_string_lossy().to_string())
-            .env("NODE_ENV", "production")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn sidecar at {:?}: {}", path, e))?
+        sidecar_process::tie_to_shell(
+            Command::new(&path)
+                .envs(env)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .spawn()
+        .map_err(|e| format!("Failed to spawn sidecar at {:?}: {}", path, e))?
     } else {
         // In development, try to run with bun directly
         log::warn!("No compiled sidecar found, trying bun for development...");
 
         // Get the project root (parent of src-tauri)
-        let project_root = app
-            .path()
-            .resource_dir()
-            .map_err(|e| e.to_string())?
+        let project_root = host
+            .resource_dir()?
             .parent()
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
 
         log::info!("Project root: {:?}", project_root);
 
-        Command::new("bun")
-            .arg("run")
-            .arg("start")
-            .current_dir(&project_root)
-            .env("PORT", port.to_string())
-            .env("C3I_BACKUP_ONE_TAURI", "1")
-            .env("C3I_BACKUP_ONE_RESOURCES_PATH", &resources_path)
-            .env("MIGRATIONS_PATH", &migrations_path)
-            .env("DATABASE_URL", db_path.to_string_lossy().to_string())
-            .env("C3I_BACKUP_ONE_REPOSITORIES_DIR", repos_dir.to_string_lossy().to_string())
-            .env("C3I_BACKUP_ONE_VOLUMES_DIR", volumes_dir.to_string_lossy().to_string())
-            .env("RESTIC_CACHE_DIR", cache_dir.to_string_lossy().to_string())
-            .env("RESTIC_PASS_FILE", passfile.to_string_lossy().to_string())
-            .env("NODE_ENV", "production")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn bun server: {}", e))?
+        sidecar_process::tie_to_shell(
+            Command::new("bun")
+                .arg("run")
+                .arg("start")
+                .current_dir(&project_root)
+                .envs(env)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .spawn()
+        .map_err(|e| format!("Failed to spawn bun server: {}", e))?
     };
+    sidecar_process::attach(&child);
+
+    // Hand over the restic password through the stdin pipe, which stays open
+    // afterwards because closing it asks the sidecar to shut down
+    if let MasterPassword::Keyring(password) = &password {
+        let handoff = match child.stdin.as_mut() {
+            Some(stdin) => stdin
+                .write_all(secrets::handoff_line(password).as_bytes())
+                .and_then(|()| stdin.flush())
+                .map_err(|e| e.to_string()),
+            None => Err("sidecar stdin is not piped".to_string()),
+        };
+        if let Err(e) = handoff {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("Failed to hand the restic password to the sidecar: {}", e).into());
+        }
+    }
+    sidecar_process::record(&paths, &child, &launched);
 
-    // Capture and log sidecar output
+    // Capture and log sidecar output at the levels the backend gave it
     if let Some(stdout) = child.stdout.take() {
         let reader = BufReader::new(stdout);
         std::thread::spawn(move || {
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    log::info!("[SIDECAR] {}", line);
-                }
+            for line in reader.lines().map_while(Result::ok) {
+                logging::log_sidecar_line(&line, logging::SidecarStream::Stdout);
             }
         });
     }
@@ -356,41 +1220,51 @@ async fn start_sidecar(app: AppHandle, state: &AppState) -> Result<(), String> {
     if let Some(stderr) = child.stderr.take() {
         let reader = BufReader::new(stderr);
         std::thread::spawn(move || {
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    log::error!("[SIDECAR] {}", line);
-                }
+            for line in reader.lines().map_while(Result::ok) {
+                logging::log_sidecar_line(&line, logging::SidecarStream::Stderr);
             }
         });
     }
 
-    // Store the port and process
+    // Store the endpoint and process
     {
-        let mut port_lock = state.backend_port.lock().map_err(|e| e.to_string())?;
-        *port_lock = Some(port);
+        let mut endpoint_lock = state.backend_endpoint.lock().map_err(|e| e.to_string())?;
+        *endpoint_lock = Some(endpoint.clone());
     }
     {
         let mut process_lock = state.sidecar_process.lock().map_err(|e| e.to_string())?;
         *process_lock = Some(child);
     }
+    {
+        let mut started_at = state.sidecar_started_at.lock().map_err(|e| e.to_string())?;
+        *started_at = Some(Instant::now());
+    }
 
     // Wait for server to be ready
-    let backend_url = format!("http://localhost:{}", port);
+    let client = BackendClient::new(&endpoint, &state.shell_token, &state.channel_token);
     let mut attempts = 0;
-    let max_attempts = 120; // 60 seconds
+    let startup_timeout = state
+        .settings
+        .lock()
+        .map(|s| s.sidecar.startup_timeout_secs)
+        .unwrap_or_else(|_| settings::SidecarSettings::default().startup_timeout_secs);
+    let max_attempts = startup_timeout * 2; // every 500 ms
 
-    log::info!("Waiting for backend to respond at {}/healthcheck", backend_url);
+    log::info!("Waiting for backend to respond on {}", endpoint);
 
     while attempts < max_attempts {
-        match reqwest::get(format!("{}/healthcheck", backend_url)).await {
-            Ok(resp) if resp.status().is_success() => {
-                log::info!("Backend is ready on port {}", port);
-                let mut running = state.sidecar_running.lock().map_err(|e| e.to_string())?;
-                *running = true;
+        match client.healthcheck(Duration::from_secs(5)).await {
+            Ok(()) => {
+                log::info!("Backend is ready on {}", endpoint);
+                set_sidecar_state(host, SidecarState::Healthy);
+                resume_supervision(state);
+                upgrade::confirm(host);
+                host.backend_ready(&endpoint.webview_url());
+                tauri::async_runtime::spawn(backend_client::check_api_compatibility(client));
                 return Ok(());
             }
-            Ok(resp) => {
-                log::warn!("Backend responded with status: {} (attempt {}/{})", resp.status(), attempts + 1, max_attempts);
+            Err(e @ backend_client::BackendError::Status { .. }) => {
+                log::warn!("Backend responded with {} (attempt {}/{})", e, attempts + 1, max_attempts);
                 attempts += 1;
                 tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
             }
@@ -410,10 +1284,15 @@ async fn start_sidecar(app: AppHandle, state: &AppState) -> Result<(), String> {
         if let Some(child) = process_lock.as_mut() {
             match child.try_wait() {
                 Ok(Some(status)) => {
-                    return Err(format!("Backend process exited with status: {}", status));
+                    return Err(format!(
+                        "Backend process exited with status: {}. The service log (sidecar.log) has the reason.",
+                        status
+                    )
+                    .into());
                 }
                 Ok(None) => {
                     log::error!("Backend process is still running but not responding");
+                    set_sidecar_state(host, SidecarState::Degraded);
                 }
                 Err(e) => {
                     log::error!("Failed to check backend process status: {}", e);
@@ -422,28 +1301,660 @@ async fn start_sidecar(app: AppHandle, state: &AppState) -> Result<(), String> {
         }
     }
 
-    Err("Backend failed to start within timeout - check logs directory for details".to_string())
+    Err(StartError::Failed(format!(
+        "Backend did not answer within {} s. The startup checks passed, so the cause is in the service log \
+         (sidecar.log); if the backend is only slow to start, raise the startup timeout in the desktop settings.",
+        startup_timeout
+    )))
+}
+
+/// Check the install and the storage locations before spawning the sidecar, keeping
+/// the report for `get_preflight_report`. Fails with every error and its fix.
+/// Run the preflight checks and keep the report. Storage errors fail the start in a
+/// way the supervisor retries; any other error is fatal.
+async fn run_preflight(host: &impl SidecarHost) -> Result<(), StartError> {
+    let checker = host.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || preflight::run(&checker))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for check in &report.checks {
+        match check.status {
+            preflight::CheckStatus::Ok => log::info!("Preflight {}: {}", check.id, check.message),
+            preflight::CheckStatus::Warning => log::warn!("Preflight {}: {}", check.id, check.message),
+            preflight::CheckStatus::Error => log::error!("Preflight {}: {}", check.id, check.message),
+        }
+    }
+
+    let result = if report.passed() {
+        Ok(())
+    } else if report.is_retryable() {
+        Err(StartError::Failed(report.error_summary()))
+    } else {
+        Err(StartError::Fatal(report.error_summary()))
+    };
+    *host.app_state().preflight.lock().map_err(|e| e.to_string())? = Some(report);
+    result
+}
+
+/// Choose where the next sidecar listens, following the transport setting
+fn pick_endpoint(host: &impl SidecarHost) -> Result<BackendEndpoint, String> {
+    let state = host.app_state();
+    let sidecar = state.settings.lock().map_err(|e| e.to_string())?.sidecar.clone();
+    let previous = state.backend_endpoint.lock().map_err(|e| e.to_string())?.clone();
+
+    // A pinned port wins over the transport setting and is never swapped for another one
+    if let Some(port) = state.launch.port {
+        if !portpicker::is_free(port) {
+            return Err(format!("Port {} given with --port is already in use", port));
+        }
+        return Ok(BackendEndpoint::Tcp(port));
+    }
+
+    match (sidecar.transport, sidecar.port) {
+        (SidecarTransport::Tcp, Some(port)) => {
+            if !portpicker::is_free(port) {
+                return Err(format!("Port {} from the settings is already in use", port));
+            }
+            Ok(BackendEndpoint::Tcp(port))
+        }
+        (SidecarTransport::Tcp, None) => {
+            // Reuse the previous port on restart so the URL the webview already holds keeps working
+            let port = match previous {
+                Some(BackendEndpoint::Tcp(p)) if portpicker::is_free(p) => p,
+                _ => portpicker::pick_unused_port().ok_or("Failed to find available port")?,
+            };
+            Ok(BackendEndpoint::Tcp(port))
+        }
+        (SidecarTransport::LocalSocket, _) => local_socket_path(host).map(BackendEndpoint::LocalSocket),
+    }
+}
+
+#[cfg(unix)]
+fn local_socket_path(host: &impl SidecarHost) -> Result<std::path::PathBuf, String> {
+    let dir = host.local_data_dir()?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    // A socket left behind by a sidecar that was killed would make the bind fail
+    let path = dir.join("backend.sock");
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to remove stale socket {:?}: {}", path, e))?;
+    }
+    Ok(path)
+}
+
+#[cfg(windows)]
+fn local_socket_path(_host: &impl SidecarHost) -> Result<std::path::PathBuf, String> {
+    Ok(std::path::PathBuf::from(format!(
+        r"\\.\pipe\c3i-backup-one-{}",
+        std::process::id()
+    )))
+}
+
+/// Environment the sidecar is started with, besides `endpoint_env`. Also reported,
+/// redacted, in support bundles.
+fn sidecar_env(
+    host: &impl SidecarHost,
+    paths: &StoragePaths,
+    logs_dir: &std::path::Path,
+) -> Result<Vec<(&'static str, String)>, String> {
+    let state = host.app_state();
+    let path_string = |path: Option<PathBuf>| path.map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+    let log_level = state
+        .settings
+        .lock()
+        .map(|s| logging::backend_level(s.logging.sidecar_level))
+        .map_err(|e| e.to_string())?;
+
+    Ok(vec![
+        ("C3I_BACKUP_ONE_TAURI", "1".to_string()),
+        ("C3I_BACKUP_ONE_SHELL_TOKEN", state.shell_token.clone()),
+        ("C3I_BACKUP_ONE_CHANNEL_TOKEN", state.channel_token.clone()),
+        ("C3I_BACKUP_ONE_RESOURCES_PATH", path_string(get_resources_path(host))),
+        ("C3I_BACKUP_ONE_LOGS_DIR", logs_dir.to_string_lossy().to_string()),
+        ("LOG_LEVEL", log_level.to_string()),
+        ("MIGRATIONS_PATH", path_string(get_migrations_path(host))),
+        ("DATABASE_URL", paths.database.to_string_lossy().to_string()),
+        ("C3I_BACKUP_ONE_REPOSITORIES_DIR", paths.repositories_dir.to_string_lossy().to_string()),
+        ("C3I_BACKUP_ONE_VOLUMES_DIR", paths.volumes_dir.to_string_lossy().to_string()),
+        ("RESTIC_CACHE_DIR", paths.cache_dir.to_string_lossy().to_string()),
+        ("RESTIC_PASS_FILE", paths.password_file.to_string_lossy().to_string()),
+        ("NODE_ENV", "production".to_string()),
+    ])
+}
+
+/// Environment telling the sidecar where to listen. TCP is pinned to loopback.
+fn endpoint_env(endpoint: &BackendEndpoint) -> Vec<(&'static str, String)> {
+    match endpoint {
+        BackendEndpoint::Tcp(port) => vec![("PORT", port.to_string()), ("LISTEN_HOST", "127.0.0.1".to_string())],
+        BackendEndpoint::LocalSocket(path) => {
+            vec![("C3I_BACKUP_ONE_UNIX_SOCKET", path.to_string_lossy().to_string())]
+        }
+    }
+}
+
+/// Serve a `c3i-backend://` request from the webview by relaying it to the sidecar's local socket
+async fn proxy_backend_request(
+    app: AppHandle,
+    request: tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    use tauri::http::header;
+
+    // Answer CORS preflights here; the backend only handles them when TRUSTED_ORIGINS is set
+    if request.method() == tauri::http::Method::OPTIONS {
+        let origin = request.headers().get(header::ORIGIN).cloned();
+        let mut response = tauri::http::Response::builder()
+            .status(tauri::http::StatusCode::NO_CONTENT)
+            .header(header::ACCESS_CONTROL_ALLOW_METHODS, "GET, POST, PUT, PATCH, DELETE, OPTIONS")
+            .header(header::ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, Authorization, X-Channel-Token")
+            .header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        if let Some(origin) = origin {
+            response = response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        }
+        return response.body(Vec::new()).unwrap_or_default();
+    }
+
+    let result = match BackendClient::from_app(&app) {
+        Ok(client) => client.forward(request).await,
+        Err(e) => Err(e),
+    };
+
+    result.unwrap_or_else(|e| {
+        log::warn!("Failed to relay webview request to backend: {}", e);
+        let body = serde_json::json!({ "message": e.to_string() }).to_string();
+        tauri::http::Response::builder()
+            .status(tauri::http::StatusCode::BAD_GATEWAY)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into_bytes())
+            .unwrap_or_default()
+    })
+}
+
+fn stop_sidecar(host: &impl SidecarHost) {
+    let state = host.app_state();
+
+    // Tell the supervisor this exit is intentional before the process goes away
+    if let Ok(mut stopping) = state.sidecar_stopping.lock() {
+        *stopping = true;
+    }
+
+    let child = match state.sidecar_process.lock() {
+        Ok(mut process_lock) => process_lock.take(),
+        Err(_) => None,
+    };
+    let Some(mut child) = child else {
+        set_sidecar_state(host, SidecarState::Stopped);
+        return;
+    };
+
+    set_sidecar_state(host, SidecarState::Stopping);
+    terminate_sidecar(state, &mut child);
+    set_sidecar_state(host, SidecarState::Stopped);
+    forget_sidecar(host);
+    instance::remove(host);
+}
+
+/// Remove the lock of a sidecar that was stopped on purpose
+fn forget_sidecar(host: &impl SidecarHost) {
+    match storage::resolve(host) {
+        Ok(paths) => sidecar_process::remove(&paths),
+        Err(e) => log::warn!("Failed to resolve the data directory: {}", e),
+    }
+}
+
+/// Let the backend stop the scheduler and unmount volumes, then exit
+fn quit_app(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        stop_sidecar(&app);
+        app.exit(0);
+    });
+}
+
+/// Stop the sidecar, run `work` while it is down and start it again, whatever
+/// `work` returns. The supervisor keeps waiting instead of treating this as a crash.
+async fn with_sidecar_suspended<T: Send + 'static>(
+    app: &AppHandle,
+    work: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let state = app.state::<AppState>();
+    *state.sidecar_suspended.lock().map_err(|e| e.to_string())? = true;
+
+    let host = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let state = host.app_state();
+        let child = state.sidecar_process.lock().ok().and_then(|mut process| process.take());
+        if let Some(mut child) = child {
+            set_sidecar_state(&host, SidecarState::Stopping);
+            terminate_sidecar(state, &mut child);
+            // `work` may move the data directory, lock included
+            forget_sidecar(&host);
+        }
+        set_sidecar_state(&host, SidecarState::Stopped);
+        work()
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+
+    if !is_sidecar_stopping(&state) {
+        if let Err(e) = start_sidecar(app).await {
+            log::error!("Failed to start sidecar: {}", e);
+            mark_sidecar_failed(app, &e);
+        }
+    }
+    *state.sidecar_suspended.lock().unwrap_or_else(|e| e.into_inner()) = false;
+
+    result
+}
+
+/// Stop the sidecar in stages: ask it to shut down, give it the configured grace
+/// period to stop the scheduler and unmount volumes, then force-kill it.
+fn terminate_sidecar(state: &AppState, child: &mut Child) {
+    let grace = shutdown_grace(state);
+
+    match request_sidecar_shutdown(child) {
+        Ok(()) => {
+            log::info!("Requested sidecar shutdown, waiting up to {:?}", grace);
+
+            let started = Instant::now();
+            let mut last_report = started;
+            while started.elapsed() < grace {
+                match child.try_wait() {
+                    Ok(Some(status)) => {
+                        log::info!("Sidecar shut down gracefully ({}) in {:?}", status, started.elapsed());
+                        return;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        log::warn!("Failed to check sidecar process status: {}", e);
+                        break;
+                    }
+                }
+
+                if last_report.elapsed() >= Duration::from_secs(1) {
+                    log::info!(
+                        "Waiting for sidecar to shut down ({}s of {}s)",
+                        started.elapsed().as_secs(),
+                        grace.as_secs()
+                    );
+                    last_report = Instant::now();
+                }
+
+                std::thread::sleep(SHUTDOWN_POLL_INTERVAL);
+            }
+
+            log::warn!("Sidecar did not shut down within {:?}, killing it", grace);
+        }
+        Err(e) => {
+            log::warn!("Failed to request graceful sidecar shutdown, killing it: {}", e);
+        }
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn shutdown_grace(state: &AppState) -> Duration {
+    Duration::from_secs(
+        state
+            .settings
+            .lock()
+            .map(|s| s.sidecar.shutdown_grace_secs)
+            .unwrap_or_else(|_| settings::SidecarSettings::default().shutdown_grace_secs),
+    )
 }
 
-fn stop_sidecar(state: &AppState) {
-    if let Ok(mut process_lock) = state.sidecar_process.lock() {
-        if let Some(mut child) = process_lock.take() {
+#[cfg(unix)]
+fn request_sidecar_shutdown(child: &mut Child) -> Result<(), String> {
+    // SIGTERM runs the backend's lifecycle shutdown (scheduler stop, volume unmount)
+    let pid = child.id() as libc::pid_t;
+    if unsafe { libc::kill(pid, libc::SIGTERM) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error().to_string())
+    }
+}
+
+#[cfg(windows)]
+fn request_sidecar_shutdown(child: &mut Child) -> Result<(), String> {
+    // There is no SIGTERM on Windows; the backend treats a closed stdin as a shutdown request
+    match child.stdin.take() {
+        Some(stdin) => {
+            drop(stdin);
+            Ok(())
+        }
+        None => Err("sidecar stdin is not piped".to_string()),
+    }
+}
+
+fn is_sidecar_stopping(state: &AppState) -> bool {
+    *state.sidecar_stopping.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn is_sidecar_suspended(state: &AppState) -> bool {
+    *state.sidecar_suspended.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn record_health_check(state: &AppState, duration: Duration, error: Option<String>) {
+    let mut checks = state.health_checks.lock().unwrap_or_else(|e| e.into_inner());
+    if checks.len() == HEALTH_HISTORY_LEN {
+        checks.pop_front();
+    }
+    checks.push_back(HealthCheck {
+        at: unix_now(),
+        duration_ms: duration.as_millis() as u64,
+        error,
+    });
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn restart_backoff(consecutive_crashes: u32) -> Duration {
+    let exponent = consecutive_crashes.saturating_sub(1).min(16);
+    (RESTART_BACKOFF_INITIAL * 2u32.pow(exponent)).min(RESTART_BACKOFF_MAX)
+}
+
+/// Poll the sidecar until it exits. Returns `None` when the exit was requested
+/// through `stop_sidecar`, so the supervisor knows not to restart it.
+async fn wait_for_sidecar_exit(state: &AppState) -> Option<SidecarExit> {
+    loop {
+        if is_sidecar_stopping(state) {
+            return None;
+        }
+
+        {
+            let mut process_lock = state.sidecar_process.lock().unwrap_or_else(|e| e.into_inner());
+            let uptime = state
+                .sidecar_started_at
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .map(|t| t.elapsed())
+                .unwrap_or_default();
+
+            match process_lock.as_mut() {
+                // Taken down on purpose and about to come back
+                _ if is_sidecar_suspended(state) => {}
+                // Spawning failed, so there is nothing to wait on
+                None => {
+                    return Some(SidecarExit {
+                        description: "sidecar is not running".to_string(),
+                        code: None,
+                        uptime,
+                    });
+                }
+                Some(child) => match child.try_wait() {
+                    Ok(Some(status)) => {
+                        process_lock.take();
+                        return Some(SidecarExit {
+                            description: status.to_string(),
+                            code: status.code(),
+                            uptime,
+                        });
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        log::warn!("Failed to check sidecar process status: {}", e);
+                    }
+                },
+            }
+        }
+
+        tokio::time::sleep(SUPERVISOR_POLL_INTERVAL).await;
+    }
+}
+
+/// Watch the sidecar for the lifetime of the app and restart it with exponential
+/// backoff when it dies. Past the crash-loop ceiling, or after a start a retry cannot
+/// fix, it waits for the sidecar to be started some other way.
+async fn supervise_sidecar(host: impl SidecarHost) {
+    let state = host.app_state();
+
+    loop {
+        let Some(exit) = wait_for_sidecar_exit(state).await else {
+            log::info!("Sidecar stopped, supervisor exiting");
+            return;
+        };
+        if state.supervisor.lock().unwrap_or_else(|e| e.into_inner()).gave_up {
+            log::error!("Automatic restarts are disabled until the sidecar is started again");
+            if !wait_for_manual_start(state).await {
+                return;
+            }
+            continue;
+        }
+
+        log::error!("Sidecar exited unexpectedly ({}) after {:?}", exit.description, exit.uptime);
+        set_sidecar_state(
+            &host,
+            SidecarState::Crashed {
+                exit: exit.description.clone(),
+                code: exit.code,
+            },
+        );
+
+        let backoff = {
+            let mut supervisor = state.supervisor.lock().unwrap_or_else(|e| e.into_inner());
+            supervisor.last_exit_status = Some(exit.description);
+            supervisor.last_exit_code = exit.code;
+            supervisor.last_exit_at = Some(unix_now());
+
+            if exit.uptime >= SIDECAR_STABLE_UPTIME {
+                supervisor.consecutive_crashes = 0;
+            }
+            supervisor.consecutive_crashes += 1;
+
+            if supervisor.consecutive_crashes > MAX_CONSECUTIVE_CRASHES {
+                supervisor.gave_up = true;
+                None
+            } else {
+                Some(restart_backoff(supervisor.consecutive_crashes))
+            }
+        };
+
+        let Some(backoff) = backoff else {
+            log::error!(
+                "Sidecar crashed {} times in a row, giving up on automatic restarts",
+                MAX_CONSECUTIVE_CRASHES
+            );
+            continue;
+        };
+
+        log::info!("Restarting sidecar in {:?}", backoff);
+        tokio::time::sleep(backoff).await;
+
+        if is_sidecar_stopping(state) {
+            return;
+        }
+
+        {
+            let mut supervisor = state.supervisor.lock().unwrap_or_else(|e| e.into_inner());
+            supervisor.restart_count += 1;
+        }
+
+        if let Err(e) = start_sidecar(&host).await {
+            log::error!("Failed to restart sidecar: {}", e);
+            mark_sidecar_failed(&host, &e);
+        }
+    }
+}
+
+/// Wait while automatic restarts are disabled, until a start outside the supervisor
+/// (a storage change, a rollback) brings the sidecar back. Returns `false` when the
+/// app is shutting down instead.
+async fn wait_for_manual_start(state: &AppState) -> bool {
+    loop {
+        if is_sidecar_stopping(state) {
+            return false;
+        }
+        if !state.supervisor.lock().unwrap_or_else(|e| e.into_inner()).gave_up {
+            return true;
+        }
+        tokio::time::sleep(SUPERVISOR_POLL_INTERVAL).await;
+    }
+}
+
+/// Re-enable automatic restarts after a successful start, with a fresh crash budget
+/// if they had been given up on
+fn resume_supervision(state: &AppState) {
+    let mut supervisor = state.supervisor.lock().unwrap_or_else(|e| e.into_inner());
+    if supervisor.gave_up {
+        log::info!("Sidecar started again, re-enabling automatic restarts");
+        supervisor.gave_up = false;
+        supervisor.consecutive_crashes = 0;
+    }
+}
+
+/// Probe `/healthcheck` periodically once the backend is up, marking it degraded
+/// when the process is alive but stops answering.
+async fn monitor_backend_health(host: impl SidecarHost) {
+    let state = host.app_state();
+    let mut consecutive_failures: u32 = 0;
+
+    loop {
+        let health = state
+            .settings
+            .lock()
+            .map(|s| s.health.clone())
+            .unwrap_or_default();
+        tokio::time::sleep(Duration::from_secs(health.interval_secs.max(1))).await;
+
+        if is_sidecar_stopping(state) {
+            return;
+        }
+
+        let client = match BackendClient::from_state(state) {
+            Ok(client) => client,
+            Err(_) => {
+                // Starting, crashed or restarting: the supervisor owns the process for now
+                consecutive_failures = 0;
+                continue;
+            }
+        };
+
+        let timeout = Duration::from_secs(health.timeout_secs.max(1));
+        let started = Instant::now();
+        let result = client.healthcheck(timeout).await;
+        record_health_check(state, started.elapsed(), result.as_ref().err().map(|e| e.to_string()));
+
+        let Err(error) = result else {
+            if consecutive_failures > 0 {
+                log::info!("Backend is answering again after {} failed health checks", consecutive_failures);
+            }
+            consecutive_failures = 0;
+            set_sidecar_state(&host, SidecarState::Healthy);
+            continue;
+        };
+
+        consecutive_failures += 1;
+        log::warn!("Backend health check failed ({} in a row): {}", consecutive_failures, error);
+
+        if consecutive_failures == health.failure_threshold {
+            set_sidecar_state(&host, SidecarState::Degraded);
+        }
+
+        if consecutive_failures == health.notify_after_failures {
+            let body = format!(
+                "El servicio no responde desde hace {} comprobaciones",
+                consecutive_failures
+            );
+            if let Err(e) = host.notify("⚠ C3i Backup ONE no responde", &body) {
+                log::warn!("Failed to show health notification: {}", e);
+            }
+        }
+
+        if health.restart_after_failures == Some(consecutive_failures) {
+            log::error!("Backend unresponsive for {} health checks, restarting it", consecutive_failures);
+            consecutive_failures = 0;
+            // The supervisor notices the exit and restarts it with the usual backoff
+            shut_down_unresponsive_sidecar(state).await;
+        }
+    }
+}
+
+/// Stop the sidecar in the stages of `terminate_sidecar`, leaving the process with
+/// the supervisor so that it sees the exit
+async fn shut_down_unresponsive_sidecar(state: &AppState) {
+    let pid = {
+        let mut process_lock = state.sidecar_process.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(child) = process_lock.as_mut() else {
+            return;
+        };
+        if let Err(e) = request_sidecar_shutdown(child) {
+            log::warn!("Failed to request graceful sidecar shutdown, killing it: {}", e);
             let _ = child.kill();
+            return;
         }
+        child.id()
+    };
+
+    let grace = shutdown_grace(state);
+    log::info!("Requested sidecar shutdown, waiting up to {:?}", grace);
+    let started = Instant::now();
+    while started.elapsed() < grace {
+        tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+
+        let mut process_lock = state.sidecar_process.lock().unwrap_or_else(|e| e.into_inner());
+        match process_lock.as_mut() {
+            // The exit status stays with the child for the supervisor to collect
+            Some(child) if child.id() == pid => match child.try_wait() {
+                Ok(None) => {}
+                Ok(Some(_)) | Err(_) => return,
+            },
+            // Already collected, or stopped by someone else
+            _ => return,
+        }
+    }
+
+    log::warn!("Sidecar did not shut down within {:?}, killing it", grace);
+    let mut process_lock = state.sidecar_process.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(child) = process_lock.as_mut().filter(|child| child.id() == pid) {
+        let _ = child.kill();
     }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
-            // When another instance tries to start, show and focus the existing window
-            log::info!("Another instance attempted to start, focusing existing window");
-            if let Some(window) = app.get_webview_window("main") {
-                let _ = window.show();
-                let _ = window.set_focus();
+    let context = tauri::generate_context!();
+    let cli = cli::Cli::parse_args();
+    let launch = cli.launch_options();
+    let app_dirs = || app_dirs::AppDirs::new(context.config().identifier.clone(), context.package_info().clone());
+
+    if let Some(command) = &cli.command {
+        if let Some(code) = cli::run_local(command, &app_dirs(), &launch) {
+            std::process::exit(code);
+        }
+    }
+
+    // Shared by both modes so they write the same files
+    match app_dirs().log_file() {
+        Ok(file) => {
+            if let Err(e) = logging::init(&file) {
+                eprintln!("Failed to set up logging in {:?}: {}", file, e);
             }
+        }
+        Err(e) => eprintln!("Failed to resolve log directory: {}", e),
+    }
+
+    if cli.headless {
+        headless::run(app_dirs(), launch);
+        return;
+    }
+
+    let initial_route = cli.initial_route();
+    let initial_link = cli.link.clone();
+    let minimized = cli.minimized;
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            // A second launch only forwards its arguments (or a focus request) to us
+            cli::handle_forwarded(app, args);
         }))
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_process::init())
@@ -453,126 +1964,81 @@ pub fn run() {
             tauri_plugin_autostart::MacosLauncher::LaunchAgent,
             Some(vec!["--minimized"]),
         ))
-        .manage(AppState {
-            backend_port: Mutex::new(None),
-            sidecar_running: Mutex::new(false),
-            sidecar_process: Mutex::new(None),
+        .register_asynchronous_uri_scheme_protocol(backend_client::PROXY_SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                responder.respond(proxy_backend_request(app, request).await);
+            });
         })
+        .manage(AppState::new(launch))
         .setup(|app| {
-            // Enable logging in both debug and release builds
-            let log_level = if cfg!(debug_assertions) {
-                log::LevelFilter::Info
-            } else {
-                log::LevelFilter::Info
-            };
-
-            app.handle().plugin(
-                tauri_plugin_log::Builder::default()
-                    .level(log_level)
-                    .build(),
-            )?;
-
-            // Create system tray (ordered same as sidebar)
-            let show = MenuItemBuilder::with_id("show", "Mostrar App").build(app)?;
-            let volumes = MenuItemBuilder::with_id("volumes", "Volúmenes").build(app)?;
-            let repositories = MenuItemBuilder::with_id("repositories", "Repositorios").build(app)?;
-            let backups = MenuItemBuilder::with_id("backups", "Copias de seguridad").build(app)?;
-            let notifications = MenuItemBuilder::with_id("notifications", "Notificaciones").build(app)?;
-            let settings = MenuItemBuilder::with_id("settings", "Configuración").build(app)?;
-            let open_logs = MenuItemBuilder::with_id("open_logs", "Abrir logs").build(app)?;
-            let quit = MenuItemBuilder::with_id("quit", "Salir").build(app)?;
-
-            let menu = MenuBuilder::new(app)
-                .item(&show)
-                .separator()
-                .item(&volumes)
-                .item(&repositories)
-                .item(&backups)
-                .item(&notifications)
-                .separator()
-                .item(&settings)
-                .item(&open_logs)
-                .separator()
-                .item(&quit)
-                .build()?;
-
-            // Load tray icon from embedded bytes (custom C3i Backup ONE logo)
-            let icon = Image::from_bytes(include_bytes!("../../public/images/logo.png"))
-                .expect("Failed to load tray icon");
-
-            let _tray = TrayIconBuilder::new()
-                .icon(icon)
-                .menu(&menu)
-                .show_menu_on_left_click(false)
-                .on_menu_event(move |app, event| {
-                    match event.id().as_ref() {
-                        "show" => {
-                            if let Some(window) = app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                            }
-                        }
-                        "volumes" => {
-                            if let Some(window) = app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                                let _ = window.eval("window.location.href = '/volumes'");
-                            }
-                        }
-                        "repositories" => {
-                            if let Some(window) = app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                                let _ = window.eval("window.location.href = '/repositories'");
-                            }
-                        }
-                        "backups" => {
-                            if let Some(window) = app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                                let _ = window.eval("window.location.href = '/backups'");
-                            }
-                        }
-                        "notifications" => {
-                            if let Some(window) = app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                                let _ = window.eval("window.location.href = '/notifications'");
-                            }
-                        }
-                        "settings" => {
-                            if let Some(window) = app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                                let _ = window.eval("window.location.href = '/settings'");
-                            }
-                        }
-                        "open_logs" => {
-                            let _ = open_logs_dir(app.clone());
-                        }
-                        "quit" => {
-                            app.exit(0);
-                        }
-                        _ => {}
-                    }
-                })
-                .on_tray_icon_event(|tray, event| {
-                    if let TrayIconEvent::Click { button: tauri::tray::MouseButton::Left, .. } = event {
-                        let app = tray.app_handle();
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                        }
+            {
+                let state = app.state::<AppState>();
+                let (loaded, problem) = settings::load(app.handle());
+                logging::apply(&loaded.logging);
+                if let Some(settings::LoadProblem::Invalid(_)) = &problem {
+                    if let Err(e) = show_notification(
+                        app.handle(),
+                        "⚠ Configuración restablecida",
+                        "No se pudo leer el archivo de configuración; se ha guardado como settings.invalid.json.",
+                    ) {
+                        log::warn!("Failed to show settings notification: {}", e);
                     }
-                })
-                .build(app)?;
+                }
+                *state.settings.lock().unwrap_or_else(|e| e.into_inner()) = loaded;
+                *state.settings_problem.lock().unwrap_or_else(|e| e.into_inner()) = problem;
+            }
+
+            if let Some(route) = initial_route {
+                let state = app.state::<AppState>();
+                *state.pending_route.lock().unwrap_or_else(|e| e.into_inner()) = Some(route);
+            }
+
+            // Installers register the scheme; dev builds and AppImages have to do it themselves
+            #[cfg(any(windows, target_os = "linux"))]
+            if cfg!(debug_assertions) {
+                if let Err(e) = app.deep_link().register_all() {
+                    log::warn!("Failed to register the {}:// scheme: {}", deep_link::SCHEME, e);
+                }
+            }
+
+            // Links that launched us arrive as an argument on Linux and Windows; later
+            // ones (and all of them on macOS) come through `on_open_url`
+            if let Some(link) = initial_link {
+                deep_link::dispatch(app.handle(), &link, true);
+            }
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                for url in event.urls() {
+                    deep_link::dispatch(&handle, url.as_str(), false);
+                }
+            });
+
+            tray::create(app.handle())?;
+
+            // The window is created hidden so that autostart can leave it in the tray
+            if minimized {
+                log::info!("Started minimized to the tray");
+            } else {
+                tray::show_main_window(app.handle());
+            }
 
             // Start sidecar in background
             let app_handle = app.handle().clone();
 
             tauri::async_runtime::spawn(async move {
-                if let Err(e) = start_sidecar_async(app_handle).await {
+                if let Err(e) = start_sidecar_async(app_handle.clone()).await {
                     log::error!("Failed to start sidecar: {}", e);
+                    mark_sidecar_failed(&app_handle, &e);
+                }
+
+                // The Vite dev server is not ours to supervise, and its events reach the
+                // shell through the webview (`handle_backend_event`)
+                if !cfg!(debug_assertions) {
+                    tauri::async_runtime::spawn(monitor_backend_health(app_handle.clone()));
+                    tauri::async_runtime::spawn(server_events::run(app_handle.clone()));
+                    tauri::async_runtime::spawn(snapshots::schedule(app_handle.clone()));
+                    supervise_sidecar(app_handle).await;
                 }
             });
 
@@ -581,28 +2047,190 @@ pub fn run() {
         .on_window_event(|window, event| {
             match event {
                 tauri::WindowEvent::CloseRequested { api, .. } => {
-                    // Hide window instead of closing (minimize to tray)
-                    window.hide().unwrap();
+                    let close_to_tray = window
+                        .state::<AppState>()
+                        .settings
+                        .lock()
+                        .map(|s| s.window.close_to_tray)
+                        .unwrap_or(true);
+
+                    // Hide window instead of closing (minimize to tray), or quit once the
+                    // backend has shut down cleanly
                     api.prevent_close();
+                    if close_to_tray {
+                        window.hide().unwrap();
+                    } else {
+                        quit_app(window.app_handle());
+                    }
                 }
                 tauri::WindowEvent::Destroyed => {
                     // Only stop sidecar when window is actually destroyed
-                    let state: State<AppState> = window.state();
-                    stop_sidecar(&*state);
+                    stop_sidecar(window.app_handle());
                 }
                 _ => {}
             }
         })
         .invoke_handler(tauri::generate_handler![
             get_backend_url,
+            get_backend_connection,
+            get_backend_status,
+            take_pending_route,
             get_data_dir,
             open_data_dir,
+            get_storage_settings,
+            set_storage_location,
+            pick_directory,
             open_logs_dir,
+            list_log_files,
+            read_log,
+            watch_logs,
+            unwatch_logs,
+            create_support_bundle,
+            export_app_state,
+            restore_app_state,
+            list_snapshots,
+            take_snapshot,
+            rollback_snapshot,
+            rollback_upgrade,
             is_backend_ready,
+            get_sidecar_status,
+            get_preflight_report,
+            get_settings,
+            update_settings,
+            get_notification_settings,
+            set_notification_settings,
             get_autostart_enabled,
             set_autostart_enabled,
             send_notification,
+            handle_backend_event,
         ])
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_backoff_doubles_up_to_the_ceiling() {
+        assert_eq!(restart_backoff(0), RESTART_BACKOFF_INITIAL);
+        assert_eq!(restart_backoff(1), Duration::from_secs(1));
+        assert_eq!(restart_backoff(2), Duration::from_secs(2));
+        assert_eq!(restart_backoff(4), Duration::from_secs(8));
+        assert_eq!(restart_backoff(7), RESTART_BACKOFF_MAX);
+        assert_eq!(restart_backoff(u32::MAX), RESTART_BACKOFF_MAX);
+    }
+
+    #[test]
+    fn url_is_only_handed_out_while_the_backend_is_up() {
+        let state = AppState::new(cli::LaunchOptions::default());
+        *state.backend_endpoint.lock().unwrap() = Some(BackendEndpoint::Tcp(4096));
+
+        for (sidecar_state, has_url) in [
+            (SidecarState::Starting, false),
+            (SidecarState::Healthy, true),
+            (SidecarState::Degraded, true),
+            (SidecarState::Stopping, false),
+            (SidecarState::Stopped, false),
+        ] {
+            *state.sidecar_state.lock().unwrap() = sidecar_state;
+            assert_eq!(backend_status(&state).url.is_some(), has_url);
+        }
+        assert_eq!(backend_status(&state).state, SidecarState::Stopped);
+    }
+
+    #[test]
+    fn status_is_tagged_with_the_state() {
+        let status = BackendStatus {
+            state: SidecarState::Crashed {
+                exit: "exit status: 1".to_string(),
+                code: Some(1),
+            },
+            url: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({ "state": "crashed", "exit": "exit status: 1", "code": 1, "url": null })
+        );
+    }
+
+    #[test]
+    fn tokens_are_long_random_and_url_safe() {
+        let token = generate_token();
+
+        assert_eq!(token.len(), 48);
+        assert!(token.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn tcp_backends_only_listen_on_loopback() {
+        assert_eq!(
+            endpoint_env(&BackendEndpoint::Tcp(4096)),
+            [("PORT", "4096".to_string()), ("LISTEN_HOST", "127.0.0.1".to_string())]
+        );
+
+        let socket = PathBuf::from("/run/user/1000/c3i-backup-one.sock");
+        assert_eq!(
+            endpoint_env(&BackendEndpoint::LocalSocket(socket)),
+            [("C3I_BACKUP_ONE_UNIX_SOCKET", "/run/user/1000/c3i-backup-one.sock".to_string())]
+        );
+    }
+
+    #[test]
+    fn settings_patches_merge_into_nested_objects() {
+        let mut settings = serde_json::json!({
+            "sidecar": { "port": null, "shutdownGraceSecs": 10 },
+            "notifications": { "backupStarted": true, "backupError": true },
+        });
+
+        merge_json(
+            &mut settings,
+            serde_json::json!({
+                "sidecar": { "port": 8080 },
+                "notifications": { "backupStarted": false },
+                "window": { "closeToTray": false },
+            }),
+        );
+
+        assert_eq!(
+            settings,
+            serde_json::json!({
+                "sidecar": { "port": 8080, "shutdownGraceSecs": 10 },
+                "notifications": { "backupStarted": false, "backupError": true },
+                "window": { "closeToTray": false },
+            })
+        );
+    }
+
+    #[test]
+    fn null_in_a_patch_clears_the_value() {
+        let mut settings = serde_json::json!({ "storage": { "dataDir": "/srv/backups", "cacheDir": "/var/cache" } });
+
+        merge_json(&mut settings, serde_json::json!({ "storage": { "dataDir": null } }));
+
+        assert_eq!(
+            settings,
+            serde_json::json!({ "storage": { "dataDir": null, "cacheDir": "/var/cache" } })
+        );
+    }
+
+    #[test]
+    fn health_history_keeps_the_latest_checks() {
+        let state = AppState::new(cli::LaunchOptions::default());
+
+        for i in 0..HEALTH_HISTORY_LEN + 2 {
+            record_health_check(&state, Duration::from_millis(i as u64), None);
+        }
+        record_health_check(&state, Duration::from_secs(5), Some("timed out".to_string()));
+
+        let checks = state.health_checks.lock().unwrap();
+        assert_eq!(checks.len(), HEALTH_HISTORY_LEN);
+        assert_eq!(checks.front().unwrap().duration_ms, 3);
+        let last = checks.back().unwrap();
+        assert_eq!(last.duration_ms, 5000);
+        assert_eq!(last.error.as_deref(), Some("timed out"));
+    }
+}
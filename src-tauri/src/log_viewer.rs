@@ -0,0 +1,622 @@
+//! Reading the shell and sidecar logs back for the in-app log viewer.
+//!
+//! Files are parsed from the format `logging` writes, `[date][time][target][LEVEL]
+//! message`. Lines without that prefix continue the entry above them (stack traces,
+//! multi-line messages).
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::logging::{self, SIDECAR_LOG_FILE, SIDECAR_TARGET};
+use crate::settings::LogLevel;
+
+/// Emitted with a `LogTailBatch` whenever a watched log file grows
+pub const LOG_ENTRIES_EVENT: &str = "logs://entries";
+const TAIL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a watched file must stop growing before its last entry is taken as complete
+const TAIL_SETTLE_TIME: Duration = Duration::from_secs(2);
+/// Prefix of the backend's lines in logs written before it got a file of its own
+const LEGACY_SIDECAR_PREFIX: &str = "[SIDECAR] ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogSource {
+    Shell,
+    Sidecar,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFile {
+    pub name: String,
+    pub source: LogSource,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch
+    pub modified_ms: Option<u64>,
+    /// Being written to, as opposed to a rotated copy
+    pub current: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// Line of the file the entry starts on, from 1
+    pub line: usize,
+    pub source: LogSource,
+    /// Local time as `YYYY-MM-DD HH:MM:SS`; `None` for lines in another format
+    pub timestamp: Option<String>,
+    pub level: Option<LogLevel>,
+    pub target: Option<String>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LogFilter {
+    /// Least severe level to include
+    pub level: Option<LogLevel>,
+    pub source: Option<LogSource>,
+    /// Local time as `YYYY-MM-DD HH:MM:SS`, or a prefix of it such as a date.
+    /// Both bounds are inclusive.
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Text the message must contain, ignoring case
+    pub text: Option<String>,
+}
+
+impl LogFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        if self.source.is_some_and(|source| source != entry.source) {
+            return false;
+        }
+        if let Some(min) = self.level {
+            match entry.level {
+                Some(level) if level.filter() <= min.filter() => {}
+                _ => return false,
+            }
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let Some(timestamp) = entry.timestamp.as_deref() else {
+                return false;
+            };
+            if let Some(since) = self.since.as_deref().map(normalize_time) {
+                if timestamp < since.as_str() {
+                    return false;
+                }
+            }
+            if let Some(until) = self.until.as_deref().map(normalize_time) {
+                if timestamp.get(..until.len()).unwrap_or(timestamp) > until.as_str() {
+                    return false;
+                }
+            }
+        }
+        if let Some(text) = self.text.as_deref().filter(|t| !t.is_empty()) {
+            if !entry.message.to_lowercase().contains(&text.to_lowercase()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Accept the `YYYY-MM-DDTHH:MM` of a `datetime-local` input
+fn normalize_time(time: &str) -> String {
+    time.trim().replacen('T', " ", 1)
+}
+
+/// Which entries of a file to return. Without either bound, the last `limit`
+/// matching entries.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LogRange {
+    /// Return the last matching entries starting before this line (scrolling back)
+    pub before_line: Option<usize>,
+    /// Return the first matching entries starting after this line (reading forward)
+    pub after_line: Option<usize>,
+    pub limit: usize,
+}
+
+impl Default for LogRange {
+    fn default() -> Self {
+        Self {
+            before_line: None,
+            after_line: None,
+            limit: 500,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    /// More matching entries exist beyond the returned ones, in the direction read
+    pub has_more: bool,
+    pub total_lines: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogTailBatch {
+    pub file: String,
+    pub entries: Vec<LogEntry>,
+}
+
+/// The shell and sidecar logs and their rotated copies, current files first and
+/// then newest first
+pub fn list(shell_log: &Path) -> Result<Vec<LogFile>, String> {
+    let dir = shell_log.parent().ok_or("The log file has no directory")?;
+    let shell_stem = stem(shell_log);
+    let sidecar_stem = stem(Path::new(SIDECAR_LOG_FILE));
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", dir, e)),
+    };
+
+    let mut files: Vec<LogFile> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let (source, current) = if name == format!("{}.log", shell_stem) {
+                (LogSource::Shell, true)
+            } else if name == SIDECAR_LOG_FILE {
+                (LogSource::Sidecar, true)
+            } else if logging::is_rotated_copy(&name, &shell_stem) {
+                (LogSource::Shell, false)
+            } else if logging::is_rotated_copy(&name, &sidecar_stem) {
+                (LogSource::Sidecar, false)
+            } else {
+                return None;
+            };
+
+            let metadata = entry.metadata().ok()?;
+            Some(LogFile {
+                name,
+                source,
+                size: metadata.len(),
+                modified_ms: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64),
+                current,
+            })
+        })
+        .collect();
+
+    files.sort_by(|a, b| b.current.cmp(&a.current).then_with(|| b.modified_ms.cmp(&a.modified_ms)));
+    Ok(files)
+}
+
+/// Resolve a file name from `list`, refusing anything else in (or outside) the log directory
+fn resolve(shell_log: &Path, name: &str) -> Result<(PathBuf, LogSource), String> {
+    let file = list(shell_log)?
+        .into_iter()
+        .find(|file| file.name == name)
+        .ok_or_else(|| format!("No log file named {:?}", name))?;
+    Ok((shell_log.with_file_name(&file.name), file.source))
+}
+
+/// Read the entries of `name` that match `filter`, within `range`
+pub fn read(shell_log: &Path, name: &str, filter: &LogFilter, range: &LogRange) -> Result<LogPage, String> {
+    let (path, source) = resolve(shell_log, name)?;
+    let contents = std::fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let text = String::from_utf8_lossy(&contents);
+    let total_lines = text.lines().count();
+
+    let matching: Vec<LogEntry> = parse(&text, 1, source)
+        .into_iter()
+        .filter(|entry| filter.matches(entry))
+        .collect();
+    let limit = range.limit.max(1);
+
+    let (entries, has_more) = match (range.after_line, range.before_line) {
+        (Some(after), _) => {
+            let mut entries: Vec<LogEntry> = matching.into_iter().filter(|e| e.line > after).collect();
+            let has_more = entries.len() > limit;
+            entries.truncate(limit);
+            (entries, has_more)
+        }
+        (None, before) => {
+            let mut entries: Vec<LogEntry> = matching
+                .into_iter()
+                .filter(|e| !before.is_some_and(|line| e.line >= line))
+                .collect();
+            let skip = entries.len().saturating_sub(limit);
+            (entries.split_off(skip), skip > 0)
+        }
+    };
+
+    Ok(LogPage {
+        entries,
+        has_more,
+        total_lines,
+    })
+}
+
+/// Split `text` into entries, numbering lines from `first_line`
+fn parse(text: &str, first_line: usize, source: LogSource) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = first_line + index;
+        match parse_line(line) {
+            Some((timestamp, target, level, message)) => {
+                let (source, message) = if target == SIDECAR_TARGET {
+                    (LogSource::Sidecar, message)
+                } else if let Some(message) = message.strip_prefix(LEGACY_SIDECAR_PREFIX) {
+                    (LogSource::Sidecar, message)
+                } else {
+                    (source, message)
+                };
+                entries.push(LogEntry {
+                    line: line_number,
+                    source,
+                    timestamp: Some(timestamp),
+                    level: Some(level),
+                    target: Some(target.to_string()),
+                    message: message.to_string(),
+                });
+            }
+            None => match entries.last_mut() {
+                Some(entry) => {
+                    entry.message.push('\n');
+                    entry.message.push_str(line);
+                }
+                None => entries.push(LogEntry {
+                    line: line_number,
+                    source,
+                    timestamp: None,
+                    level: None,
+                    target: None,
+                    message: line.to_string(),
+                }),
+            },
+        }
+    }
+
+    entries
+}
+
+/// Split `[2024-01-31][12:00:00][target][INFO] message` into its parts
+fn parse_line(line: &str) -> Option<(String, &str, LogLevel, &str)> {
+    let mut fields = [""; 4];
+    let mut rest = line;
+    for field in fields.iter_mut() {
+        let inner = rest.strip_prefix('[')?;
+        let end = inner.find(']')?;
+        *field = &inner[..end];
+        rest = &inner[end + 1..];
+    }
+
+    let [date, time, target, level] = fields;
+    let looks_like_date = date.len() == 10 && date.chars().all(|c| c.is_ascii_digit() || c == '-');
+    if !looks_like_date {
+        return None;
+    }
+    let level = match level {
+        "ERROR" => LogLevel::Error,
+        "WARN" => LogLevel::Warn,
+        "INFO" => LogLevel::Info,
+        "DEBUG" => LogLevel::Debug,
+        "TRACE" => LogLevel::Trace,
+        _ => return None,
+    };
+
+    Some((format!("{} {}", date, time), target, level, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+/// Where a watched file has been read up to
+struct TailPosition {
+    name: String,
+    path: PathBuf,
+    source: LogSource,
+    offset: u64,
+    /// Lines before `offset`, to number new entries
+    lines: usize,
+    /// Size of the file when it last grew, and when that was
+    size: u64,
+    grown_at: Instant,
+    settle_time: Duration,
+}
+
+impl TailPosition {
+    fn start(path: PathBuf, source: LogSource) -> Self {
+        let contents = std::fs::read(&path).unwrap_or_default();
+        // Only complete lines count as read; a partial one is picked up once it ends
+        let offset = contents.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        Self {
+            name: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            lines: contents[..offset].iter().filter(|&&b| b == b'\n').count(),
+            offset: offset as u64,
+            size: contents.len() as u64,
+            grown_at: Instant::now(),
+            settle_time: TAIL_SETTLE_TIME,
+            path,
+            source,
+        }
+    }
+
+    /// Entries written since the last call
+    fn read_new(&mut self) -> std::io::Result<Vec<LogEntry>> {
+        let len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        if len < self.offset {
+            // Rotated: the new file starts over
+            self.offset = 0;
+            self.lines = 0;
+        }
+        if len != self.size {
+            self.size = len;
+            self.grown_at = Instant::now();
+        }
+        if len == self.offset {
+            return Ok(Vec::new());
+        }
+
+        let mut file = std::fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut chunk = Vec::new();
+        file.read_to_end(&mut chunk)?;
+
+        let Some(mut end) = chunk.iter().rposition(|&b| b == b'\n').map(|i| i + 1) else {
+            return Ok(Vec::new());
+        };
+        // The last entry may still be getting its continuation lines, so it is held
+        // back until the next entry starts or the file stops growing
+        if self.grown_at.elapsed() < self.settle_time {
+            end = last_entry_start(&chunk[..end]);
+        }
+        if end == 0 {
+            return Ok(Vec::new());
+        }
+        let text = String::from_utf8_lossy(&chunk[..end]);
+        let entries = parse(&text, self.lines + 1, self.source);
+        self.offset += end as u64;
+        self.lines += text.lines().count();
+        Ok(entries)
+    }
+}
+
+/// Offset of the last line in `lines` that starts an entry, or 0 if none does
+fn last_entry_start(lines: &[u8]) -> usize {
+    let mut start = 0;
+    let mut last = 0;
+    for line in lines.split_inclusive(|&b| b == b'\n') {
+        if parse_line(&String::from_utf8_lossy(line)).is_some() {
+            last = start;
+        }
+        start += line.len();
+    }
+    last
+}
+
+/// Emit the entries appended to the current shell and sidecar logs that match
+/// `filter`, until the task is aborted
+pub async fn tail(app: AppHandle, shell_log: PathBuf, filter: LogFilter) {
+    let mut positions = vec![
+        TailPosition::start(shell_log.clone(), LogSource::Shell),
+        TailPosition::start(shell_log.with_file_name(SIDECAR_LOG_FILE), LogSource::Sidecar),
+    ];
+
+    loop {
+        tokio::time::sleep(TAIL_POLL_INTERVAL).await;
+
+        for position in positions.iter_mut() {
+            let entries: Vec<LogEntry> = match position.read_new() {
+                Ok(entries) => entries.into_iter().filter(|e| filter.matches(e)).collect(),
+                Err(e) => {
+                    // Not logged, as that would grow the very file being watched
+                    eprintln!("Failed to read {:?}: {}", position.path, e);
+                    continue;
+                }
+            };
+            if entries.is_empty() {
+                continue;
+            }
+
+            let batch = LogTailBatch {
+                file: position.name.clone(),
+                entries,
+            };
+            let _ = app.emit(LOG_ENTRIES_EVENT, batch);
+        }
+    }
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[2024-01-31][12:00:00][c3i_backup_one][INFO] Starting
+[2024-01-31][12:00:01][sidecar][WARN] Backend warning
+[2024-01-31][12:00:02][c3i_backup_one][ERROR] Failed to start sidecar
+    at main.ts:12
+    at index.ts:3
+[2024-02-01][08:30:00][c3i_backup_one][INFO] [SIDECAR] Legacy backend line
+[2024-02-01][08:30:01][c3i_backup_one][DEBUG] Health check ok
+";
+
+    fn entries() -> Vec<LogEntry> {
+        parse(LOG, 1, LogSource::Shell)
+    }
+
+    fn messages(entries: &[LogEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.message.as_str()).collect()
+    }
+
+    fn write_log(dir: &Path, contents: &str) -> PathBuf {
+        let shell_log = dir.join("c3i-backup-one.log");
+        std::fs::write(&shell_log, contents).unwrap();
+        shell_log
+    }
+
+    #[test]
+    fn lines_without_a_prefix_continue_the_entry_above() {
+        let entries = entries();
+
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[2].message, "Failed to start sidecar\n    at main.ts:12\n    at index.ts:3");
+        assert_eq!(entries[2].level, Some(LogLevel::Error));
+        assert_eq!(entries[2].timestamp.as_deref(), Some("2024-01-31 12:00:02"));
+        assert_eq!(entries[3].line, 6);
+    }
+
+    #[test]
+    fn backend_lines_are_attributed_to_the_sidecar() {
+        let entries = entries();
+
+        assert_eq!(entries[1].source, LogSource::Sidecar);
+        assert_eq!(entries[3].source, LogSource::Sidecar);
+        assert_eq!(entries[3].message, "Legacy backend line");
+        assert_eq!(entries[4].source, LogSource::Shell);
+    }
+
+    #[test]
+    fn leading_lines_in_another_format_are_kept() {
+        let entries = parse("panicked at src/lib.rs\n[2024-01-31][12:00:00][app][INFO] Up\n", 1, LogSource::Shell);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timestamp, None);
+        assert_eq!(entries[0].message, "panicked at src/lib.rs");
+    }
+
+    #[test]
+    fn filter_by_level_source_and_text() {
+        let entries = entries();
+        let select = |filter: LogFilter| entries.iter().filter(|e| filter.matches(e)).map(|e| e.line).collect::<Vec<_>>();
+
+        let warnings = LogFilter {
+            level: Some(LogLevel::Warn),
+            ..Default::default()
+        };
+        assert_eq!(select(warnings), [2, 3]);
+
+        let sidecar = LogFilter {
+            source: Some(LogSource::Sidecar),
+            ..Default::default()
+        };
+        assert_eq!(select(sidecar), [2, 6]);
+
+        let text = LogFilter {
+            text: Some("HEALTH".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(select(text), [7]);
+    }
+
+    #[test]
+    fn filter_by_time_with_inclusive_bounds() {
+        let entries = entries();
+        let filter = LogFilter {
+            since: Some("2024-01-31T12:00:01".to_string()),
+            until: Some("2024-01-31".to_string()),
+            ..Default::default()
+        };
+
+        let lines: Vec<usize> = entries.iter().filter(|e| filter.matches(e)).map(|e| e.line).collect();
+
+        assert_eq!(lines, [2, 3]);
+    }
+
+    #[test]
+    fn read_pages_backwards_and_forwards() {
+        let dir = tempfile::tempdir().unwrap();
+        let shell_log = write_log(dir.path(), LOG);
+        let page = |before_line, after_line, limit| {
+            let range = LogRange {
+                before_line,
+                after_line,
+                limit,
+            };
+            read(&shell_log, "c3i-backup-one.log", &LogFilter::default(), &range).unwrap()
+        };
+
+        let last = page(None, None, 2);
+        assert_eq!(messages(&last.entries), ["Legacy backend line", "Health check ok"]);
+        assert!(last.has_more);
+        assert_eq!(last.total_lines, 7);
+
+        let earlier = page(Some(6), None, 10);
+        assert_eq!(earlier.entries.len(), 3);
+        assert!(!earlier.has_more);
+
+        let later = page(None, Some(3), 1);
+        assert_eq!(messages(&later.entries), ["Legacy backend line"]);
+        assert!(later.has_more);
+    }
+
+    #[test]
+    fn only_log_files_can_be_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let shell_log = write_log(dir.path(), LOG);
+        std::fs::write(dir.path().join("settings.json"), "{}").unwrap();
+
+        for name in ["settings.json", "../c3i-backup-one.log", "missing.log"] {
+            assert!(read(&shell_log, name, &LogFilter::default(), &LogRange::default()).is_err());
+        }
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut contents = std::fs::read_to_string(path).unwrap();
+        contents.push_str(text);
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn tail_reads_complete_lines_and_starts_over_after_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let shell_log = write_log(dir.path(), "[2024-01-31][12:00:00][app][INFO] Before\n");
+        let mut position = TailPosition::start(shell_log.clone(), LogSource::Shell);
+        position.settle_time = Duration::ZERO;
+        assert!(position.read_new().unwrap().is_empty());
+
+        append(&shell_log, "[2024-01-31][12:00:01][app][INFO] New\n[2024-01-31][12:00:02][app][INFO] Partial");
+        let entries = position.read_new().unwrap();
+        assert_eq!(messages(&entries), ["New"]);
+        assert_eq!(entries[0].line, 2);
+
+        std::fs::write(&shell_log, "[2024-01-31][13:00:00][app][INFO] Rotated\n").unwrap();
+        let entries = position.read_new().unwrap();
+        assert_eq!(messages(&entries), ["Rotated"]);
+        assert_eq!(entries[0].line, 1);
+    }
+
+    #[test]
+    fn tail_holds_the_last_entry_until_the_next_one_starts() {
+        let dir = tempfile::tempdir().unwrap();
+        let shell_log = write_log(dir.path(), "[2024-01-31][12:00:00][app][INFO] Before\n");
+        let mut position = TailPosition::start(shell_log.clone(), LogSource::Shell);
+
+        append(&shell_log, "[2024-01-31][12:00:01][app][ERROR] Panicked\n    at main.rs:1\n");
+        assert!(position.read_new().unwrap().is_empty());
+
+        append(&shell_log, "    at lib.rs:2\n[2024-01-31][12:00:02][app][INFO] Next\n");
+        let entries = position.read_new().unwrap();
+        assert_eq!(messages(&entries), ["Panicked\n    at main.rs:1\n    at lib.rs:2"]);
+        assert_eq!(entries[0].level, Some(LogLevel::Error));
+        assert_eq!(entries[0].line, 2);
+
+        // Shown once the file stops growing
+        assert!(position.read_new().unwrap().is_empty());
+        position.settle_time = Duration::ZERO;
+        let entries = position.read_new().unwrap();
+        assert_eq!(messages(&entries), ["Next"]);
+        assert_eq!(entries[0].line, 5);
+    }
+}
@@ -0,0 +1,472 @@
+//! Shell and sidecar logs.
+//!
+//! Records go to stdout and to two files in the app log directory: the shell's own
+//! log and `sidecar.log` with the backend's output. Each file is rotated once it
+//! outgrows the configured size or rotation interval, and rotated files are pruned
+//! by count and age. `apply` changes levels and rotation while the app runs.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::settings::{LogLevel, LogRotation, LoggingSettings};
+
+/// Target of the backend's lines, which go to `SIDECAR_LOG_FILE` instead of the shell log
+pub const SIDECAR_TARGET: &str = "sidecar";
+pub const SIDECAR_LOG_FILE: &str = "sidecar.log";
+
+const TIMESTAMP_FORMAT: &[time::format_description::BorrowedFormatItem<'static>] =
+    time::macros::format_description!("[year]-[month]-[day]][[[hour]:[minute]:[second]");
+/// Inserted between a log file's stem and extension when it is rotated; sorts by age
+const ROTATED_SUFFIX_FORMAT: &[time::format_description::BorrowedFormatItem<'static>] =
+    time::macros::format_description!("[year][month][day]-[hour][minute][second]");
+
+static SHELL_LEVEL: AtomicUsize = AtomicUsize::new(log::LevelFilter::Info as usize);
+static SIDECAR_LEVEL: AtomicUsize = AtomicUsize::new(log::LevelFilter::Info as usize);
+/// `None` until the settings are applied, meaning the defaults
+static ROTATION: Mutex<Option<RotationPolicy>> = Mutex::new(None);
+
+/// Which of the sidecar's streams a line came from
+#[derive(Clone, Copy)]
+pub enum SidecarStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, Copy)]
+struct RotationPolicy {
+    max_size: u64,
+    interval: Option<Duration>,
+    keep_files: usize,
+    max_age: Option<Duration>,
+}
+
+impl From<&LoggingSettings> for RotationPolicy {
+    fn from(settings: &LoggingSettings) -> Self {
+        const DAY: u64 = 24 * 60 * 60;
+
+        Self {
+            max_size: settings.max_file_size_mb * 1024 * 1024,
+            interval: match settings.rotation {
+                LogRotation::Never => None,
+                LogRotation::Daily => Some(Duration::from_secs(DAY)),
+                LogRotation::Weekly => Some(Duration::from_secs(7 * DAY)),
+            },
+            keep_files: settings.keep_files as usize,
+            max_age: settings.keep_days.map(|days| Duration::from_secs(days as u64 * DAY)),
+        }
+    }
+}
+
+fn rotation_policy() -> RotationPolicy {
+    let policy = *ROTATION.lock().unwrap_or_else(|e| e.into_inner());
+    policy.unwrap_or_else(|| RotationPolicy::from(&LoggingSettings::default()))
+}
+
+/// Install the logger, writing the shell log to `shell_log` and the sidecar log next
+/// to it. Levels and rotation are the defaults until `apply` is called.
+pub fn init(shell_log: &Path) -> Result<(), String> {
+    let shell_file = RotatingFile::open(shell_log.to_path_buf()).map_err(|e| e.to_string())?;
+    let sidecar_file =
+        RotatingFile::open(shell_log.with_file_name(SIDECAR_LOG_FILE)).map_err(|e| e.to_string())?;
+
+    apply(&LoggingSettings::default());
+
+    fern::Dispatch::new()
+        .format(|out, message, record| {
+            let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+            out.finish(format_args!(
+                "[{}][{}][{}] {}",
+                now.format(TIMESTAMP_FORMAT).unwrap_or_default(),
+                record.target(),
+                record.level(),
+                message
+            ))
+        })
+        // `log::set_max_level` lets through the more verbose of the two levels;
+        // `enabled` holds each target to its own
+        .level(log::LevelFilter::Trace)
+        .filter(enabled)
+        .chain(std::io::stdout())
+        .chain(
+            fern::Dispatch::new()
+                .filter(|metadata| metadata.target() != SIDECAR_TARGET)
+                .chain(Box::new(shell_file) as Box<dyn Write + Send>),
+        )
+        .chain(
+            fern::Dispatch::new()
+                .filter(|metadata| metadata.target() == SIDECAR_TARGET)
+                .chain(Box::new(sidecar_file) as Box<dyn Write + Send>),
+        )
+        .apply()
+        .map_err(|e| e.to_string())
+}
+
+/// Apply the level and rotation settings to the running logger
+pub fn apply(settings: &LoggingSettings) {
+    let shell = settings.level.filter();
+    let sidecar = settings.sidecar_level.filter();
+
+    SHELL_LEVEL.store(shell as usize, Ordering::Relaxed);
+    SIDECAR_LEVEL.store(sidecar as usize, Ordering::Relaxed);
+    log::set_max_level(shell.max(sidecar));
+    *ROTATION.lock().unwrap_or_else(|e| e.into_inner()) = Some(RotationPolicy::from(settings));
+}
+
+fn enabled(metadata: &log::Metadata) -> bool {
+    let level = if metadata.target() == SIDECAR_TARGET {
+        &SIDECAR_LEVEL
+    } else {
+        &SHELL_LEVEL
+    };
+    metadata.level() as usize <= level.load(Ordering::Relaxed)
+}
+
+/// `LOG_LEVEL` for the backend, whose logger has no level below debug
+pub fn backend_level(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Error => "error",
+        LogLevel::Warn => "warn",
+        LogLevel::Info => "info",
+        LogLevel::Debug | LogLevel::Trace => "debug",
+    }
+}
+
+/// A line the backend logged as JSON (`{"timestamp", "level", "message", "stack"?}`)
+#[derive(serde::Deserialize)]
+struct SidecarRecord {
+    level: String,
+    message: String,
+    #[serde(default)]
+    stack: Option<String>,
+}
+
+/// Log a line of the sidecar's output at the level the backend logged it at. Lines
+/// that are not the backend's own (runtime crashes, output of child processes) are
+/// logged as info from stdout and as errors from stderr.
+pub fn log_sidecar_line(line: &str, stream: SidecarStream) {
+    let fallback = match stream {
+        SidecarStream::Stdout => log::Level::Info,
+        SidecarStream::Stderr => log::Level::Error,
+    };
+
+    if let Ok(record) = serde_json::from_str::<SidecarRecord>(line) {
+        let level = parse_backend_level(&strip_ansi(&record.level)).unwrap_or(fallback);
+        match record.stack {
+            // Errors repeat their message on the first line of the stack
+            Some(stack) if stack.starts_with(&record.message) => {
+                log::log!(target: SIDECAR_TARGET, level, "{}", stack)
+            }
+            Some(stack) => log::log!(target: SIDECAR_TARGET, level, "{}\n{}", record.message, stack),
+            None => log::log!(target: SIDECAR_TARGET, level, "{}", record.message),
+        }
+        return;
+    }
+
+    // The backend's console format, `HH:MM:SS level > message`, in case it was not
+    // told to log JSON
+    let line = strip_ansi(line);
+    let parsed = line.split_once(" > ").and_then(|(prefix, message)| {
+        let (_time, level) = prefix.split_once(' ')?;
+        Some((parse_backend_level(level)?, message))
+    });
+    match parsed {
+        Some((level, message)) => log::log!(target: SIDECAR_TARGET, level, "{}", message),
+        None => log::log!(target: SIDECAR_TARGET, fallback, "{}", line),
+    }
+}
+
+/// Map a winston level to ours
+fn parse_backend_level(level: &str) -> Option<log::Level> {
+    match level.trim() {
+        "error" => Some(log::Level::Error),
+        "warn" => Some(log::Level::Warn),
+        "info" | "http" => Some(log::Level::Info),
+        "verbose" | "debug" => Some(log::Level::Debug),
+        "silly" => Some(log::Level::Trace),
+        _ => None,
+    }
+}
+
+/// Remove the terminal colour codes winston's `colorize` adds
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // CSI sequences end with a letter, e.g. `\x1b[32m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Whether `name` is a rotated copy of the log file named `<stem>.log`
+pub fn is_rotated_copy(name: &str, stem: &str) -> bool {
+    name.strip_prefix(stem)
+        .and_then(|rest| rest.strip_prefix('.'))
+        .and_then(|rest| rest.strip_suffix(".log"))
+        .is_some_and(|suffix| !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit() || c == '-'))
+}
+
+/// A log file that moves itself aside when it gets too big or too old. The check
+/// runs on `flush`, which fern calls after every record, so records are never split
+/// across files.
+struct RotatingFile {
+    path: PathBuf,
+    /// `None` when reopening after a rotation failed; retried on the next write
+    file: Option<File>,
+    size: u64,
+    opened_at: SystemTime,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+
+        let rotating = Self {
+            path,
+            file: Some(file),
+            size: metadata.len(),
+            // Keep counting from the file's creation across restarts of the app
+            opened_at: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+        };
+        rotating.prune(&rotation_policy());
+        Ok(rotating)
+    }
+
+    fn should_rotate(&self, policy: &RotationPolicy) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        if self.size >= policy.max_size {
+            return true;
+        }
+        policy
+            .interval
+            .is_some_and(|interval| self.opened_at.elapsed().is_ok_and(|age| age >= interval))
+    }
+
+    fn rotate(&mut self, policy: &RotationPolicy) -> std::io::Result<()> {
+        self.file = None;
+
+        let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+        let suffix = now.format(ROTATED_SUFFIX_FORMAT).unwrap_or_default();
+        // Numbered after every copy of the same second, even where pruning left a gap,
+        // so that it sorts as the newest
+        let next = self
+            .rotated_files()
+            .iter()
+            .map(|path| rotation_order(path))
+            .filter(|(time, _)| *time == suffix)
+            .map(|(_, n)| n + 1)
+            .max();
+        let target = match next {
+            Some(n) => self.rotated_path(&format!("{}-{}", suffix, n)),
+            None => self.rotated_path(&suffix),
+        };
+        std::fs::rename(&self.path, &target)?;
+
+        self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        self.size = 0;
+        self.opened_at = SystemTime::now();
+        self.prune(policy);
+        Ok(())
+    }
+
+    fn stem(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// `sidecar.log` becomes `sidecar.<suffix>.log`
+    fn rotated_path(&self, suffix: &str) -> PathBuf {
+        self.path.with_file_name(format!("{}.{}.log", self.stem(), suffix))
+    }
+
+    /// Rotated copies of this file, newest first
+    fn rotated_files(&self) -> Vec<PathBuf> {
+        let Some(dir) = self.path.parent() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let stem = self.stem();
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| is_rotated_copy(&name.to_string_lossy(), &stem))
+            })
+            .collect();
+        files.sort_by_key(|path| std::cmp::Reverse(rotation_order(path)));
+        files
+    }
+
+    /// Delete rotated files beyond the count to keep or older than the maximum age.
+    /// Failures are reported on stderr since this runs inside the logger.
+    fn prune(&self, policy: &RotationPolicy) {
+        for (index, path) in self.rotated_files().into_iter().enumerate() {
+            let expired = policy.max_age.is_some_and(|max_age| {
+                std::fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .is_some_and(|age| age > max_age)
+            });
+            if index >= policy.keep_files || expired {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    eprintln!("Failed to remove old log file {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+/// Sort key of a rotated copy, oldest first. `<time>-<n>` is the `n`th copy rotated
+/// within the same second as `<time>`, which a plain sort would put before it.
+fn rotation_order(path: &Path) -> (String, u32) {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let suffix = stem.rsplit('.').next().unwrap_or_default();
+    let time = suffix.get(..15).unwrap_or(suffix);
+    let n = suffix.get(16..).and_then(|n| n.parse().ok()).unwrap_or(0);
+    (time.to_string(), n)
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.file.is_none() {
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        let written = self.file.as_mut().map_or(Ok(0), |file| file.write(buf))?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            file.flush()?;
+        }
+
+        let policy = rotation_policy();
+        if self.should_rotate(&policy) {
+            if let Err(e) = self.rotate(&policy) {
+                eprintln!("Failed to rotate {:?}: {}", self.path, e);
+                // Keep writing to the same file rather than retrying on every record
+                self.size = 0;
+                self.opened_at = SystemTime::now();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_size: u64, keep_files: usize) -> RotationPolicy {
+        RotationPolicy {
+            max_size,
+            interval: None,
+            keep_files,
+            max_age: None,
+        }
+    }
+
+    #[test]
+    fn rotated_copies_are_recognised_by_their_suffix() {
+        assert!(is_rotated_copy("sidecar.20240131-120000.log", "sidecar"));
+        assert!(is_rotated_copy("sidecar.20240131-120000-2.log", "sidecar"));
+        assert!(!is_rotated_copy("sidecar.log", "sidecar"));
+        assert!(!is_rotated_copy("sidecar..log", "sidecar"));
+        assert!(!is_rotated_copy("sidecar.old.log", "sidecar"));
+        assert!(!is_rotated_copy("sidecar-2.20240131-120000.log", "sidecar"));
+    }
+
+    #[test]
+    fn colour_codes_are_stripped() {
+        assert_eq!(strip_ansi("\u{1b}[32minfo\u{1b}[39m"), "info");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn winston_levels_map_to_ours() {
+        assert_eq!(parse_backend_level("http"), Some(log::Level::Info));
+        assert_eq!(parse_backend_level(" verbose "), Some(log::Level::Debug));
+        assert_eq!(parse_backend_level("silly"), Some(log::Level::Trace));
+        assert_eq!(parse_backend_level("notice"), None);
+        assert_eq!(backend_level(LogLevel::Trace), "debug");
+    }
+
+    #[test]
+    fn oversized_file_is_rotated_and_old_copies_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sidecar.log");
+        let policy = policy(5, 2);
+        let mut file = RotatingFile::open(path.clone()).unwrap();
+
+        for round in 0..4 {
+            file.write_all(format!("record {}\n", round).as_bytes()).unwrap();
+            assert!(file.should_rotate(&policy));
+            file.rotate(&policy).unwrap();
+        }
+        file.write_all(b"current\n").unwrap();
+        file.flush().unwrap();
+
+        let rotated = file.rotated_files();
+        assert_eq!(rotated.len(), 2);
+        assert_eq!(std::fs::read_to_string(&rotated[0]).unwrap(), "record 3\n");
+        assert_eq!(std::fs::read_to_string(&rotated[1]).unwrap(), "record 2\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "current\n");
+    }
+
+    #[test]
+    fn copies_rotated_within_a_second_sort_after_the_first() {
+        let mut names = vec![
+            "sidecar.20240131-120000-10.log",
+            "sidecar.20240131-120000.log",
+            "sidecar.20240131-120001.log",
+            "sidecar.20240131-120000-2.log",
+        ];
+        names.sort_by_key(|name| rotation_order(Path::new(name)));
+
+        assert_eq!(
+            names,
+            [
+                "sidecar.20240131-120000.log",
+                "sidecar.20240131-120000-2.log",
+                "sidecar.20240131-120000-10.log",
+                "sidecar.20240131-120001.log",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_file_is_never_rotated() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = RotatingFile::open(dir.path().join("sidecar.log")).unwrap();
+
+        assert!(!file.should_rotate(&policy(0, 2)));
+    }
+}
@@ -0,0 +1,410 @@
+//! Checks run before the sidecar is spawned, so a broken install or an unwritable
+//! data directory fails the start right away with something the user can act on,
+//! instead of surfacing as a startup timeout.
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::diagnostics::{self, BUNDLED_TOOLS};
+use crate::settings::LoadProblem;
+use crate::storage::{self, StorageLocation};
+use crate::SidecarHost;
+
+/// Only this tool is required for the backend to be useful; the others back optional features
+const REQUIRED_TOOL: &str = "restic";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Ok,
+    /// The backend can start, but something will not work or is not the bundled one
+    Warning,
+    /// The backend would not start or would be unusable, so it is not spawned
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightCheck {
+    /// `settings`, `sidecar`, a tool name, `migrations` or `storage:<location>`
+    pub id: String,
+    pub status: CheckStatus,
+    pub message: String,
+    /// What to do about a warning or an error
+    pub fix: Option<String>,
+}
+
+impl PreflightCheck {
+    fn ok(id: impl Into<String>, message: String) -> Self {
+        Self {
+            id: id.into(),
+            status: CheckStatus::Ok,
+            message,
+            fix: None,
+        }
+    }
+
+    fn failed(id: impl Into<String>, status: CheckStatus, message: String, fix: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            status,
+            message,
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Outcome of the last preflight, handed to the frontend by `get_preflight_report`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightReport {
+    /// Unix timestamp (seconds)
+    pub checked_at: u64,
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Whether the sidecar can be spawned
+    pub fn passed(&self) -> bool {
+        !self.checks.iter().any(|check| check.status == CheckStatus::Error)
+    }
+
+    /// Whether every error may clear up on its own, like a data directory on a drive
+    /// that is not mounted yet, so starting again later is worth it. A broken install
+    /// stays broken until the app is reinstalled.
+    pub fn is_retryable(&self) -> bool {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Error)
+            .all(|check| check.id.starts_with("storage:"))
+    }
+
+    /// One line per failed check, with its fix, for the `backend://failed` error
+    pub fn error_summary(&self) -> String {
+        let failures: Vec<String> = self
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Error)
+            .map(|check| match &check.fix {
+                Some(fix) => format!("{}. {}", check.message.trim_end_matches('.'), fix),
+                None => check.message.clone(),
+            })
+            .collect();
+        format!("Startup checks failed:\n{}", failures.join("\n"))
+    }
+}
+
+/// Check the settings file, the sidecar binary, the bundled tools, the migrations and the storage locations
+pub fn run(host: &impl SidecarHost) -> PreflightReport {
+    let mut checks = vec![check_settings(host), check_sidecar(host)];
+
+    let bin_dir = crate::get_resources_path(host);
+    for tool in BUNDLED_TOOLS {
+        checks.push(check_tool(bin_dir.as_deref(), tool));
+    }
+
+    checks.push(check_migrations(host));
+
+    match storage::resolve(host) {
+        Ok(paths) => {
+            for location in StorageLocation::ALL {
+                let id = format!("storage:{}", location_name(location));
+                let dir = paths.dir(location);
+                checks.push(match storage::ensure_writable(dir) {
+                    Ok(()) => PreflightCheck::ok(id, format!("{:?} is writable", dir)),
+                    Err(e) => PreflightCheck::failed(
+                        id,
+                        CheckStatus::Error,
+                        e,
+                        "Fix the permissions of this directory, or choose another location with --data-dir or in the storage settings.",
+                    ),
+                });
+            }
+        }
+        Err(e) => checks.push(PreflightCheck::failed(
+            "storage:data",
+            CheckStatus::Error,
+            format!("The data directory could not be resolved: {}", e),
+            "Start the app with --data-dir pointing to a writable directory.",
+        )),
+    }
+
+    PreflightReport {
+        checked_at: crate::unix_now(),
+        checks,
+    }
+}
+
+fn check_settings(host: &impl SidecarHost) -> PreflightCheck {
+    let problem = host.app_state().settings_problem.lock().ok().and_then(|problem| problem.clone());
+    match problem {
+        None => PreflightCheck::ok("settings", "The settings file was loaded".to_string()),
+        Some(LoadProblem::Invalid(e)) => PreflightCheck::failed(
+            "settings",
+            CheckStatus::Warning,
+            format!("The settings file could not be used, so the defaults are in use: {}", e),
+            "Your previous settings were kept as settings.invalid.json in the config directory; fix it and rename it to settings.json while the app is closed, or set your options again.",
+        ),
+        // Running on defaults would move the data back to the default directories
+        Some(LoadProblem::Newer(e)) => PreflightCheck::failed(
+            "settings",
+            CheckStatus::Error,
+            format!("The settings file cannot be used: {}", e),
+            "Install the newer version of the app again; this one will not change the settings file.",
+        ),
+    }
+}
+
+fn check_sidecar(host: &impl SidecarHost) -> PreflightCheck {
+    let Some(path) = crate::get_sidecar_path(host) else {
+        // `start_sidecar` falls back to running the backend from source with bun
+        return match diagnostics::program_version(Path::new("bun"), "bun") {
+            Ok(version) => PreflightCheck::failed(
+                "sidecar",
+                CheckStatus::Warning,
+                format!("The compiled backend was not found; running it from source with bun {}", version),
+                "Reinstall the application if this is not a development build.",
+            ),
+            Err(e) => PreflightCheck::failed(
+                "sidecar",
+                CheckStatus::Error,
+                format!("The compiled backend was not found in the resources directory, and bun is not available: {}", e),
+                "Reinstall the application.",
+            ),
+        };
+    };
+
+    match is_executable(&path) {
+        Ok(true) => PreflightCheck::ok("sidecar", format!("Backend found at {:?}", path)),
+        Ok(false) => PreflightCheck::failed(
+            "sidecar",
+            CheckStatus::Error,
+            format!("The backend at {:?} is not executable.", path),
+            "Reinstall the application, or make the file executable (chmod +x).",
+        ),
+        Err(e) => PreflightCheck::failed(
+            "sidecar",
+            CheckStatus::Error,
+            format!("The backend at {:?} could not be read: {}", path, e),
+            "Reinstall the application.",
+        ),
+    }
+}
+
+fn check_tool(bin_dir: Option<&Path>, tool: &str) -> PreflightCheck {
+    let severity = if tool == REQUIRED_TOOL {
+        CheckStatus::Error
+    } else {
+        CheckStatus::Warning
+    };
+    let bundled = bin_dir.and_then(|dir| diagnostics::tool_path(dir, tool));
+
+    let Some(path) = bundled else {
+        // Like the backend, use the one in PATH when it is not bundled
+        let location = match bin_dir {
+            Some(dir) => format!("in {:?}", dir),
+            None => "(the resources directory could not be resolved)".to_string(),
+        };
+        return match diagnostics::program_version(Path::new(tool), tool) {
+            Ok(version) => PreflightCheck::failed(
+                tool,
+                CheckStatus::Warning,
+                format!("{} is not bundled {}; using {} from PATH", tool, location, version),
+                "Reinstall the application to use the tested version.",
+            ),
+            Err(_) => PreflightCheck::failed(
+                tool,
+                severity,
+                format!("{} was not found {} nor in PATH. {}", tool, location, tool_impact(tool)),
+                "Reinstall the application.",
+            ),
+        };
+    };
+
+    match diagnostics::program_version(&path, tool) {
+        Ok(version) => PreflightCheck::ok(tool, version),
+        Err(e) => PreflightCheck::failed(
+            tool,
+            severity,
+            format!("{} did not report a version: {}. {}", tool, e, tool_impact(tool)),
+            "Reinstall the application; if it keeps failing, check that an antivirus is not blocking the file.",
+        ),
+    }
+}
+
+/// What stops working without `tool`
+fn tool_impact(tool: &str) -> &'static str {
+    match tool {
+        "restic" => "Backups and restores cannot run.",
+        "rclone" => "Repositories on rclone remotes will not work.",
+        "shoutrrr" => "Notifications will not be sent.",
+        _ => "",
+    }
+}
+
+fn check_migrations(host: &impl SidecarHost) -> PreflightCheck {
+    let fix = "Reinstall the application.";
+    let Some(dir) = crate::get_migrations_path(host) else {
+        return PreflightCheck::failed(
+            "migrations",
+            CheckStatus::Error,
+            "The resources directory could not be resolved, so the database migrations cannot be found.".to_string(),
+            fix,
+        );
+    };
+
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return PreflightCheck::failed(
+                "migrations",
+                CheckStatus::Error,
+                format!("The database migrations at {:?} could not be read: {}", dir, e),
+                fix,
+            );
+        }
+    };
+
+    let count = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "sql"))
+        .count();
+    // drizzle only applies the migrations listed in its journal
+    let journal = dir.join("meta").join("_journal.json");
+
+    if count == 0 || !journal.is_file() {
+        return PreflightCheck::failed(
+            "migrations",
+            CheckStatus::Error,
+            format!("The database migrations at {:?} are incomplete.", dir),
+            fix,
+        );
+    }
+    PreflightCheck::ok("migrations", format!("{} migrations in {:?}", count, dir))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> std::io::Result<bool> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = std::fs::metadata(path)?;
+    Ok(metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> std::io::Result<bool> {
+    Ok(std::fs::metadata(path)?.is_file())
+}
+
+fn location_name(location: StorageLocation) -> &'static str {
+    match location {
+        StorageLocation::Data => "data",
+        StorageLocation::Repositories => "repositories",
+        StorageLocation::Volumes => "volumes",
+        StorageLocation::Cache => "cache",
+        StorageLocation::Password => "password",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(id: &str, status: CheckStatus, message: &str, fix: Option<&str>) -> PreflightCheck {
+        PreflightCheck {
+            id: id.to_string(),
+            status,
+            message: message.to_string(),
+            fix: fix.map(str::to_string),
+        }
+    }
+
+    fn report(checks: Vec<PreflightCheck>) -> PreflightReport {
+        PreflightReport { checked_at: 0, checks }
+    }
+
+    #[test]
+    fn warnings_do_not_fail_the_preflight() {
+        let passed = report(vec![
+            check("sidecar", CheckStatus::Ok, "Backend found", None),
+            check("rclone", CheckStatus::Warning, "rclone is not bundled", Some("Reinstall.")),
+        ]);
+        assert!(passed.passed());
+
+        let mut failed = passed.clone();
+        failed.checks.push(check("restic", CheckStatus::Error, "restic was not found", Some("Reinstall.")));
+        assert!(!failed.passed());
+    }
+
+    #[test]
+    fn only_storage_errors_are_retryable() {
+        let storage = report(vec![
+            check("rclone", CheckStatus::Warning, "rclone is not bundled", Some("Reinstall.")),
+            check("storage:data", CheckStatus::Error, "Not mounted", Some("Mount it.")),
+            check("storage:cache", CheckStatus::Error, "Not mounted", Some("Mount it.")),
+        ]);
+        assert!(storage.is_retryable());
+
+        let mut broken = storage.clone();
+        broken.checks.push(check("migrations", CheckStatus::Error, "Incomplete", Some("Reinstall.")));
+        assert!(!broken.is_retryable());
+    }
+
+    #[test]
+    fn error_summary_lists_each_error_with_its_fix() {
+        let failed = report(vec![
+            check("settings", CheckStatus::Warning, "Defaults are in use", Some("Set your options again.")),
+            check("restic", CheckStatus::Error, "restic was not found.", Some("Reinstall the application.")),
+            check("storage:data", CheckStatus::Error, "Permission denied", None),
+        ]);
+
+        assert_eq!(
+            failed.error_summary(),
+            "Startup checks failed:\nrestic was not found. Reinstall the application.\nPermission denied"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_files_with_an_execute_bit_are_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("c3i-backup-one-backend");
+        std::fs::write(&file, "").unwrap();
+        assert!(!is_executable(&file).unwrap());
+
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_executable(&file).unwrap());
+
+        assert!(!is_executable(dir.path()).unwrap());
+        assert!(is_executable(&dir.path().join("missing")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bundled_tool_reports_its_version() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join("shoutrrr");
+        std::fs::write(&tool, "#!/bin/sh\necho \"shoutrrr 0.8.0\"\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let check = check_tool(Some(dir.path()), "shoutrrr");
+
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert_eq!(check.message, "shoutrrr 0.8.0");
+    }
+
+    #[test]
+    fn missing_optional_tool_is_a_warning() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let check = check_tool(Some(dir.path()), "c3i-missing-tool");
+
+        assert_eq!(check.status, CheckStatus::Warning);
+        assert!(check.message.starts_with("c3i-missing-tool was not found in"));
+        assert!(check.fix.is_some());
+    }
+}
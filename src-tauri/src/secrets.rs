@@ -0,0 +1,499 @@
+//! The master password of the restic repositories, which the backend also derives
+//! the keys of its encrypted settings from. It is kept in the platform secret store
+//! (Keychain, Credential Manager, Secret Service) and handed to the sidecar as the
+//! first line of its stdin, so it is never written to disk.
+//!
+//! Installs that predate this kept it in the `restic-pass` file, which is imported
+//! once and then shredded. Where no secret store can be reached (typically a
+//! headless server without a Secret Service), the file keeps being used.
+
+use rand::Rng;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::storage::StoragePaths;
+
+const SERVICE: &str = "c3i-backup-one";
+/// Left in the data directory once its password is in the secret store, so that an
+/// unreachable store fails the start instead of the sidecar creating a new password
+pub const KEYRING_MARKER_FILE: &str = "restic-pass.keyring";
+const KEYRING_MARKER_CONTENTS: &str = "The restic password of this data directory is kept in the system secret store.\n";
+/// Length of a generated password, in random bytes (hex encoded), as the backend generated them
+const GENERATED_PASSWORD_BYTES: usize = 32;
+
+/// Where the password of the running sidecar comes from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PasswordStore {
+    Keyring,
+    /// The sidecar reads, and creates if needed, the password file itself
+    File,
+}
+
+pub enum MasterPassword {
+    Keyring(String),
+    File,
+}
+
+impl MasterPassword {
+    pub fn store(&self) -> PasswordStore {
+        match self {
+            MasterPassword::Keyring(_) => PasswordStore::Keyring,
+            MasterPassword::File => PasswordStore::File,
+        }
+    }
+}
+
+/// Secret store entry for the data directory of `paths`, so that installs started
+/// with different `--data-dir`s keep their own passwords
+fn entry(paths: &StoragePaths) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, &format!("restic-password:{}", paths.data_dir.to_string_lossy()))
+}
+
+fn marker(paths: &StoragePaths) -> PathBuf {
+    paths.data_dir.join(KEYRING_MARKER_FILE)
+}
+
+/// Password for the sidecar about to start with `paths`, importing the password file
+/// into the secret store (or creating a new password) the first time
+pub fn load(paths: &StoragePaths) -> Result<MasterPassword, String> {
+    match load_from_keyring(paths) {
+        Ok(password) => Ok(MasterPassword::Keyring(password)),
+        Err(e) if marker(paths).exists() => Err(format!(
+            "The restic password is kept in the system secret store, but it could not be read from it: {}. \
+             Unlock the secret store (or log in to the desktop session) and restart the application.",
+            e
+        )),
+        Err(e) => {
+            log::warn!(
+                "Secret store unavailable, the restic password stays in {:?}: {}",
+                paths.password_file,
+                e
+            );
+            Ok(MasterPassword::File)
+        }
+    }
+}
+
+fn load_from_keyring(paths: &StoragePaths) -> Result<String, String> {
+    let entry = entry(paths).map_err(|e| e.to_string())?;
+
+    match entry.get_password() {
+        Ok(password) => {
+            write_marker(paths);
+            remove_leftover_file(&paths.password_file, &password);
+            return Ok(password);
+        }
+        // Generating a new password would lock the backend out of its repositories
+        Err(keyring::Error::NoEntry) if marker(paths).exists() => {
+            return Err("the password of this data directory is missing".to_string());
+        }
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(e.to_string()),
+    }
+
+    let (password, imported) = match std::fs::read_to_string(&paths.password_file) {
+        // Kept byte for byte: the backend derives keys from the untrimmed contents
+        Ok(password) => (password, true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (generate(), false),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", paths.password_file, e)),
+    };
+
+    entry.set_password(&password).map_err(|e| e.to_string())?;
+    // Read it back before the only other copy goes away
+    match entry.get_password() {
+        Ok(stored) if stored == password => {}
+        Ok(_) => return Err("The secret store returned a different password than the one saved".to_string()),
+        Err(e) => return Err(e.to_string()),
+    }
+    write_marker(paths);
+
+    if imported {
+        log::info!("Moved the restic password from {:?} to the secret store", paths.password_file);
+        if let Err(e) = shred(&paths.password_file) {
+            log::error!("Failed to remove {:?}: {}", paths.password_file, e);
+        }
+    } else {
+        log::info!("Created a new restic password in the secret store");
+    }
+    Ok(password)
+}
+
+fn write_marker(paths: &StoragePaths) {
+    let marker = marker(paths);
+    if !marker.exists() {
+        if let Err(e) = std::fs::write(&marker, KEYRING_MARKER_CONTENTS) {
+            log::warn!("Failed to write {:?}: {}", marker, e);
+        }
+    }
+}
+
+/// Finish an import interrupted before the file was shredded. A file with another
+/// password (e.g. restored by hand) is left alone, since it may be the only copy.
+fn remove_leftover_file(file: &Path, password: &str) {
+    match std::fs::read_to_string(file) {
+        Ok(contents) if contents == password => {
+            if let Err(e) = shred(file) {
+                log::error!("Failed to remove {:?}: {}", file, e);
+            }
+        }
+        Ok(_) => log::warn!(
+            "{:?} holds a different password than the secret store and is ignored",
+            file
+        ),
+        Err(_) => {}
+    }
+}
+
+/// Password in use for `paths`, without importing or creating anything
+pub fn peek(paths: &StoragePaths) -> Option<String> {
+    entry(paths)
+        .and_then(|entry| entry.get_password())
+        .ok()
+        .or_else(|| std::fs::read_to_string(&paths.password_file).ok())
+}
+
+/// Copy the password to the entry of `to` before the data directory moves with
+/// its data. `forget` removes the old entry once the data has moved.
+pub fn copy(from: &StoragePaths, to: &StoragePaths) -> Result<(), String> {
+    let source = entry(from).map_err(|e| e.to_string())?;
+    let password = match source.get_password() {
+        Ok(password) => password,
+        // Only in the password file, which moves with the data
+        Err(keyring::Error::NoEntry) => return Ok(()),
+        Err(e) => return Err(format!("Failed to read the restic password from the secret store: {}", e)),
+    };
+
+    entry(to)
+        .and_then(|target| target.set_password(&password))
+        .map_err(|e| format!("Failed to save the restic password in the secret store: {}", e))
+}
+
+/// Remove the password of a data directory that is no longer used
+pub fn forget(paths: &StoragePaths) {
+    match entry(paths).and_then(|entry| entry.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => log::warn!(
+            "Failed to remove the restic password of {:?} from the secret store: {}",
+            paths.data_dir,
+            e
+        ),
+    }
+}
+
+/// Make `password` the password of `paths`, as restored from a state archive: in
+/// the secret store when it can be reached, otherwise in the password file
+pub fn replace(paths: &StoragePaths, password: &str) -> Result<(), String> {
+    let stored = entry(paths).and_then(|entry| {
+        entry.set_password(password)?;
+        entry.get_password()
+    });
+
+    match stored {
+        Ok(stored) if stored == password => {
+            write_marker(paths);
+            // The replaced password, already archived with the replaced state
+            if paths.password_file.exists() {
+                shred(&paths.password_file).map_err(|e| format!("Failed to remove {:?}: {}", paths.password_file, e))?;
+            }
+            Ok(())
+        }
+        Ok(_) => Err("The secret store returned a different password than the one saved".to_string()),
+        Err(e) if marker(paths).exists() => Err(format!(
+            "The restic password could not be saved in the system secret store: {}",
+            e
+        )),
+        Err(e) => {
+            log::warn!("Secret store unavailable, restoring the restic password to {:?}: {}", paths.password_file, e);
+            write_password_file(&paths.password_file, password)
+        }
+    }
+}
+
+/// Write the password file readable only by the current user, as the backend creates it
+pub fn write_password_file(file: &Path, password: &str) -> Result<(), String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    }
+    options
+        .open(file)
+        .and_then(|mut handle| handle.write_all(password.as_bytes()))
+        .map_err(|e| format!("Failed to write {:?}: {}", file, e))
+}
+
+/// The line the sidecar reads from its stdin: the password, hex encoded so that
+/// any newline in it survives
+pub fn handoff_line(password: &str) -> String {
+    let mut line = hex(password.as_bytes());
+    line.push('\n');
+    line
+}
+
+fn generate() -> String {
+    let bytes: [u8; GENERATED_PASSWORD_BYTES] = rand::thread_rng().gen();
+    hex(&bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Overwrite `file` with zeros before removing it. Best effort: journaling and
+/// copy-on-write filesystems or SSDs may still keep the old blocks.
+fn shred(file: &Path) -> std::io::Result<()> {
+    let mut handle = std::fs::OpenOptions::new().write(true).open(file)?;
+    let len = handle.metadata()?.len();
+    handle.write_all(&vec![0; len as usize])?;
+    handle.sync_all()?;
+    drop(handle);
+    std::fs::remove_file(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi, CredentialPersistence};
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::{Mutex, Once};
+
+    /// Passwords by entry user. Unlike keyring's own mock, which keeps the password in
+    /// the `Entry`, every entry for the same data directory sees the same password.
+    static STORE: Mutex<BTreeMap<String, Vec<u8>>> = Mutex::new(BTreeMap::new());
+    /// Entry users whose store behaves as if it were locked
+    static UNREACHABLE: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+    #[derive(Debug)]
+    struct TestCredential {
+        user: String,
+    }
+
+    impl TestCredential {
+        fn check_reachable(&self) -> keyring::Result<()> {
+            if UNREACHABLE.lock().unwrap().contains(&self.user) {
+                return Err(keyring::Error::NoStorageAccess("the secret store is locked".into()));
+            }
+            Ok(())
+        }
+    }
+
+    impl CredentialApi for TestCredential {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            self.check_reachable()?;
+            STORE.lock().unwrap().insert(self.user.clone(), secret.to_vec());
+            Ok(())
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            self.check_reachable()?;
+            STORE.lock().unwrap().get(&self.user).cloned().ok_or(keyring::Error::NoEntry)
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            self.check_reachable()?;
+            STORE.lock().unwrap().remove(&self.user).map(|_| ()).ok_or(keyring::Error::NoEntry)
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    struct TestCredentialBuilder;
+
+    impl CredentialBuilderApi for TestCredentialBuilder {
+        fn build(&self, _target: Option<&str>, _service: &str, user: &str) -> keyring::Result<Box<Credential>> {
+            Ok(Box::new(TestCredential { user: user.to_string() }))
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn persistence(&self) -> CredentialPersistence {
+            CredentialPersistence::ProcessOnly
+        }
+    }
+
+    /// Paths in a new data directory, with the test store as the default credential store
+    fn test_paths() -> (tempfile::TempDir, StoragePaths) {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| keyring::set_default_credential_builder(Box::new(TestCredentialBuilder)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_path_buf();
+        let paths = StoragePaths {
+            database: data_dir.join("c3i-backup-one.db"),
+            repositories_dir: data_dir.join("repositories"),
+            volumes_dir: data_dir.join("volumes"),
+            cache_dir: data_dir.join("cache"),
+            password_file: data_dir.join("restic-pass"),
+            data_dir,
+        };
+        (dir, paths)
+    }
+
+    fn stored(paths: &StoragePaths) -> keyring::Result<String> {
+        entry(paths).and_then(|entry| entry.get_password())
+    }
+
+    fn make_unreachable(paths: &StoragePaths) {
+        let user = format!("restic-password:{}", paths.data_dir.to_string_lossy());
+        UNREACHABLE.lock().unwrap().insert(user);
+    }
+
+    fn keyring_password(password: MasterPassword) -> String {
+        match password {
+            MasterPassword::Keyring(password) => password,
+            MasterPassword::File => panic!("expected the password to come from the secret store"),
+        }
+    }
+
+    #[test]
+    fn imports_the_password_file_and_shreds_it() {
+        let (_dir, paths) = test_paths();
+        std::fs::write(&paths.password_file, "old-password\n").unwrap();
+
+        let password = keyring_password(load(&paths).unwrap());
+
+        assert_eq!(password, "old-password\n");
+        assert_eq!(stored(&paths).unwrap(), "old-password\n");
+        assert!(!paths.password_file.exists());
+        assert!(marker(&paths).exists());
+        assert_eq!(keyring_password(load(&paths).unwrap()), "old-password\n");
+    }
+
+    #[test]
+    fn creates_a_password_when_there_is_none() {
+        let (_dir, paths) = test_paths();
+
+        let password = keyring_password(load(&paths).unwrap());
+
+        assert_eq!(password.len(), GENERATED_PASSWORD_BYTES * 2);
+        assert_eq!(stored(&paths).unwrap(), password);
+        assert!(!paths.password_file.exists());
+    }
+
+    #[test]
+    fn fails_when_the_marker_exists_but_the_entry_is_missing() {
+        let (_dir, paths) = test_paths();
+        std::fs::write(marker(&paths), KEYRING_MARKER_CONTENTS).unwrap();
+
+        let error = load(&paths).err().unwrap();
+
+        assert!(error.contains("is missing"), "{}", error);
+        assert!(matches!(stored(&paths), Err(keyring::Error::NoEntry)));
+        assert!(!paths.password_file.exists());
+    }
+
+    #[test]
+    fn keeps_using_the_file_when_the_store_cannot_be_reached() {
+        let (_dir, paths) = test_paths();
+        std::fs::write(&paths.password_file, "file-password").unwrap();
+        make_unreachable(&paths);
+
+        assert!(matches!(load(&paths), Ok(MasterPassword::File)));
+        assert_eq!(std::fs::read_to_string(&paths.password_file).unwrap(), "file-password");
+        assert!(!marker(&paths).exists());
+    }
+
+    #[test]
+    fn fails_when_the_store_cannot_be_reached_after_an_import() {
+        let (_dir, paths) = test_paths();
+        load(&paths).unwrap();
+        make_unreachable(&paths);
+
+        assert!(load(&paths).is_err());
+    }
+
+    #[test]
+    fn removes_a_leftover_file_only_when_it_holds_the_same_password() {
+        let (_dir, paths) = test_paths();
+        let password = keyring_password(load(&paths).unwrap());
+
+        std::fs::write(&paths.password_file, &password).unwrap();
+        load(&paths).unwrap();
+        assert!(!paths.password_file.exists());
+
+        std::fs::write(&paths.password_file, "restored-by-hand").unwrap();
+        assert_eq!(keyring_password(load(&paths).unwrap()), password);
+        assert_eq!(std::fs::read_to_string(&paths.password_file).unwrap(), "restored-by-hand");
+    }
+
+    #[test]
+    fn copy_and_forget_follow_a_storage_move() {
+        let (_from_dir, from) = test_paths();
+        let (_to_dir, to) = test_paths();
+        let password = keyring_password(load(&from).unwrap());
+
+        copy(&from, &to).unwrap();
+        assert_eq!(stored(&to).unwrap(), password);
+
+        forget(&from);
+        assert!(matches!(stored(&from), Err(keyring::Error::NoEntry)));
+        assert_eq!(peek(&to).as_deref(), Some(password.as_str()));
+    }
+
+    #[test]
+    fn copy_leaves_a_password_file_to_the_data_move() {
+        let (_from_dir, from) = test_paths();
+        let (_to_dir, to) = test_paths();
+        std::fs::write(&from.password_file, "file-password").unwrap();
+
+        copy(&from, &to).unwrap();
+
+        assert!(matches!(stored(&to), Err(keyring::Error::NoEntry)));
+    }
+
+    #[test]
+    fn replace_stores_the_password_and_removes_the_file() {
+        let (_dir, paths) = test_paths();
+        load(&paths).unwrap();
+        std::fs::write(&paths.password_file, "replaced").unwrap();
+
+        replace(&paths, "restored").unwrap();
+
+        assert_eq!(stored(&paths).unwrap(), "restored");
+        assert!(!paths.password_file.exists());
+        assert!(marker(&paths).exists());
+    }
+
+    #[test]
+    fn replace_writes_the_file_when_the_store_cannot_be_reached() {
+        let (_dir, paths) = test_paths();
+        make_unreachable(&paths);
+
+        replace(&paths, "restored").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&paths.password_file).unwrap(), "restored");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&paths.password_file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn replace_fails_when_the_store_holding_the_password_cannot_be_reached() {
+        let (_dir, paths) = test_paths();
+        load(&paths).unwrap();
+        make_unreachable(&paths);
+
+        assert!(replace(&paths, "restored").is_err());
+        assert!(!paths.password_file.exists());
+    }
+
+    #[test]
+    fn handoff_line_survives_newlines() {
+        assert_eq!(handoff_line("a\nb"), "610a62\n");
+    }
+}
@@ -0,0 +1,312 @@
+//! Subscription to the backend's `/api/v1/events` SSE stream, so the shell can
+//! react to backup activity even while the webview is hidden in the tray.
+
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::backend_client::{BackendClient, BackendEndpoint, ServerEvent};
+use crate::settings::NotificationSettings;
+use crate::tray::{self, RunningBackup};
+use crate::AppState;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Keep a subscription open for as long as the app runs, reconnecting whenever
+/// the backend restarts or the stream drops.
+pub async fn run(app: AppHandle) {
+    let mut last_error: Option<String> = None;
+
+    loop {
+        if crate::is_sidecar_stopping(&app.state::<AppState>()) {
+            return;
+        }
+
+        let result = stream_events(&app).await;
+        clear_running_backups(&app);
+
+        match result {
+            Ok(()) => {
+                log::info!("Backend event stream closed, reconnecting");
+                last_error = None;
+            }
+            Err(e) => {
+                // The backend is often just not up yet; only log when the reason changes
+                if last_error.as_deref() != Some(e.as_str()) {
+                    log::warn!("Backend event stream unavailable: {}", e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn stream_events(app: &AppHandle) -> Result<(), String> {
+    let client = BackendClient::from_app(app).map_err(|e| e.to_string())?;
+    let mut events = client.subscribe_events().await.map_err(|e| e.to_string())?;
+
+    // A webview cannot open an EventSource on a local socket, so it gets the stream from us
+    let relay = matches!(
+        crate::backend_endpoint(&app.state::<AppState>()),
+        Some(BackendEndpoint::LocalSocket(_))
+    );
+
+    log::info!("Subscribed to backend events");
+
+    loop {
+        let raw = match events.next_raw().await {
+            Ok(Some(raw)) => raw,
+            Ok(None) => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if relay {
+            if let Err(e) = app.emit(crate::BACKEND_EVENT, &raw) {
+                log::warn!("Failed to relay backend event to webview: {}", e);
+            }
+        }
+
+        match raw.parse() {
+            Ok(event) => handle_event(app, &event),
+            Err(e) => log::warn!("Ignoring malformed backend event: {}", e),
+        }
+    }
+}
+
+pub fn handle_event(app: &AppHandle, event: &ServerEvent) {
+    track_backup_progress(app, event);
+
+    // Refresh the tray's schedule list on (re)connect and whenever a run starts or ends
+    if matches!(
+        event,
+        ServerEvent::Connected | ServerEvent::BackupStarted(_) | ServerEvent::BackupCompleted(_)
+    ) {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            tray::reload_schedules(&app).await;
+        });
+    }
+
+    let flags = app
+        .state::<AppState>()
+        .settings
+        .lock()
+        .map(|s| s.notifications.clone())
+        .unwrap_or_default();
+
+    if let Some((title, body)) = notification_for(event, &flags) {
+        if let Err(e) = crate::show_notification(app, &title, &body) {
+            log::warn!("Failed to show notification: {}", e);
+        }
+    }
+}
+
+/// Keep the tray's running-backups section in sync. The tray is only rebuilt
+/// when a percentage changes, not on every progress tick, so the bytes and files
+/// it shows are those of the last whole percent.
+fn track_backup_progress(app: &AppHandle, event: &ServerEvent) {
+    let state = app.state::<AppState>();
+
+    let changed = match event {
+        ServerEvent::BackupStarted(e) => {
+            let mut running = state.running_backups.lock().unwrap_or_else(|e| e.into_inner());
+            running.insert(
+                e.schedule_id,
+                RunningBackup {
+                    volume_name: e.volume_name.clone(),
+                    repository_name: e.repository_name.clone(),
+                    percent_done: 0.0,
+                    seconds_elapsed: 0.0,
+                    bytes_done: 0,
+                    total_bytes: 0,
+                    current_files: Vec::new(),
+                },
+            );
+            true
+        }
+        ServerEvent::BackupProgress(e) => {
+            let update = RunningBackup {
+                volume_name: e.volume_name.clone(),
+                repository_name: e.repository_name.clone(),
+                percent_done: e.percent_done,
+                seconds_elapsed: e.seconds_elapsed,
+                bytes_done: e.bytes_done,
+                total_bytes: e.total_bytes,
+                current_files: e.current_files.clone(),
+            };
+            let mut running = state.running_backups.lock().unwrap_or_else(|e| e.into_inner());
+            let previous = running.insert(e.schedule_id, update.clone());
+            previous.map(|p| p.percent()) != Some(update.percent())
+        }
+        ServerEvent::BackupCompleted(e) => {
+            let mut running = state.running_backups.lock().unwrap_or_else(|e| e.into_inner());
+            running.remove(&e.schedule_id).is_some()
+        }
+        _ => false,
+    };
+
+    if changed {
+        tray::refresh(app);
+    }
+}
+
+/// Backups cannot be tracked without the stream, so drop them instead of showing stale progress
+fn clear_running_backups(app: &AppHandle) {
+    let had_backups = {
+        let state = app.state::<AppState>();
+        let mut running = state.running_backups.lock().unwrap_or_else(|e| e.into_inner());
+        let had_backups = !running.is_empty();
+        running.clear();
+        had_backups
+    };
+
+    if had_backups {
+        tray::refresh(app);
+    }
+}
+
+/// Map a backend event to the native notification it should raise, if enabled
+fn notification_for(event: &ServerEvent, flags: &NotificationSettings) -> Option<(String, String)> {
+    match event {
+        ServerEvent::BackupStarted(e) if flags.backup_started => Some((
+            "⏳ Iniciando copia de seguridad".to_string(),
+            format!("{} → {}", e.volume_name, e.repository_name),
+        )),
+        ServerEvent::BackupCompleted(e) => {
+            let title = match e.status.as_str() {
+                "success" if flags.backup_success => "✓ Copia de seguridad completada",
+                "error" if flags.backup_error => "✗ Error en copia de seguridad",
+                "warning" if flags.backup_warning => "⚠ Copia de seguridad completada con advertencias",
+                "stopped" if flags.backup_warning => "⏹ Copia de seguridad detenida",
+                _ => return None,
+            };
+            Some((title.to_string(), format!("{} → {}", e.volume_name, e.repository_name)))
+        }
+        ServerEvent::MirrorStarted(e) if flags.mirror_started => {
+            Some(("⏳ Iniciando copia espejo".to_string(), e.repository_name.clone()))
+        }
+        ServerEvent::MirrorCompleted(e) => match e.status.as_deref() {
+            Some("success") if flags.mirror_success => {
+                Some(("✓ Copia espejo completada".to_string(), e.repository_name.clone()))
+            }
+            Some("error") if flags.mirror_error => {
+                let body = match &e.error {
+                    Some(error) => format!("{}: {}", e.repository_name, error),
+                    None => e.repository_name.clone(),
+                };
+                Some(("✗ Error en copia espejo".to_string(), body))
+            }
+            _ => None,
+        },
+        ServerEvent::VolumeStatusChanged(e) if flags.volume_status => {
+            let title = if e.status == "error" {
+                "✗ Error en el volumen"
+            } else {
+                "Estado del volumen actualizado"
+            };
+            Some((title.to_string(), format!("{}: {}", e.volume_name, e.status)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend_client::{BackupCompletedEvent, BackupEvent, MirrorEvent, VolumeStatusEvent};
+
+    fn completed(status: &str) -> ServerEvent {
+        ServerEvent::BackupCompleted(BackupCompletedEvent {
+            schedule_id: 1,
+            volume_name: "docs".to_string(),
+            repository_name: "nas".to_string(),
+            status: status.to_string(),
+        })
+    }
+
+    fn mirror_completed(status: &str, error: Option<&str>) -> ServerEvent {
+        ServerEvent::MirrorCompleted(MirrorEvent {
+            repository_name: "offsite".to_string(),
+            status: Some(status.to_string()),
+            error: error.map(str::to_string),
+        })
+    }
+
+    fn title(event: &ServerEvent, flags: &NotificationSettings) -> Option<String> {
+        notification_for(event, flags).map(|(title, _)| title)
+    }
+
+    fn title_of(event: &ServerEvent, flags: &NotificationSettings) -> String {
+        title(event, flags).unwrap()
+    }
+
+    #[test]
+    fn backup_notifications_name_the_volume_and_repository() {
+        let started = ServerEvent::BackupStarted(BackupEvent {
+            schedule_id: 1,
+            volume_name: "docs".to_string(),
+            repository_name: "nas".to_string(),
+        });
+        let flags = NotificationSettings::default();
+
+        let (heading, body) = notification_for(&started, &flags).unwrap();
+        assert_eq!(heading, "⏳ Iniciando copia de seguridad");
+        assert_eq!(body, "docs → nas");
+
+        assert_eq!(title_of(&completed("success"), &flags), "✓ Copia de seguridad completada");
+        assert_eq!(title_of(&completed("error"), &flags), "✗ Error en copia de seguridad");
+        assert_eq!(title_of(&completed("stopped"), &flags), "⏹ Copia de seguridad detenida");
+        assert!(title(&completed("unknown"), &flags).is_none());
+    }
+
+    #[test]
+    fn disabled_notifications_are_not_raised() {
+        let flags = NotificationSettings {
+            backup_success: false,
+            backup_warning: false,
+            mirror_error: false,
+            volume_status: false,
+            ..NotificationSettings::default()
+        };
+
+        assert!(title(&completed("success"), &flags).is_none());
+        // Stopped backups follow the warning flag
+        assert!(title(&completed("warning"), &flags).is_none());
+        assert!(title(&completed("stopped"), &flags).is_none());
+        assert!(title(&completed("error"), &flags).is_some());
+        assert!(title(&mirror_completed("error", None), &flags).is_none());
+        assert!(title(&mirror_completed("success", None), &flags).is_some());
+
+        let volume = ServerEvent::VolumeStatusChanged(VolumeStatusEvent {
+            volume_name: "docs".to_string(),
+            status: "error".to_string(),
+        });
+        assert!(title(&volume, &flags).is_none());
+    }
+
+    #[test]
+    fn mirror_errors_carry_the_reason() {
+        let flags = NotificationSettings::default();
+
+        let (heading, body) = notification_for(&mirror_completed("error", Some("timeout")), &flags).unwrap();
+        assert_eq!(heading, "✗ Error en copia espejo");
+        assert_eq!(body, "offsite: timeout");
+
+        let (_, body) = notification_for(&mirror_completed("error", None), &flags).unwrap();
+        assert_eq!(body, "offsite");
+    }
+
+    #[test]
+    fn events_without_a_notification_are_ignored() {
+        let flags = NotificationSettings::default();
+
+        for event in [
+            ServerEvent::Connected,
+            ServerEvent::Heartbeat,
+            ServerEvent::Other("backup:queued".to_string()),
+        ] {
+            assert!(notification_for(&event, &flags).is_none());
+        }
+    }
+}
@@ -0,0 +1,635 @@
+//! Shell-side settings, persisted as JSON in the app config directory.
+//!
+//! The file carries a schema `version`. Older files are migrated on load (keeping
+//! a copy of the original) and every file is validated. An unreadable or invalid
+//! file is moved aside and defaults are used; a file from a newer version is left
+//! alone and keeps the backend from starting. Neither is ever saved over.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const SETTINGS_FILE: &str = "settings.json";
+/// Where an unreadable settings file is kept for the user to fix
+const INVALID_SETTINGS_FILE: &str = "settings.invalid.json";
+
+/// Bump when a field is renamed or changes meaning, and add a step to `migrate`.
+/// New fields with defaults do not need a bump; older builds ignore them.
+pub const CURRENT_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ShellSettings {
+    pub version: u32,
+    pub sidecar: SidecarSettings,
+    pub health: HealthSettings,
+    pub notifications: NotificationSettings,
+    pub storage: StorageSettings,
+    pub logging: LoggingSettings,
+    pub window: WindowSettings,
+    pub tray: TraySettings,
+    pub snapshots: SnapshotSettings,
+    /// Written by the shell only
+    pub upgrades: UpgradeHistory,
+}
+
+impl Default for ShellSettings {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            sidecar: SidecarSettings::default(),
+            health: HealthSettings::default(),
+            notifications: NotificationSettings::default(),
+            storage: StorageSettings::default(),
+            logging: LoggingSettings::default(),
+            window: WindowSettings::default(),
+            tray: TraySettings::default(),
+            snapshots: SnapshotSettings::default(),
+            upgrades: UpgradeHistory::default(),
+        }
+    }
+}
+
+impl ShellSettings {
+    /// Reject values the shell cannot work with, naming the offending field
+    pub fn validate(&self) -> Result<(), String> {
+        fn check(ok: bool, message: &str) -> Result<(), String> {
+            if ok {
+                Ok(())
+            } else {
+                Err(message.to_string())
+            }
+        }
+
+        check(
+            (1..=300).contains(&self.sidecar.shutdown_grace_secs),
+            "sidecar.shutdownGraceSecs must be between 1 and 300",
+        )?;
+        check(
+            (10..=600).contains(&self.sidecar.startup_timeout_secs),
+            "sidecar.startupTimeoutSecs must be between 10 and 600",
+        )?;
+        check(self.sidecar.port != Some(0), "sidecar.port must be between 1 and 65535")?;
+        check(self.health.interval_secs >= 1, "health.intervalSecs must be at least 1")?;
+        check(
+            (1..=self.health.interval_secs).contains(&self.health.timeout_secs),
+            "health.timeoutSecs must be between 1 and health.intervalSecs",
+        )?;
+        check(self.health.failure_threshold >= 1, "health.failureThreshold must be at least 1")?;
+        check(self.health.notify_after_failures >= 1, "health.notifyAfterFailures must be at least 1")?;
+        check(
+            self.health.restart_after_failures != Some(0),
+            "health.restartAfterFailures must be at least 1, or null to disable restarts",
+        )?;
+        check(
+            (1..=1024).contains(&self.logging.max_file_size_mb),
+            "logging.maxFileSizeMb must be between 1 and 1024",
+        )?;
+        check(
+            (1..=100).contains(&self.logging.keep_files),
+            "logging.keepFiles must be between 1 and 100",
+        )?;
+        check(
+            self.logging.keep_days != Some(0),
+            "logging.keepDays must be at least 1, or null to keep files by count only",
+        )?;
+        check(
+            (1..=720).contains(&self.snapshots.interval_hours),
+            "snapshots.intervalHours must be between 1 and 720",
+        )?;
+        check(
+            (1..=100).contains(&self.snapshots.keep),
+            "snapshots.keep must be between 1 and 100",
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SidecarSettings {
+    /// Seconds to wait for the backend to shut down on its own before killing it
+    pub shutdown_grace_secs: u64,
+    /// Seconds to wait for a (re)started backend to answer its first healthcheck
+    pub startup_timeout_secs: u64,
+    /// How the shell and webview reach the backend
+    pub transport: SidecarTransport,
+    /// Fixed loopback port for the TCP transport instead of a free one; `--port` wins
+    pub port: Option<u16>,
+}
+
+impl Default for SidecarSettings {
+    fn default() -> Self {
+        Self {
+            shutdown_grace_secs: 15,
+            startup_timeout_secs: 60,
+            transport: SidecarTransport::default(),
+            port: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SidecarTransport {
+    /// HTTP on 127.0.0.1 with a random port
+    #[default]
+    Tcp,
+    /// A Unix domain socket in the app's local data directory, or a named pipe on Windows.
+    /// Other local users and processes cannot reach the backend at all.
+    LocalSocket,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LoggingSettings {
+    /// Messages of the shell itself
+    pub level: LogLevel,
+    /// Lines of the backend, by the level it logged them at. The backend is started
+    /// with this level, so raising it only takes full effect once it restarts.
+    pub sidecar_level: LogLevel,
+    /// Start a new log file once the current one reaches this size
+    pub max_file_size_mb: u64,
+    /// Also start a new log file once the current one is this old
+    pub rotation: LogRotation,
+    /// Rotated files kept per log, newest first
+    pub keep_files: u32,
+    /// Delete rotated files older than this many days; `None` keeps them by count only
+    pub keep_days: Option<u32>,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::default(),
+            sidecar_level: LogLevel::default(),
+            max_file_size_mb: 10,
+            rotation: LogRotation::default(),
+            keep_files: 7,
+            keep_days: Some(30),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogRotation {
+    /// By size only
+    Never,
+    #[default]
+    Daily,
+    Weekly,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn filter(self) -> log::LevelFilter {
+        match self {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WindowSettings {
+    /// Closing the window hides it to the tray; otherwise it quits the app
+    pub close_to_tray: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self { close_to_tray: true }
+    }
+}
+
+/// Local snapshots of the database, restic password and settings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SnapshotSettings {
+    pub enabled: bool,
+    /// Take a snapshot once the newest one is this old
+    pub interval_hours: u32,
+    /// Snapshots kept, newest first
+    pub keep: u32,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
+
+/// The versions this install has run, so the database can be snapshotted before a
+/// new version migrates it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UpgradeHistory {
+    /// Last version whose backend started; `None` on fresh installs and on installs
+    /// that predate this section
+    pub last_run_version: Option<String>,
+    /// Oldest first
+    pub history: Vec<UpgradeRecord>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradeRecord {
+    /// `None` when the previous version predates the upgrade history
+    pub from: Option<String>,
+    pub to: String,
+    /// Unix timestamp (seconds)
+    pub started_at: u64,
+    /// Unix timestamp (seconds) the outcome was known at
+    pub finished_at: Option<u64>,
+    /// Snapshot of the database before the upgrade, if one could be taken
+    pub snapshot: Option<String>,
+    pub outcome: UpgradeOutcome,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpgradeOutcome {
+    /// The new backend has not started yet
+    Pending,
+    Succeeded,
+    /// The database was rolled back to the snapshot
+    RolledBack,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TraySettings {
+    pub left_click: TrayClickAction,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrayClickAction {
+    #[default]
+    ShowWindow,
+    ShowMenu,
+}
+
+/// Where the backend keeps its data. Unset directories live in `data_dir`, which
+/// itself defaults to the app data directory.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StorageSettings {
+    /// Holds the database, and everything below unless it is moved elsewhere
+    pub data_dir: Option<PathBuf>,
+    pub repositories_dir: Option<PathBuf>,
+    pub volumes_dir: Option<PathBuf>,
+    /// Restic cache, which can grow to several GB per repository
+    pub cache_dir: Option<PathBuf>,
+    /// Directory holding the restic password file
+    pub password_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HealthSettings {
+    /// Seconds between liveness probes once the backend is up
+    pub interval_secs: u64,
+    /// Seconds before a single probe is considered failed
+    pub timeout_secs: u64,
+    /// Consecutive failed probes before the backend is marked degraded
+    pub failure_threshold: u32,
+    /// Consecutive failed probes before a desktop notification is shown
+    pub notify_after_failures: u32,
+    /// Consecutive failed probes before the sidecar is restarted; `None` disables restarts
+    pub restart_after_failures: Option<u32>,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: 15,
+            timeout_secs: 5,
+            failure_threshold: 2,
+            notify_after_failures: 4,
+            restart_after_failures: None,
+        }
+    }
+}
+
+/// Which backend events raise a native desktop notification
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NotificationSettings {
+    pub backup_started: bool,
+    pub backup_success: bool,
+    /// Also covers backups stopped before completion
+    pub backup_warning: bool,
+    pub backup_error: bool,
+    pub mirror_started: bool,
+    pub mirror_success: bool,
+    pub mirror_error: bool,
+    pub volume_status: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            backup_started: true,
+            backup_success: true,
+            backup_warning: true,
+            backup_error: true,
+            mirror_started: true,
+            mirror_success: true,
+            mirror_error: true,
+            volume_status: true,
+        }
+    }
+}
+
+fn config_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(crate::normalize_windows_path)
+        .map_err(|e| e.to_string())
+}
+
+/// Why the settings in use are not the ones in the settings file
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadProblem {
+    /// The file could not be read or is invalid; it was moved to `settings.invalid.json`
+    Invalid(String),
+    /// The file was written by a newer version of the app and is left as it is
+    Newer(String),
+}
+
+/// Load the settings file, falling back to defaults when it is missing or cannot be used
+pub fn load(app: &AppHandle) -> (ShellSettings, Option<LoadProblem>) {
+    match app.path().app_config_dir() {
+        Ok(dir) => load_from(&crate::normalize_windows_path(dir)),
+        Err(e) => {
+            log::warn!("Failed to resolve settings path, using defaults: {}", e);
+            (ShellSettings::default(), None)
+        }
+    }
+}
+
+/// Like `load`, for callers without an `AppHandle` (headless mode)
+pub fn load_from(config_dir: &Path) -> (ShellSettings, Option<LoadProblem>) {
+    let path = config_dir.join(SETTINGS_FILE);
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (ShellSettings::default(), None),
+        Err(e) => return set_aside(config_dir, format!("Failed to read {:?}: {}", path, e)),
+    };
+
+    match parse(&contents) {
+        Ok((settings, None)) => (settings, None),
+        Ok((settings, Some(from_version))) => {
+            // Keep the original around in case the migration got something wrong
+            let backup = config_dir.join(format!("settings.v{}.json", from_version));
+            if let Err(e) = std::fs::write(&backup, &contents) {
+                log::warn!("Failed to back up settings to {:?}: {}", backup, e);
+            }
+            match save_to(config_dir, &settings) {
+                Ok(()) => log::info!("Migrated settings from version {} to {}", from_version, CURRENT_VERSION),
+                Err(e) => log::warn!("Failed to save migrated settings: {}", e),
+            }
+            (settings, None)
+        }
+        Err(e) if is_newer(&contents) => {
+            log::error!("Settings file {:?} cannot be used: {}", path, e);
+            (ShellSettings::default(), Some(LoadProblem::Newer(e)))
+        }
+        Err(e) => set_aside(config_dir, format!("Invalid settings file {:?}: {}", path, e)),
+    }
+}
+
+/// Move an unusable settings file out of the way so that saving the defaults does not destroy it
+fn set_aside(config_dir: &Path, error: String) -> (ShellSettings, Option<LoadProblem>) {
+    let path = config_dir.join(SETTINGS_FILE);
+    let kept = config_dir.join(INVALID_SETTINGS_FILE);
+    match std::fs::rename(&path, &kept) {
+        Ok(()) => log::warn!("{}; moved it to {:?} and using defaults", error, kept),
+        // `save_to` refuses to replace a file it cannot load, so it is still safe where it is
+        Err(e) => log::warn!("{}; using defaults, and failed to move it to {:?}: {}", error, kept, e),
+    }
+    (ShellSettings::default(), Some(LoadProblem::Invalid(error)))
+}
+
+fn is_newer(contents: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(contents)
+        .is_ok_and(|value| schema_version(&value).is_ok_and(|version| version > CURRENT_VERSION))
+}
+
+/// Parse, migrate and validate a settings file. Also returns the version it was
+/// migrated from, if it was.
+pub fn parse(contents: &str) -> Result<(ShellSettings, Option<u32>), String> {
+    let mut value: serde_json::Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+    let from_version = migrate(&mut value)?;
+    let settings: ShellSettings = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    settings.validate()?;
+    Ok((settings, from_version))
+}
+
+/// Bring a settings file up to `CURRENT_VERSION`, one version at a time
+fn migrate(value: &mut serde_json::Value) -> Result<Option<u32>, String> {
+    let original = schema_version(value)?;
+    let object = value.as_object_mut().ok_or("not a JSON object")?;
+
+    if original > CURRENT_VERSION {
+        return Err(format!(
+            "written by a newer version of the app (schema {}, this one reads up to {})",
+            original, CURRENT_VERSION
+        ));
+    }
+    if original == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    for version in original..CURRENT_VERSION {
+        match version {
+            // 2 added logging, window and tray sections and sidecar.port and
+            // sidecar.startupTimeoutSecs, all of which have defaults
+            1 => {}
+            _ => return Err(format!("no migration from settings version {}", version)),
+        }
+    }
+
+    object.insert("version".to_string(), CURRENT_VERSION.into());
+    Ok(Some(original))
+}
+
+fn schema_version(value: &serde_json::Value) -> Result<u32, String> {
+    // Version 1 files predate the field
+    let Some(version) = value.get("version") else {
+        return Ok(1);
+    };
+    version
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|&v| v > 0)
+        .ok_or_else(|| format!("invalid settings version {}", version))
+}
+
+pub fn save(app: &AppHandle, settings: &ShellSettings) -> Result<(), String> {
+    save_to(&config_dir(app)?, settings)
+}
+
+pub fn save_to(config_dir: &Path, settings: &ShellSettings) -> Result<(), String> {
+    std::fs::create_dir_all(config_dir).map_err(|e| e.to_string())?;
+
+    let path = config_dir.join(SETTINGS_FILE);
+    check_replaceable(&path)?;
+    let contents = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    std::fs::write(&path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Refuse to replace a settings file this version cannot load, so it is never lost to defaults
+fn check_replaceable(path: &Path) -> Result<(), String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to read {:?}, so it is not overwritten: {}", path, e)),
+    };
+    parse(&contents)
+        .map(|_| ())
+        .map_err(|e| format!("{:?} cannot be loaded by this version, so it is not overwritten: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_settings(dir: &Path, contents: &str) {
+        std::fs::write(dir.join(SETTINGS_FILE), contents).unwrap();
+    }
+
+    #[test]
+    fn missing_file_loads_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(load_from(dir.path()), (ShellSettings::default(), None));
+    }
+
+    #[test]
+    fn saved_settings_load_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut settings = ShellSettings::default();
+        settings.storage.data_dir = Some(PathBuf::from("/srv/backups"));
+
+        save_to(dir.path(), &settings).unwrap();
+
+        assert_eq!(load_from(dir.path()), (settings, None));
+    }
+
+    #[test]
+    fn invalid_file_is_moved_aside_and_not_saved_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = r#"{"version": 1, "storage": {"dataDir": "/srv/backups"}, "health": "#;
+        write_settings(dir.path(), contents);
+
+        let (settings, problem) = load_from(dir.path());
+
+        assert_eq!(settings, ShellSettings::default());
+        assert!(matches!(problem, Some(LoadProblem::Invalid(_))));
+        assert!(!dir.path().join(SETTINGS_FILE).exists());
+        assert_eq!(std::fs::read_to_string(dir.path().join(INVALID_SETTINGS_FILE)).unwrap(), contents);
+
+        save_to(dir.path(), &settings).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join(INVALID_SETTINGS_FILE)).unwrap(), contents);
+    }
+
+    #[test]
+    fn values_failing_validation_are_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = format!(r#"{{"version": {}, "health": {{"intervalSecs": 0}}}}"#, CURRENT_VERSION);
+        write_settings(dir.path(), &contents);
+
+        let (_, problem) = load_from(dir.path());
+
+        assert!(matches!(problem, Some(LoadProblem::Invalid(e)) if e.contains("health.intervalSecs")));
+        assert!(dir.path().join(INVALID_SETTINGS_FILE).exists());
+    }
+
+    #[test]
+    fn newer_file_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = format!(r#"{{"version": {}, "storage": {{"dataDir": "/srv/backups"}}}}"#, CURRENT_VERSION + 1);
+        write_settings(dir.path(), &contents);
+
+        let (settings, problem) = load_from(dir.path());
+
+        assert_eq!(settings, ShellSettings::default());
+        assert!(matches!(problem, Some(LoadProblem::Newer(_))));
+        assert!(!dir.path().join(INVALID_SETTINGS_FILE).exists());
+        assert!(save_to(dir.path(), &settings).is_err());
+        assert_eq!(std::fs::read_to_string(dir.path().join(SETTINGS_FILE)).unwrap(), contents);
+    }
+
+    #[test]
+    fn version_zero_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        write_settings(dir.path(), r#"{"version": 0}"#);
+
+        let (settings, problem) = load_from(dir.path());
+
+        assert_eq!(settings, ShellSettings::default());
+        assert!(matches!(problem, Some(LoadProblem::Invalid(e)) if e.contains("invalid settings version 0")));
+        assert!(dir.path().join(INVALID_SETTINGS_FILE).exists());
+    }
+
+    #[test]
+    fn version_out_of_range_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        // Truncates to 0 as a u32
+        write_settings(dir.path(), &format!(r#"{{"version": {}}}"#, 1u64 << 32));
+
+        let (_, problem) = load_from(dir.path());
+
+        assert!(matches!(problem, Some(LoadProblem::Invalid(_))));
+        assert!(dir.path().join(INVALID_SETTINGS_FILE).exists());
+    }
+
+    #[test]
+    fn version_above_current_is_newer() {
+        let dir = tempfile::tempdir().unwrap();
+        write_settings(dir.path(), &format!(r#"{{"version": {}}}"#, u32::MAX));
+
+        let (settings, problem) = load_from(dir.path());
+
+        assert_eq!(settings, ShellSettings::default());
+        assert!(matches!(problem, Some(LoadProblem::Newer(_))));
+        assert!(!dir.path().join(INVALID_SETTINGS_FILE).exists());
+    }
+
+    #[test]
+    fn older_file_is_migrated_and_backed_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = r#"{"storage": {"dataDir": "/srv/backups"}}"#;
+        write_settings(dir.path(), contents);
+
+        let (settings, problem) = load_from(dir.path());
+
+        assert_eq!(problem, None);
+        assert_eq!(settings.version, CURRENT_VERSION);
+        assert_eq!(settings.storage.data_dir, Some(PathBuf::from("/srv/backups")));
+        assert_eq!(std::fs::read_to_string(dir.path().join("settings.v1.json")).unwrap(), contents);
+        assert_eq!(load_from(dir.path()), (settings, None));
+    }
+}
@@ -0,0 +1,492 @@
+//! The sidecar process as seen from outside the shell that started it.
+//!
+//! `sidecar.pid` in the data directory records the running sidecar, so that a
+//! shell started after a crash finds the one its predecessor left behind before
+//! spawning a second backend against the same database. An orphan cannot be
+//! adopted, since its endpoint and tokens died with the shell that generated
+//! them, so it is stopped instead.
+//!
+//! Where the platform allows it, the sidecar is also tied to the shell's lifetime
+//! so that orphans are rare in the first place: a parent-death signal on Linux,
+//! a job object on Windows. Elsewhere the backend stops when its stdin closes.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use crate::instance::is_process_alive;
+use crate::storage::StoragePaths;
+
+const LOCK_FILE: &str = "sidecar.pid";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a force-killed orphan may take to go away
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SidecarLock {
+    pid: u32,
+    executable: PathBuf,
+    shell_pid: u32,
+    #[serde(default)]
+    shell_executable: Option<PathBuf>,
+    started_at: u64,
+}
+
+fn lock_path(paths: &StoragePaths) -> PathBuf {
+    paths.data_dir.join(LOCK_FILE)
+}
+
+/// Have the sidecar about to be spawned by `command` go away with the shell
+#[cfg(unix)]
+pub fn tie_to_shell(command: &mut Command) -> &mut Command {
+    use std::os::unix::process::CommandExt;
+
+    // Its own group, so that an orphan is killed together with the restic processes it started
+    command.process_group(0);
+
+    #[cfg(target_os = "linux")]
+    {
+        let shell = std::process::id();
+        // Only async-signal-safe calls between fork and exec. The signal is sent when
+        // the spawning thread exits; sidecars are spawned from the runtime's workers or
+        // the main thread, which live as long as the shell.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // The shell may have died before the signal was armed
+                if libc::getppid() as u32 != shell {
+                    return Err(std::io::Error::from_raw_os_error(libc::ESRCH));
+                }
+                Ok(())
+            });
+        }
+    }
+
+    command
+}
+
+#[cfg(windows)]
+pub fn tie_to_shell(command: &mut Command) -> &mut Command {
+    command
+}
+
+/// Put the spawned sidecar in a job that the system kills when the shell exits,
+/// however it exits. Processes the sidecar starts later join the job too.
+#[cfg(windows)]
+pub fn attach(child: &Child) {
+    use std::os::windows::io::AsRawHandle;
+    use std::sync::OnceLock;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+        JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+
+    // Never closed by the shell: the system closes it on exit, which is what kills the job
+    static JOB: OnceLock<Option<usize>> = OnceLock::new();
+
+    let job = JOB.get_or_init(|| unsafe {
+        let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+        if job.is_null() {
+            log::warn!("Failed to create the sidecar job object: {}", std::io::Error::last_os_error());
+            return None;
+        }
+
+        let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        let configured = SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &limits as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        );
+        if configured == 0 {
+            log::warn!("Failed to configure the sidecar job object: {}", std::io::Error::last_os_error());
+            CloseHandle(job);
+            return None;
+        }
+        Some(job as usize)
+    });
+
+    let Some(job) = job else {
+        return;
+    };
+    if unsafe { AssignProcessToJobObject(*job as HANDLE, child.as_raw_handle() as HANDLE) } == 0 {
+        log::warn!(
+            "Failed to tie the sidecar to the shell, it may outlive a crash: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(unix)]
+pub fn attach(_child: &Child) {}
+
+/// Record the sidecar just spawned with `paths`
+pub fn record(paths: &StoragePaths, child: &Child, launched: &Path) {
+    let pid = child.id();
+    let shell_pid = std::process::id();
+    let lock = SidecarLock {
+        pid,
+        // `bun` is launched by name, so prefer what actually runs
+        executable: executable_of(pid).unwrap_or_else(|| launched.to_path_buf()),
+        shell_pid,
+        shell_executable: executable_of(shell_pid).or_else(|| std::env::current_exe().ok()),
+        started_at: crate::unix_now(),
+    };
+
+    if let Err(e) = write(paths, &lock) {
+        log::warn!("Failed to record the sidecar process: {}", e);
+    }
+}
+
+fn write(paths: &StoragePaths, lock: &SidecarLock) -> Result<(), String> {
+    let path = lock_path(paths);
+    let temp = path.with_extension("pid.tmp");
+    let contents = serde_json::to_vec_pretty(lock).map_err(|e| e.to_string())?;
+
+    std::fs::write(&temp, contents).map_err(|e| format!("Failed to write {:?}: {}", temp, e))?;
+    std::fs::rename(&temp, &path).map_err(|e| format!("Failed to replace {:?}: {}", path, e))
+}
+
+/// Forget the sidecar of `paths` once it has been stopped
+pub fn remove(paths: &StoragePaths) {
+    let path = lock_path(paths);
+    match std::fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log::warn!("Failed to remove {:?}: {}", path, e),
+    }
+}
+
+fn read(paths: &StoragePaths) -> Result<Option<SidecarLock>, String> {
+    let path = lock_path(paths);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+
+    match serde_json::from_str(&contents) {
+        Ok(lock) => Ok(Some(lock)),
+        Err(e) => {
+            log::warn!("Ignoring the invalid sidecar lock {:?}: {}", path, e);
+            Ok(None)
+        }
+    }
+}
+
+/// Deal with the sidecar recorded for `paths` before another one is spawned. One
+/// that is gone, or whose pid now belongs to another program, is forgotten; one
+/// whose shell is still running is left alone and fails the start; an orphan is
+/// asked to shut down, given `grace` to do so, then killed.
+pub fn recover(paths: &StoragePaths, grace: Duration) -> Result<(), String> {
+    let Some(lock) = read(paths)? else {
+        return Ok(());
+    };
+
+    match executable_of(lock.pid) {
+        None => log::info!("The sidecar of a previous run (process {}) is no longer running", lock.pid),
+        Some(executable) if !same_executable(&executable, &lock.executable) => log::info!(
+            "Process {} is now {:?}, not the sidecar of a previous run, leaving it alone",
+            lock.pid,
+            executable
+        ),
+        Some(_) if is_owned_by_other_shell(&lock) => {
+            return Err(format!(
+                "Another instance of the application (process {}) runs the backend of {:?}. \
+                 Close it before starting this one.",
+                lock.shell_pid, paths.data_dir
+            ));
+        }
+        Some(_) => {
+            log::warn!(
+                "The sidecar of a previous run (process {}, started at {}) is still running, stopping it",
+                lock.pid,
+                lock.started_at
+            );
+            terminate(lock.pid, grace)?;
+            log::info!("Stopped the orphaned sidecar (process {})", lock.pid);
+        }
+    }
+
+    remove(paths);
+    Ok(())
+}
+
+fn is_owned_by_other_shell(lock: &SidecarLock) -> bool {
+    if lock.shell_pid == std::process::id() {
+        return false;
+    }
+    match (&lock.shell_executable, executable_of(lock.shell_pid)) {
+        (Some(recorded), Some(running)) => same_executable(recorded, &running),
+        _ => false,
+    }
+}
+
+/// Ask the orphan to shut down like `terminate_sidecar` does, then kill it along
+/// with whatever it started
+#[cfg(unix)]
+fn terminate(pid: u32, grace: Duration) -> Result<(), String> {
+    let target = pid as libc::pid_t;
+    // Sidecars spawned by older versions are not the leaders of their own group
+    let group = unsafe { libc::getpgid(target) } == target;
+
+    if unsafe { libc::kill(target, libc::SIGTERM) } != 0 {
+        return Err(format!(
+            "Failed to stop the sidecar of a previous run (process {}): {}",
+            pid,
+            std::io::Error::last_os_error()
+        ));
+    }
+    if wait_for_exit(pid, grace) {
+        return Ok(());
+    }
+
+    log::warn!("The orphaned sidecar did not shut down within {:?}, killing it", grace);
+    unsafe { libc::kill(if group { -target } else { target }, libc::SIGKILL) };
+    if wait_for_exit(pid, KILL_TIMEOUT) {
+        Ok(())
+    } else {
+        Err(format!("The sidecar of a previous run (process {}) could not be stopped", pid))
+    }
+}
+
+/// Its stdin closed with the shell, which already asked it to shut down; give it
+/// `grace` to finish, then kill it
+#[cfg(windows)]
+fn terminate(pid: u32, grace: Duration) -> Result<(), String> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+    if wait_for_exit(pid, grace) {
+        return Ok(());
+    }
+
+    log::warn!("The orphaned sidecar did not shut down within {:?}, killing it", grace);
+    unsafe {
+        let handle = OpenProcess(PROCESS_TERMINATE, 0, pid);
+        if !handle.is_null() {
+            TerminateProcess(handle, 1);
+            CloseHandle(handle);
+        }
+    }
+    if wait_for_exit(pid, KILL_TIMEOUT) {
+        Ok(())
+    } else {
+        Err(format!("The sidecar of a previous run (process {}) could not be stopped", pid))
+    }
+}
+
+fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let started = Instant::now();
+    while is_process_alive(pid) {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    true
+}
+
+fn same_executable(a: &Path, b: &Path) -> bool {
+    let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let (a, b) = (canonical(a), canonical(b));
+    if cfg!(windows) {
+        a.to_string_lossy().eq_ignore_ascii_case(&b.to_string_lossy())
+    } else {
+        a == b
+    }
+}
+
+/// The executable of a running process, if it can be found out
+fn executable_of(pid: u32) -> Option<PathBuf> {
+    if !is_process_alive(pid) {
+        return None;
+    }
+    process_executable(pid)
+}
+
+#[cfg(target_os = "linux")]
+fn process_executable(pid: u32) -> Option<PathBuf> {
+    let path = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    // A binary replaced by an upgrade while it ran
+    match path.to_str().and_then(|path| path.strip_suffix(" (deleted)")) {
+        Some(original) => Some(PathBuf::from(original)),
+        None => Some(path),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn process_executable(pid: u32) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let len = unsafe { libc::proc_pidpath(pid as libc::c_int, buffer.as_mut_ptr().cast(), buffer.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(&buffer[..len as usize])))
+}
+
+#[cfg(windows)]
+fn process_executable(pid: u32) -> Option<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return None;
+        }
+
+        let mut buffer = vec![0u16; 32 * 1024];
+        let mut len = buffer.len() as u32;
+        let queried = QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &mut len) != 0;
+        CloseHandle(handle);
+        queried.then(|| PathBuf::from(std::ffi::OsString::from_wide(&buffer[..len as usize])))
+    }
+}
+
+/// Without a way to check what runs under a pid, a recorded one is never killed
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn process_executable(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+// They start real processes and read their executables from /proc
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    fn test_paths() -> (tempfile::TempDir, StoragePaths) {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_path_buf();
+        let paths = StoragePaths {
+            database: data_dir.join("c3i-backup-one.db"),
+            repositories_dir: data_dir.join("repositories"),
+            volumes_dir: data_dir.join("volumes"),
+            cache_dir: data_dir.join("cache"),
+            password_file: data_dir.join("restic-pass"),
+            data_dir,
+        };
+        (dir, paths)
+    }
+
+    fn lock(pid: u32, executable: PathBuf, shell_pid: u32, shell_executable: Option<PathBuf>) -> SidecarLock {
+        SidecarLock {
+            pid,
+            executable,
+            shell_pid,
+            shell_executable,
+            started_at: crate::unix_now(),
+        }
+    }
+
+    /// A process that runs until killed, reaped in the background so that it is gone once it exits
+    fn spawn_sleeper() -> (u32, PathBuf) {
+        let mut child = Command::new("sleep").arg("30").stdin(Stdio::null()).spawn().unwrap();
+        let pid = child.id();
+        std::thread::spawn(move || child.wait());
+        (pid, executable_of(pid).unwrap())
+    }
+
+    fn dead_pid() -> u32 {
+        let mut child = Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        child.id()
+    }
+
+    fn kill(pid: u32) {
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+        wait_for_exit(pid, KILL_TIMEOUT);
+    }
+
+    #[test]
+    fn nothing_recorded_is_nothing_to_do() {
+        let (_dir, paths) = test_paths();
+
+        recover(&paths, Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn a_sidecar_that_is_gone_is_forgotten() {
+        let (_dir, paths) = test_paths();
+        write(&paths, &lock(dead_pid(), PathBuf::from("/opt/c3i/sidecar"), dead_pid(), None)).unwrap();
+
+        recover(&paths, Duration::from_secs(1)).unwrap();
+
+        assert!(!lock_path(&paths).exists());
+    }
+
+    #[test]
+    fn a_reused_pid_is_left_alone() {
+        let (_dir, paths) = test_paths();
+        let (pid, _) = spawn_sleeper();
+        write(&paths, &lock(pid, PathBuf::from("/opt/c3i/sidecar"), dead_pid(), None)).unwrap();
+
+        recover(&paths, Duration::from_secs(1)).unwrap();
+
+        assert!(is_process_alive(pid));
+        assert!(!lock_path(&paths).exists());
+        kill(pid);
+    }
+
+    #[test]
+    fn a_sidecar_owned_by_another_running_shell_fails_the_start() {
+        let (_dir, paths) = test_paths();
+        let (pid, executable) = spawn_sleeper();
+        let (shell_pid, shell_executable) = spawn_sleeper();
+        write(&paths, &lock(pid, executable, shell_pid, Some(shell_executable))).unwrap();
+
+        let error = recover(&paths, Duration::from_secs(1)).unwrap_err();
+
+        assert!(error.contains(&format!("process {}", shell_pid)), "{}", error);
+        assert!(is_process_alive(pid));
+        assert!(lock_path(&paths).exists());
+        kill(pid);
+        kill(shell_pid);
+    }
+
+    #[test]
+    fn a_pid_reused_by_another_program_is_not_a_running_shell() {
+        let (_dir, paths) = test_paths();
+        let (pid, executable) = spawn_sleeper();
+        let (shell_pid, _) = spawn_sleeper();
+        write(&paths, &lock(pid, executable, shell_pid, Some(PathBuf::from("/opt/c3i/shell")))).unwrap();
+
+        recover(&paths, Duration::from_secs(5)).unwrap();
+
+        assert!(!is_process_alive(pid));
+        assert!(is_process_alive(shell_pid));
+        kill(shell_pid);
+    }
+
+    #[test]
+    fn an_orphan_is_stopped() {
+        let (_dir, paths) = test_paths();
+        let (pid, executable) = spawn_sleeper();
+        write(&paths, &lock(pid, executable, dead_pid(), Some(PathBuf::from("/opt/c3i/shell")))).unwrap();
+
+        recover(&paths, Duration::from_secs(5)).unwrap();
+
+        assert!(!is_process_alive(pid));
+        assert!(!lock_path(&paths).exists());
+    }
+
+    #[test]
+    fn an_invalid_lock_file_is_ignored() {
+        let (_dir, paths) = test_paths();
+        std::fs::write(lock_path(&paths), "{\"pid\": ").unwrap();
+
+        recover(&paths, Duration::from_secs(1)).unwrap();
+    }
+}
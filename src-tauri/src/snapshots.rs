@@ -0,0 +1,452 @@
+//! Local snapshots of the app's own state, taken on a schedule so that a damaged
+//! or mistakenly edited database can be rolled back without an exported archive.
+//!
+//! Each snapshot is a directory in `<data dir>/snapshots` holding a consistent copy
+//! of the database made with SQLite's online backup API (so the sidecar keeps
+//! running), the shell settings and, when the restic password is kept in a file, a
+//! copy of it. Passwords in the secret store are not written to disk; the snapshot
+//! records a fingerprint instead, so a rollback can tell when the password changed.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::settings::{ShellSettings, SnapshotSettings};
+use crate::storage::{self, with_suffix, StoragePaths};
+use crate::{secrets, state_backup, SidecarHost};
+
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+const MANIFEST_FILE: &str = "snapshot.json";
+const DATABASE_FILE: &str = "c3i-backup-one.db";
+const PASSWORD_FILE: &str = "restic-pass";
+const SETTINGS_FILE: &str = "settings.json";
+const PARTIAL_SUFFIX: &str = ".partial";
+/// How often the scheduler checks whether a snapshot is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How long a snapshot waits for the backend to release a write lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Snapshots taken before upgrades, kept apart from the rotation of the others
+const KEEP_UPGRADE_SNAPSHOTS: usize = 3;
+
+/// Why a snapshot was taken
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SnapshotReason {
+    Scheduled,
+    Manual,
+    /// The state replaced by a rollback
+    BeforeRollback,
+    /// The database of the previous version, before the new one migrates it
+    BeforeUpgrade,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    reason: SnapshotReason,
+    /// Version of the app whose database this is
+    app_version: String,
+    /// Unix timestamp (seconds)
+    created_at: u64,
+    /// Tags of the migrations applied to the database
+    migrations: Vec<String>,
+    /// SHA-256 of the restic password, hex encoded
+    password_fingerprint: Option<String>,
+}
+
+/// A snapshot as the settings page lists it
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    /// Directory name, passed back to `rollback`
+    pub id: String,
+    pub reason: SnapshotReason,
+    pub app_version: String,
+    /// Unix timestamp (seconds)
+    pub created_at: u64,
+    pub size_bytes: u64,
+}
+
+/// A rolled back snapshot, for the caller to apply its settings (the storage
+/// locations, snapshot settings and upgrade history of this machine are kept)
+pub struct RolledBack {
+    pub snapshot: SnapshotInfo,
+    pub settings: Option<ShellSettings>,
+}
+
+fn snapshots_dir(paths: &StoragePaths) -> PathBuf {
+    paths.data_dir.join(SNAPSHOTS_DIR)
+}
+
+/// Take a snapshot whenever the newest one is older than the configured interval
+pub async fn schedule(host: impl SidecarHost) {
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let state = host.app_state();
+        if crate::is_sidecar_stopping(state) {
+            return;
+        }
+        // Restores and rollbacks replace the database meanwhile
+        if crate::is_sidecar_suspended(state) {
+            continue;
+        }
+        let settings = state.settings.lock().map(|s| s.snapshots.clone()).unwrap_or_default();
+        if !settings.enabled {
+            continue;
+        }
+
+        let worker = host.clone();
+        match tauri::async_runtime::spawn_blocking(move || take_if_due(&worker, &settings)).await {
+            Ok(Ok(Some(snapshot))) => log::info!("Took scheduled snapshot {}", snapshot.id),
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => log::warn!("Failed to take a scheduled snapshot: {}", e),
+            Err(e) => log::warn!("Failed to take a scheduled snapshot: {}", e),
+        }
+    }
+}
+
+fn take_if_due(host: &impl SidecarHost, settings: &SnapshotSettings) -> Result<Option<SnapshotInfo>, String> {
+    let newest = list(host)?.into_iter().map(|snapshot| snapshot.created_at).max();
+    let interval = u64::from(settings.interval_hours) * 3600;
+    if newest.is_some_and(|newest| crate::unix_now() < newest + interval) {
+        return Ok(None);
+    }
+
+    let snapshot = take(host, SnapshotReason::Scheduled)?;
+    prune(host, settings.keep)?;
+    Ok(snapshot)
+}
+
+/// Snapshot the current state. `None` when there is no database yet.
+pub fn take(host: &impl SidecarHost, reason: SnapshotReason) -> Result<Option<SnapshotInfo>, String> {
+    take_as(host, reason, env!("CARGO_PKG_VERSION"))
+}
+
+/// Like `take`, for a database last used by `app_version`
+pub fn take_as(
+    host: &impl SidecarHost,
+    reason: SnapshotReason,
+    app_version: &str,
+) -> Result<Option<SnapshotInfo>, String> {
+    let paths = storage::resolve(host)?;
+    if !paths.database.exists() {
+        return Ok(None);
+    }
+
+    let mut manifest = Manifest {
+        reason,
+        app_version: app_version.to_string(),
+        created_at: crate::unix_now(),
+        migrations: Vec::new(),
+        password_fingerprint: secrets::peek(&paths).map(|password| fingerprint(&password)),
+    };
+    let id = format!("snapshot-{}", manifest.created_at);
+    let dir = snapshots_dir(&paths).join(&id);
+    if dir.exists() {
+        return Err(format!("A snapshot was already taken this second ({})", id));
+    }
+
+    // Built next to its final name, so a failure never leaves a partial snapshot listed
+    let partial = with_suffix(&dir, PARTIAL_SUFFIX);
+    let result = write(host, &paths, &partial, &mut manifest).and_then(|()| {
+        std::fs::rename(&partial, &dir).map_err(|e| format!("Failed to write {:?}: {}", dir, e))
+    });
+    if let Err(e) = result {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err(e);
+    }
+
+    Ok(Some(info(&id, &dir, manifest)))
+}
+
+fn write(host: &impl SidecarHost, paths: &StoragePaths, dir: &Path, manifest: &mut Manifest) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+
+    // Never creates the database, and only reads it; a read-only connection could
+    // not open a WAL database whose `-shm` file is missing
+    let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let source = rusqlite::Connection::open_with_flags(&paths.database, flags)
+        .map_err(|e| format!("Failed to open {:?}: {}", paths.database, e))?;
+    source.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+    source
+        .backup(rusqlite::DatabaseName::Main, dir.join(DATABASE_FILE), None)
+        .map_err(|e| format!("Failed to copy the database: {}", e))?;
+
+    // Read from the copy, which cannot change under us
+    let copy = rusqlite::Connection::open_with_flags(dir.join(DATABASE_FILE), flags)
+        .map_err(|e| format!("Failed to open the copied database: {}", e))?;
+    manifest.migrations = state_backup::applied_migrations(host, &copy)?;
+
+    // Already on disk next to the database, so the copy exposes nothing new
+    match std::fs::read_to_string(&paths.password_file) {
+        Ok(password) => secrets::write_password_file(&dir.join(PASSWORD_FILE), &password)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {:?}: {}", paths.password_file, e)),
+    }
+
+    let settings = host.app_state().settings.lock().map_err(|e| e.to_string())?.clone();
+    write_json(&dir.join(SETTINGS_FILE), &settings)?;
+    write_json(&dir.join(MANIFEST_FILE), manifest)
+}
+
+/// Snapshots of the current data directory, newest first
+pub fn list(host: &impl SidecarHost) -> Result<Vec<SnapshotInfo>, String> {
+    let dir = snapshots_dir(&storage::resolve(host)?);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", dir, e)),
+    };
+
+    let mut snapshots: Vec<SnapshotInfo> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let id = entry.file_name().to_string_lossy().to_string();
+            let manifest = read_manifest(&entry.path()).ok()?;
+            Some(info(&id, &entry.path(), manifest))
+        })
+        .collect();
+    snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(snapshots)
+}
+
+/// Delete all but the `keep` newest snapshots, and any left half-written. The
+/// snapshots taken before upgrades are rotated apart, so that a backend failing
+/// to start after an upgrade cannot rotate its own safety net away.
+pub fn prune(host: &impl SidecarHost, keep: u32) -> Result<(), String> {
+    let dir = snapshots_dir(&storage::resolve(host)?);
+
+    for snapshot in expired(list(host)?, keep) {
+        let path = dir.join(&snapshot.id);
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => log::info!("Deleted snapshot {}", snapshot.id),
+            Err(e) => log::warn!("Failed to delete snapshot {:?}: {}", path, e),
+        }
+    }
+
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.filter_map(Result::ok) {
+            if entry.file_name().to_string_lossy().ends_with(PARTIAL_SUFFIX) {
+                let _ = std::fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Snapshots beyond the `keep` newest ones, given newest first
+fn expired(snapshots: Vec<SnapshotInfo>, keep: u32) -> Vec<SnapshotInfo> {
+    let (upgrades, others): (Vec<SnapshotInfo>, Vec<SnapshotInfo>) = snapshots
+        .into_iter()
+        .partition(|snapshot| snapshot.reason == SnapshotReason::BeforeUpgrade);
+    others
+        .into_iter()
+        .skip(keep as usize)
+        .chain(upgrades.into_iter().skip(KEEP_UPGRADE_SNAPSHOTS))
+        .collect()
+}
+
+/// Replace the database (and, when it was copied, the restic password) with those
+/// of snapshot `id`. The sidecar must be stopped; the replaced state is snapshotted
+/// first.
+pub fn rollback(host: &impl SidecarHost, id: &str) -> Result<RolledBack, String> {
+    if !is_valid_id(id) {
+        return Err(format!("Invalid snapshot id {:?}", id));
+    }
+    let paths = storage::resolve(host)?;
+    let dir = snapshots_dir(&paths).join(id);
+    let manifest = read_manifest(&dir).map_err(|e| format!("Snapshot {} cannot be used: {}", id, e))?;
+
+    state_backup::ensure_known_migrations(host, &manifest.migrations, &manifest.app_version)?;
+
+    let password = match std::fs::read_to_string(dir.join(PASSWORD_FILE)) {
+        Ok(password) => Some(password),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to read the restic password of snapshot {}: {}", id, e)),
+    };
+    if password.is_none() {
+        let current = secrets::peek(&paths).map(|password| fingerprint(&password));
+        if manifest.password_fingerprint.is_some() && current != manifest.password_fingerprint {
+            return Err(format!(
+                "Snapshot {} was taken with another restic password, which it does not hold; \
+                 restore an exported archive instead",
+                id
+            ));
+        }
+    }
+
+    let settings = match std::fs::read_to_string(dir.join(SETTINGS_FILE)) {
+        Ok(json) => {
+            let (mut settings, _) = crate::settings::parse(&json)
+                .map_err(|e| format!("The settings in snapshot {} cannot be used: {}", id, e))?;
+            let current = host.app_state().settings.lock().map_err(|e| e.to_string())?.clone();
+            settings.storage = current.storage;
+            settings.snapshots = current.snapshots;
+            settings.upgrades = current.upgrades;
+            Some(settings)
+        }
+        Err(_) => None,
+    };
+
+    if let Some(previous) = take(host, SnapshotReason::BeforeRollback)? {
+        log::info!("Saved the state being rolled back as snapshot {}", previous.id);
+    }
+
+    // A WAL left from the current database would be replayed onto the snapshot
+    for suffix in ["-wal", "-shm"] {
+        let path = with_suffix(&paths.database, suffix);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove {:?}: {}", path, e)),
+        }
+    }
+    let partial = with_suffix(&paths.database, PARTIAL_SUFFIX);
+    std::fs::copy(dir.join(DATABASE_FILE), &partial)
+        .and_then(|_| std::fs::rename(&partial, &paths.database))
+        .map_err(|e| format!("Failed to write {:?}: {}", paths.database, e))?;
+
+    if let Some(password) = password {
+        secrets::replace(&paths, &password)?;
+    }
+
+    log::info!("Rolled back to snapshot {}", id);
+    Ok(RolledBack {
+        snapshot: info(id, &dir, manifest),
+        settings,
+    })
+}
+
+/// Whether `id` names a directory inside the snapshots directory
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn read_manifest(dir: &Path) -> Result<Manifest, String> {
+    let path = dir.join(MANIFEST_FILE);
+    let contents = std::fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_slice(&contents).map_err(|e| format!("Invalid {:?}: {}", path, e))
+}
+
+fn info(id: &str, dir: &Path, manifest: Manifest) -> SnapshotInfo {
+    let size_bytes = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| entry.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0);
+
+    SnapshotInfo {
+        id: id.to_string(),
+        reason: manifest.reason,
+        app_version: manifest.app_version,
+        created_at: manifest.created_at,
+        size_bytes,
+    }
+}
+
+fn fingerprint(password: &str) -> String {
+    Sha256::digest(password.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<(), String> {
+    let contents = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(created_at: u64, reason: SnapshotReason) -> SnapshotInfo {
+        SnapshotInfo {
+            id: format!("snapshot-{}", created_at),
+            reason,
+            app_version: "1.0.0".to_string(),
+            created_at,
+            size_bytes: 0,
+        }
+    }
+
+    fn ids(snapshots: &[SnapshotInfo]) -> Vec<&str> {
+        snapshots.iter().map(|snapshot| snapshot.id.as_str()).collect()
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_snapshots() {
+        let snapshots = vec![
+            snapshot(5, SnapshotReason::Scheduled),
+            snapshot(4, SnapshotReason::Manual),
+            snapshot(3, SnapshotReason::BeforeRollback),
+            snapshot(2, SnapshotReason::Scheduled),
+        ];
+
+        assert_eq!(ids(&expired(snapshots, 2)), ["snapshot-3", "snapshot-2"]);
+    }
+
+    #[test]
+    fn snapshots_before_upgrades_are_rotated_apart() {
+        let mut snapshots: Vec<_> = (10..16).map(|at| snapshot(at, SnapshotReason::Scheduled)).collect();
+        snapshots.extend((1..6).rev().map(|at| snapshot(at, SnapshotReason::BeforeUpgrade)));
+
+        let expired = expired(snapshots, 0);
+
+        assert_eq!(expired.len(), 6 + 5 - KEEP_UPGRADE_SNAPSHOTS);
+        assert!(expired.iter().all(|snapshot| snapshot.created_at >= 10 || snapshot.created_at <= 2));
+    }
+
+    #[test]
+    fn ids_cannot_leave_the_snapshots_directory() {
+        assert!(is_valid_id("snapshot-1700000000"));
+        for id in ["", "..", "../snapshot-1", "snapshot-1/..", "snapshot 1", "C:\\snapshot"] {
+            assert!(!is_valid_id(id), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn fingerprint_is_the_hex_encoded_sha256() {
+        assert_eq!(
+            fingerprint("password"),
+            "5e884898da28047151d0e56f8dc6292773603d0d6aabbdd62a11ef721d1542d8"
+        );
+    }
+
+    #[test]
+    fn manifest_is_read_back_with_the_size_of_the_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = Manifest {
+            reason: SnapshotReason::BeforeUpgrade,
+            app_version: "1.2.0".to_string(),
+            created_at: 1_700_000_000,
+            migrations: vec!["0000_first".to_string()],
+            password_fingerprint: Some(fingerprint("password")),
+        };
+        write_json(&dir.path().join(MANIFEST_FILE), &manifest).unwrap();
+        std::fs::write(dir.path().join(DATABASE_FILE), [0u8; 100]).unwrap();
+
+        let read = read_manifest(dir.path()).unwrap();
+        assert_eq!(read.migrations, ["0000_first"]);
+        assert_eq!(read.password_fingerprint, manifest.password_fingerprint);
+
+        let info = info("snapshot-1700000000", dir.path(), read);
+        assert_eq!(info.reason, SnapshotReason::BeforeUpgrade);
+        assert_eq!(info.app_version, "1.2.0");
+        assert_eq!(info.created_at, 1_700_000_000);
+        let manifest_size = std::fs::metadata(dir.path().join(MANIFEST_FILE)).unwrap().len();
+        assert_eq!(info.size_bytes, 100 + manifest_size);
+    }
+
+    #[test]
+    fn a_missing_or_invalid_manifest_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_manifest(dir.path()).is_err());
+
+        std::fs::write(dir.path().join(MANIFEST_FILE), "{}").unwrap();
+        assert!(read_manifest(dir.path()).is_err_and(|e| e.starts_with("Invalid")));
+    }
+}
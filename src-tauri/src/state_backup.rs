@@ -0,0 +1,482 @@
+//! Passphrase-encrypted archives of the app's own state: the database, the restic
+//! master password and the shell settings. Every repository is encrypted with that
+//! password and every stored credential with keys derived from it, so losing them
+//! loses access to all the backups.
+//!
+//! An archive is a zip inside an age envelope keyed by the passphrase (scrypt). The
+//! restic cache, logs, snapshots, local repositories and volumes are left out. The
+//! sidecar must be stopped while an archive is written or restored.
+
+use age::secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::settings::{self, ShellSettings};
+use crate::storage::{self, with_suffix};
+use crate::{secrets, SidecarHost};
+
+/// Bump when the layout changes; restoring refuses archives from a newer format
+const FORMAT_VERSION: u32 = 1;
+pub const ARCHIVE_EXTENSION: &str = "c3istate";
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const PASSWORD_ENTRY: &str = "restic-password";
+const SETTINGS_ENTRY: &str = "settings.json";
+const DATABASE_ENTRY: &str = "database/c3i-backup-one.db";
+/// SQLite files that make up the database, by suffix. `-shm` is only an index of
+/// the WAL and is rebuilt when missing.
+const DATABASE_SUFFIXES: &[&str] = &["", "-wal"];
+/// Appended to the replaced database files while a restore is in progress
+const ASIDE_SUFFIX: &str = ".before-restore";
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What an archive holds besides the state itself, checked before restoring
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub format: u32,
+    pub app_version: String,
+    /// Unix timestamp (seconds)
+    pub created_at: u64,
+    /// Tags of the migrations applied to the archived database, in order
+    pub migrations: Vec<String>,
+}
+
+/// A restored archive, for the caller to apply the settings (the storage locations
+/// and upgrade history of this machine are kept)
+pub struct Restored {
+    pub manifest: Manifest,
+    pub settings: Option<ShellSettings>,
+    /// Archive of the state that was replaced, if there was one
+    pub previous_state: Option<PathBuf>,
+}
+
+struct Contents {
+    manifest: Manifest,
+    /// By suffix, see `DATABASE_SUFFIXES`
+    database: Vec<(&'static str, Vec<u8>)>,
+    password: String,
+    settings: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+/// A migration listed in drizzle's journal
+#[derive(Deserialize)]
+pub struct JournalEntry {
+    pub tag: String,
+    /// Recorded as `created_at` in the `__drizzle_migrations` table once applied
+    pub when: u64,
+}
+
+/// The bundled database migrations, in order
+pub fn journal(host: &impl SidecarHost) -> Result<Vec<JournalEntry>, String> {
+    let dir = crate::get_migrations_path(host).ok_or("The resources directory could not be resolved")?;
+    let path = dir.join("meta").join("_journal.json");
+    let contents = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let journal: Journal = serde_json::from_str(&contents).map_err(|e| format!("Invalid {:?}: {}", path, e))?;
+    Ok(journal.entries)
+}
+
+/// Tags of the migrations that drizzle has recorded as applied to `database`, in order.
+/// Migrations this version does not bundle come last, so `ensure_known_migrations` refuses them.
+pub fn applied_migrations(host: &impl SidecarHost, database: &rusqlite::Connection) -> Result<Vec<String>, String> {
+    applied_in(journal(host)?, database)
+}
+
+fn applied_in(journal: Vec<JournalEntry>, database: &rusqlite::Connection) -> Result<Vec<String>, String> {
+    let applied: Vec<i64> = match database.prepare("SELECT created_at FROM __drizzle_migrations") {
+        Ok(mut statement) => statement
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(|e| format!("Failed to read the applied migrations: {}", e))?,
+        // Created by the first migration, so a database the backend never migrated lacks it
+        Err(_) => Vec::new(),
+    };
+
+    let unknown = applied
+        .iter()
+        .filter(|when| !journal.iter().any(|entry| entry.when as i64 == **when))
+        .map(|when| format!("unknown-{}", when))
+        .collect::<Vec<_>>();
+    Ok(journal
+        .into_iter()
+        .filter(|entry| applied.contains(&(entry.when as i64)))
+        .map(|entry| entry.tag)
+        .chain(unknown)
+        .collect())
+}
+
+/// Tags of the bundled database migrations, from drizzle's journal
+pub fn bundled_migrations(host: &impl SidecarHost) -> Result<Vec<String>, String> {
+    Ok(journal(host)?.into_iter().map(|entry| entry.tag).collect())
+}
+
+/// Refuse a database copied from version `app_version` with `migrations` applied
+/// when this version does not bundle all of them, since its backend cannot open it
+pub fn ensure_known_migrations(
+    host: &impl SidecarHost,
+    migrations: &[String],
+    app_version: &str,
+) -> Result<(), String> {
+    let bundled = bundled_migrations(host)?;
+    let known = migrations.len() <= bundled.len()
+        && migrations.iter().zip(&bundled).all(|(copied, bundled)| copied == bundled);
+    if !known {
+        return Err(format!(
+            "The database was saved by version {}, and has migrations this version does not know; \
+             update the app to at least that version to restore it",
+            app_version
+        ));
+    }
+    Ok(())
+}
+
+/// Write the state of the data directory of `host` to `output`
+pub fn export(host: &impl SidecarHost, output: &Path, passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("The passphrase must have at least {} characters", MIN_PASSPHRASE_LEN));
+    }
+
+    let paths = storage::resolve(host)?;
+    if !paths.database.exists() {
+        return Err(format!("There is no database at {:?} to export", paths.database));
+    }
+    let password = secrets::peek(&paths).ok_or("The restic password could not be read")?;
+    let settings = host.app_state().settings.lock().map_err(|e| e.to_string())?.clone();
+
+    // Never creates the database, and only reads it; a read-only connection could
+    // not open a WAL database whose `-shm` file is missing
+    let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let database = rusqlite::Connection::open_with_flags(&paths.database, flags)
+        .map_err(|e| format!("Failed to open {:?}: {}", paths.database, e))?;
+    database.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+
+    let manifest = Manifest {
+        format: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: crate::unix_now(),
+        migrations: applied_migrations(host, &database)?,
+    };
+    drop(database);
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut add = |name: &str, contents: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(contents)
+            .map_err(|e| format!("Failed to add {} to the archive: {}", name, e))
+    };
+
+    add(MANIFEST_ENTRY, &to_json(&manifest)?)?;
+    add(PASSWORD_ENTRY, password.as_bytes())?;
+    add(SETTINGS_ENTRY, &to_json(&settings)?)?;
+    for suffix in DATABASE_SUFFIXES {
+        let path = with_suffix(&paths.database, suffix);
+        match std::fs::read(&path) {
+            Ok(contents) => add(&database_entry(suffix), &contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !suffix.is_empty() => {}
+            Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+        }
+    }
+    let archive = zip.finish().map_err(|e| e.to_string())?.into_inner();
+
+    // Written next to the target first, so a failure never leaves half an archive behind
+    let partial = with_suffix(output, ".partial");
+    let result = encrypt(&archive, passphrase, &partial).and_then(|()| {
+        std::fs::rename(&partial, output).map_err(|e| format!("Failed to write {:?}: {}", output, e))
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Replace the state of the data directory of `host` with the one in `archive`,
+/// after checking that this version of the app can use it. The replaced state is
+/// first archived in the data directory with the same passphrase.
+pub fn restore(host: &impl SidecarHost, archive: &Path, passphrase: &str) -> Result<Restored, String> {
+    let contents = read(archive, passphrase)?;
+    let manifest = contents.manifest;
+
+    if manifest.format > FORMAT_VERSION {
+        return Err(format!(
+            "The archive was made by version {} in a newer format; update the app to restore it",
+            manifest.app_version
+        ));
+    }
+    ensure_known_migrations(host, &manifest.migrations, &manifest.app_version)?;
+
+    let restored_settings = match &contents.settings {
+        Some(json) => {
+            let (mut restored, _) =
+                settings::parse(json).map_err(|e| format!("The settings in the archive cannot be used: {}", e))?;
+            let current = host.app_state().settings.lock().map_err(|e| e.to_string())?.clone();
+            restored.storage = current.storage;
+            restored.upgrades = current.upgrades;
+            Some(restored)
+        }
+        None => None,
+    };
+
+    let paths = storage::resolve(host)?;
+    storage::ensure_writable(&paths.data_dir)?;
+
+    let previous_state = if paths.database.exists() {
+        let file_name = format!("state-before-restore-{}.{}", crate::unix_now(), ARCHIVE_EXTENSION);
+        let backup = paths.data_dir.join(file_name);
+        export(host, &backup, passphrase)
+            .map_err(|e| format!("Failed to save the current state before restoring: {}", e))?;
+        log::info!("Saved the state being replaced to {:?}", backup);
+        Some(backup)
+    } else {
+        None
+    };
+
+    // The replaced database stays aside until the password is in place too, so that
+    // a failure leaves the database and the password that go together
+    let aside = set_database_aside(&paths.database)?;
+    let result = write_database(&paths.database, &contents.database)
+        .and_then(|()| secrets::replace(&paths, &contents.password));
+    if let Err(e) = result {
+        put_database_back(&paths.database, &aside);
+        return Err(e);
+    }
+    for (_, path) in aside {
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("Failed to remove {:?}: {}", path, e);
+        }
+    }
+
+    log::info!(
+        "Restored the app state archived by version {} at {}",
+        manifest.app_version,
+        manifest.created_at
+    );
+    Ok(Restored {
+        manifest,
+        settings: restored_settings,
+        previous_state,
+    })
+}
+
+/// Move the database files out of the way, including a WAL that would otherwise be
+/// replayed onto the restored database. Returns each moved file and where it went.
+fn set_database_aside(database: &Path) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut aside = Vec::new();
+    for suffix in ["", "-wal", "-shm"] {
+        let path = with_suffix(database, suffix);
+        let target = with_suffix(&path, ASIDE_SUFFIX);
+        match std::fs::rename(&path, &target) {
+            Ok(()) => aside.push((path, target)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                put_database_back(database, &aside);
+                return Err(format!("Failed to move {:?} aside: {}", path, e));
+            }
+        }
+    }
+    Ok(aside)
+}
+
+/// Undo `set_database_aside`, removing whatever was restored in between
+fn put_database_back(database: &Path, aside: &[(PathBuf, PathBuf)]) {
+    for suffix in ["", "-wal", "-shm"] {
+        let path = with_suffix(database, suffix);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::error!("Failed to remove the restored {:?}: {}", path, e),
+        }
+    }
+    for (path, moved) in aside {
+        if let Err(e) = std::fs::rename(moved, path) {
+            log::error!("Failed to put {:?} back to {:?}: {}", moved, path, e);
+        }
+    }
+}
+
+fn write_database(database: &Path, files: &[(&'static str, Vec<u8>)]) -> Result<(), String> {
+    for (suffix, data) in files {
+        let path = with_suffix(database, suffix);
+        let partial = with_suffix(&path, ".partial");
+        std::fs::write(&partial, data)
+            .and_then(|()| std::fs::rename(&partial, &path))
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+fn read(archive: &Path, passphrase: &str) -> Result<Contents, String> {
+    let zipped = decrypt(archive, passphrase)?;
+    let mut zip = zip::ZipArchive::new(Cursor::new(zipped)).map_err(|e| format!("Not an app state archive: {}", e))?;
+
+    let mut entry = |name: &str| -> Result<Option<Vec<u8>>, String> {
+        let mut file = match zip.by_name(name) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read {} from the archive: {}", name, e))?;
+        Ok(Some(contents))
+    };
+
+    let manifest = entry(MANIFEST_ENTRY)?.ok_or("Not an app state archive: it has no manifest")?;
+    let manifest: Manifest = serde_json::from_slice(&manifest).map_err(|e| format!("Invalid manifest: {}", e))?;
+
+    let password = entry(PASSWORD_ENTRY)?.ok_or("The archive has no restic password")?;
+    let password = String::from_utf8(password).map_err(|_| "The restic password in the archive is not text")?;
+
+    let mut database = Vec::new();
+    for suffix in DATABASE_SUFFIXES {
+        if let Some(data) = entry(&database_entry(suffix))? {
+            database.push((*suffix, data));
+        }
+    }
+    if !database.iter().any(|(suffix, _)| suffix.is_empty()) {
+        return Err("The archive has no database".to_string());
+    }
+
+    let settings = entry(SETTINGS_ENTRY)?
+        .map(String::from_utf8)
+        .transpose()
+        .map_err(|_| "The settings in the archive are not text")?;
+
+    Ok(Contents {
+        manifest,
+        database,
+        password,
+        settings,
+    })
+}
+
+fn encrypt(data: &[u8], passphrase: &str, output: &Path) -> Result<(), String> {
+    let file = std::fs::File::create(output).map_err(|e| format!("Failed to create {:?}: {}", output, e))?;
+    let encryptor = age::Encryptor::with_user_passphrase(SecretString::from(passphrase));
+    let mut writer = encryptor.wrap_output(file).map_err(|e| e.to_string())?;
+    writer.write_all(data).map_err(|e| e.to_string())?;
+    let file = writer.finish().map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())
+}
+
+fn decrypt(archive: &Path, passphrase: &str) -> Result<Vec<u8>, String> {
+    let file = std::fs::File::open(archive).map_err(|e| format!("Failed to open {:?}: {}", archive, e))?;
+    let decryptor = match age::Decryptor::new_buffered(std::io::BufReader::new(file)) {
+        Ok(decryptor) if decryptor.is_scrypt() => decryptor,
+        Ok(_) | Err(age::DecryptError::InvalidHeader | age::DecryptError::UnknownFormat) => {
+            return Err(format!("{:?} is not an app state archive", archive));
+        }
+        Err(e) => return Err(format!("Failed to read {:?}: {}", archive, e)),
+    };
+
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase));
+    let mut reader = decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| match e {
+            age::DecryptError::DecryptionFailed | age::DecryptError::NoMatchingKeys => {
+                "Wrong passphrase".to_string()
+            }
+            e => e.to_string(),
+        })?;
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(|e| format!("The archive is damaged: {}", e))?;
+    Ok(data)
+}
+
+fn database_entry(suffix: &str) -> String {
+    format!("{}{}", DATABASE_ENTRY, suffix)
+}
+
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>, String> {
+    serde_json::to_vec_pretty(value).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal_entry(tag: &str, when: u64) -> JournalEntry {
+        JournalEntry {
+            tag: tag.to_string(),
+            when,
+        }
+    }
+
+    #[test]
+    fn applied_migrations_follow_the_journal_with_unknown_ones_last() {
+        let database = rusqlite::Connection::open_in_memory().unwrap();
+        database
+            .execute_batch(concat!(
+                "CREATE TABLE __drizzle_migrations (id INTEGER PRIMARY KEY, hash TEXT, created_at NUMERIC);",
+                "INSERT INTO __drizzle_migrations (hash, created_at) VALUES ('a', 100), ('c', 900), ('b', 200);",
+            ))
+            .unwrap();
+        let journal = vec![
+            journal_entry("0000_first", 100),
+            journal_entry("0001_second", 200),
+            journal_entry("0002_third", 300),
+        ];
+
+        let applied = applied_in(journal, &database).unwrap();
+
+        assert_eq!(applied, ["0000_first", "0001_second", "unknown-900"]);
+    }
+
+    #[test]
+    fn a_database_never_migrated_has_no_applied_migrations() {
+        let database = rusqlite::Connection::open_in_memory().unwrap();
+
+        assert!(applied_in(vec![journal_entry("0000_first", 100)], &database).unwrap().is_empty());
+    }
+
+    #[test]
+    fn database_set_aside_is_put_back_over_a_restored_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("c3i-backup-one.db");
+        std::fs::write(&database, b"old").unwrap();
+        std::fs::write(with_suffix(&database, "-wal"), b"old wal").unwrap();
+
+        let aside = set_database_aside(&database).unwrap();
+        assert_eq!(aside.len(), 2);
+        assert!(!database.exists());
+        assert!(!with_suffix(&database, "-wal").exists());
+
+        write_database(&database, &[("", b"new".to_vec())]).unwrap();
+        std::fs::write(with_suffix(&database, "-shm"), b"new shm").unwrap();
+        put_database_back(&database, &aside);
+
+        assert_eq!(std::fs::read(&database).unwrap(), b"old");
+        assert_eq!(std::fs::read(with_suffix(&database, "-wal")).unwrap(), b"old wal");
+        assert!(!with_suffix(&database, "-shm").exists());
+        assert!(!with_suffix(&database, ASIDE_SUFFIX).exists());
+    }
+
+    #[test]
+    fn archives_decrypt_only_with_their_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("state.c3istate");
+
+        encrypt(b"app state", "correct horse", &archive).unwrap();
+
+        assert_eq!(decrypt(&archive, "correct horse").unwrap(), b"app state");
+        assert!(decrypt(&archive, "wrong horse").is_err());
+    }
+
+    #[test]
+    fn other_files_are_not_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "not encrypted").unwrap();
+
+        assert!(decrypt(&file, "correct horse").unwrap_err().contains("is not an app state archive"));
+    }
+}
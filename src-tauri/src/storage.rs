@@ -0,0 +1,454 @@
+//! Where the backend keeps its database, repositories, volumes, restic cache and
+//! password file, and moving that data when a location changes.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{secrets, snapshots};
+use crate::settings::StorageSettings;
+use crate::SidecarHost;
+
+const DATABASE_FILE: &str = "c3i-backup-one.db";
+const PASSWORD_FILE: &str = "restic-pass";
+const WRITE_PROBE_FILE: &str = ".c3i-backup-one-write-test";
+
+/// One of the locations that can be moved from the settings page
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageLocation {
+    Data,
+    Repositories,
+    Volumes,
+    Cache,
+    Password,
+}
+
+impl StorageLocation {
+    pub const ALL: [StorageLocation; 5] = [
+        StorageLocation::Data,
+        StorageLocation::Repositories,
+        StorageLocation::Volumes,
+        StorageLocation::Cache,
+        StorageLocation::Password,
+    ];
+}
+
+/// The paths handed to the sidecar, after applying `--data-dir` and the settings
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoragePaths {
+    pub data_dir: PathBuf,
+    pub database: PathBuf,
+    pub repositories_dir: PathBuf,
+    pub volumes_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub password_file: PathBuf,
+}
+
+impl StorageSettings {
+    pub fn set(&mut self, location: StorageLocation, dir: Option<PathBuf>) {
+        let slot = match location {
+            StorageLocation::Data => &mut self.data_dir,
+            StorageLocation::Repositories => &mut self.repositories_dir,
+            StorageLocation::Volumes => &mut self.volumes_dir,
+            StorageLocation::Cache => &mut self.cache_dir,
+            StorageLocation::Password => &mut self.password_dir,
+        };
+        *slot = dir;
+    }
+}
+
+impl StoragePaths {
+    /// Directory the given location resolves to
+    pub fn dir(&self, location: StorageLocation) -> &Path {
+        match location {
+            StorageLocation::Data => &self.data_dir,
+            StorageLocation::Repositories => &self.repositories_dir,
+            StorageLocation::Volumes => &self.volumes_dir,
+            StorageLocation::Cache => &self.cache_dir,
+            StorageLocation::Password => self.password_file.parent().unwrap_or(&self.data_dir),
+        }
+    }
+
+    /// Existing files and directories that change place between `self` and `to`
+    fn moves(&self, to: &StoragePaths) -> Vec<(PathBuf, PathBuf)> {
+        let mut moves = Vec::new();
+
+        // SQLite keeps uncommitted pages next to the database in WAL mode
+        for suffix in ["", "-wal", "-shm"] {
+            moves.push((with_suffix(&self.database, suffix), with_suffix(&to.database, suffix)));
+        }
+        moves.push((self.repositories_dir.clone(), to.repositories_dir.clone()));
+        moves.push((self.volumes_dir.clone(), to.volumes_dir.clone()));
+        moves.push((self.cache_dir.clone(), to.cache_dir.clone()));
+        moves.push((self.password_file.clone(), to.password_file.clone()));
+        moves.push((
+            self.data_dir.join(secrets::KEYRING_MARKER_FILE),
+            to.data_dir.join(secrets::KEYRING_MARKER_FILE),
+        ));
+        moves.push((
+            self.data_dir.join(snapshots::SNAPSHOTS_DIR),
+            to.data_dir.join(snapshots::SNAPSHOTS_DIR),
+        ));
+
+        moves.retain(|(from, to)| from != to && from.exists());
+        moves
+    }
+}
+
+pub fn resolve(host: &impl SidecarHost) -> Result<StoragePaths, String> {
+    let settings = host.app_state().settings.lock().map_err(|e| e.to_string())?.storage.clone();
+    resolve_with(host, &settings)
+}
+
+/// Resolve the paths for `settings`. `--data-dir` wins over the data directory setting.
+pub fn resolve_with(host: &impl SidecarHost, settings: &StorageSettings) -> Result<StoragePaths, String> {
+    let data_dir = match (&host.app_state().launch.data_dir, &settings.data_dir) {
+        (None, Some(dir)) => dir.clone(),
+        _ => host.data_dir()?,
+    };
+    let data_dir = crate::normalize_windows_path(data_dir);
+    let dir_or_default = |dir: &Option<PathBuf>, name: &str| {
+        dir.clone()
+            .map(crate::normalize_windows_path)
+            .unwrap_or_else(|| data_dir.join(name))
+    };
+
+    Ok(StoragePaths {
+        database: data_dir.join(DATABASE_FILE),
+        repositories_dir: dir_or_default(&settings.repositories_dir, "repositories"),
+        volumes_dir: dir_or_default(&settings.volumes_dir, "volumes"),
+        cache_dir: dir_or_default(&settings.cache_dir, "cache"),
+        password_file: settings
+            .password_dir
+            .clone()
+            .map(crate::normalize_windows_path)
+            .unwrap_or_else(|| data_dir.clone())
+            .join(PASSWORD_FILE),
+        data_dir,
+    })
+}
+
+/// Create `dir` if needed and check that files can be written in it
+pub fn ensure_writable(dir: &Path) -> Result<(), String> {
+    if !dir.is_absolute() {
+        return Err(format!("{:?} is not an absolute path", dir));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+
+    let probe = dir.join(WRITE_PROBE_FILE);
+    std::fs::write(&probe, b"").map_err(|e| format!("{:?} is not writable: {}", dir, e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Check, before the sidecar is stopped, that moving from `from` to `to` can work
+pub fn check_migration(from: &StoragePaths, to: &StoragePaths) -> Result<(), String> {
+    for (source, target) in from.moves(to) {
+        if target.starts_with(&source) {
+            return Err(format!("{:?} is inside {:?}, which is being moved", target, source));
+        }
+        if target.is_file() || (target.is_dir() && !is_empty_dir(&target)) {
+            return Err(format!(
+                "{:?} already exists; choose an empty directory or move the data yourself",
+                target
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Move the data from `from` to `to`, putting everything back if one move fails.
+/// The sidecar must be stopped, which unmounts the volumes.
+pub fn migrate(from: &StoragePaths, to: &StoragePaths) -> Result<(), String> {
+    let moves = from.moves(to);
+
+    // Copying a volume that is still mounted would copy the remote share and then delete its contents
+    if moves.iter().any(|(source, _)| *source == from.volumes_dir) {
+        if let Some(mountpoint) = find_mountpoint(&from.volumes_dir).map_err(|e| e.to_string())? {
+            return Err(format!(
+                "{:?} is still mounted; unmount it before moving the volumes directory",
+                mountpoint
+            ));
+        }
+    }
+
+    let mut done: Vec<&(PathBuf, PathBuf)> = Vec::new();
+
+    for entry in &moves {
+        let (source, target) = entry;
+        log::info!("Moving {:?} to {:?}", source, target);
+
+        if let Err(e) = move_path(source, target) {
+            for (source, target) in done.into_iter().rev() {
+                if let Err(e) = move_path(target, source) {
+                    log::error!("Failed to move {:?} back to {:?}: {}", target, source, e);
+                }
+            }
+            return Err(format!("Failed to move {:?} to {:?}: {}", source, target, e));
+        }
+        done.push(entry);
+    }
+
+    Ok(())
+}
+
+fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    // Directories created empty by an earlier start would make the rename fail
+    if to.is_dir() && is_empty_dir(to) {
+        std::fs::remove_dir(to).map_err(|e| e.to_string())?;
+    }
+
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    // Renaming fails across file systems, which is the point of moving to another disk
+    copy_and_remove(from, to)
+}
+
+fn copy_and_remove(from: &Path, to: &Path) -> Result<(), String> {
+    if let Err(e) = copy_recursive(from, to) {
+        let _ = remove_path(to);
+        return Err(e.to_string());
+    }
+    remove_path(from).map_err(|e| e.to_string())
+}
+
+/// Copy `from` to `to` without following symbolic links, which are copied as links.
+/// Fails on a mountpoint rather than copying another file system that `remove_path` would then empty.
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        return copy_symlink(from, to);
+    }
+    if !metadata.is_dir() {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if is_mountpoint(&path, &metadata)? {
+            return Err(std::io::Error::other(format!("{:?} is a mountpoint", path)));
+        }
+        copy_recursive(&path, &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(from)?;
+    if std::fs::metadata(from).map(|m| m.is_dir()).unwrap_or(false) {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+/// First directory below `dir` that another file system is mounted on, without following symbolic links
+fn find_mountpoint(dir: &Path) -> std::io::Result<Option<PathBuf>> {
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() {
+        return Ok(None);
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_mountpoint(&path, &metadata)? {
+            return Ok(Some(path));
+        }
+        if let Some(found) = find_mountpoint(&path)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `path` is on another device than its parent directory, whose metadata is `parent`
+#[cfg(unix)]
+fn is_mountpoint(path: &Path, parent: &std::fs::Metadata) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::symlink_metadata(path)?;
+    Ok(metadata.is_dir() && metadata.dev() != parent.dev())
+}
+
+// The backend only mounts remote volumes on Linux
+#[cfg(windows)]
+fn is_mountpoint(_path: &Path, _parent: &std::fs::Metadata) -> std::io::Result<bool> {
+    Ok(false)
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+fn is_empty_dir(path: &Path) -> bool {
+    std::fs::read_dir(path)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+/// `path` with `suffix` appended to its file name, e.g. the `-wal` file of a database
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths_in(data_dir: &Path) -> StoragePaths {
+        StoragePaths {
+            data_dir: data_dir.to_path_buf(),
+            database: data_dir.join(DATABASE_FILE),
+            repositories_dir: data_dir.join("repositories"),
+            volumes_dir: data_dir.join("volumes"),
+            cache_dir: data_dir.join("cache"),
+            password_file: data_dir.join(PASSWORD_FILE),
+        }
+    }
+
+    #[test]
+    fn migrate_moves_the_database_with_its_wal_and_the_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let from = paths_in(&temp.path().join("old"));
+        let to = paths_in(&temp.path().join("new"));
+        std::fs::create_dir_all(from.repositories_dir.join("repo")).unwrap();
+        std::fs::write(from.repositories_dir.join("repo/config"), b"config").unwrap();
+        std::fs::write(&from.database, b"db").unwrap();
+        std::fs::write(with_suffix(&from.database, "-wal"), b"wal").unwrap();
+
+        check_migration(&from, &to).unwrap();
+        migrate(&from, &to).unwrap();
+
+        assert_eq!(std::fs::read(&to.database).unwrap(), b"db");
+        assert_eq!(std::fs::read(with_suffix(&to.database, "-wal")).unwrap(), b"wal");
+        assert_eq!(std::fs::read(to.repositories_dir.join("repo/config")).unwrap(), b"config");
+        assert!(!from.database.exists());
+        assert!(!from.repositories_dir.exists());
+    }
+
+    #[test]
+    fn check_migration_refuses_a_non_empty_target() {
+        let temp = tempfile::tempdir().unwrap();
+        let from = paths_in(&temp.path().join("old"));
+        let to = paths_in(&temp.path().join("new"));
+        std::fs::create_dir_all(&from.repositories_dir).unwrap();
+        std::fs::create_dir_all(&to.repositories_dir).unwrap();
+        assert!(check_migration(&from, &to).is_ok());
+
+        std::fs::write(to.repositories_dir.join("leftover"), b"").unwrap();
+        assert!(check_migration(&from, &to).unwrap_err().contains("already exists"));
+    }
+
+    #[test]
+    fn check_migration_refuses_a_target_inside_the_source() {
+        let temp = tempfile::tempdir().unwrap();
+        let from = paths_in(temp.path());
+        let mut to = from.clone();
+        to.repositories_dir = from.repositories_dir.join("nested");
+        std::fs::create_dir_all(&from.repositories_dir).unwrap();
+
+        assert!(check_migration(&from, &to).unwrap_err().contains("is being moved"));
+    }
+
+    #[test]
+    fn copy_and_remove_moves_a_tree() {
+        let temp = tempfile::tempdir().unwrap();
+        let from = temp.path().join("from");
+        let to = temp.path().join("to");
+        std::fs::create_dir_all(from.join("a/b")).unwrap();
+        std::fs::create_dir_all(from.join("empty")).unwrap();
+        std::fs::write(from.join("top"), b"top").unwrap();
+        std::fs::write(from.join("a/b/deep"), b"deep").unwrap();
+
+        copy_and_remove(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(std::fs::read(to.join("top")).unwrap(), b"top");
+        assert_eq!(std::fs::read(to.join("a/b/deep")).unwrap(), b"deep");
+        assert!(to.join("empty").is_dir());
+    }
+
+    #[test]
+    fn copy_and_remove_moves_a_single_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let from = temp.path().join(PASSWORD_FILE);
+        let to = temp.path().join("elsewhere");
+        std::fs::write(&from, b"secret").unwrap();
+
+        copy_and_remove(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(std::fs::read(&to).unwrap(), b"secret");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_and_remove_keeps_symlinks_as_links_and_leaves_their_targets_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        let outside = temp.path().join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("data"), b"remote").unwrap();
+        let from = temp.path().join("from");
+        let to = temp.path().join("to");
+        std::fs::create_dir_all(&from).unwrap();
+        std::os::unix::fs::symlink(&outside, from.join("link")).unwrap();
+
+        copy_and_remove(&from, &to).unwrap();
+
+        assert!(std::fs::symlink_metadata(to.join("link")).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_link(to.join("link")).unwrap(), outside);
+        assert_eq!(std::fs::read(outside.join("data")).unwrap(), b"remote");
+    }
+
+    #[test]
+    fn failed_copy_leaves_the_source_in_place() {
+        let temp = tempfile::tempdir().unwrap();
+        let from = temp.path().join("from");
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::write(from.join("file"), b"data").unwrap();
+        let blocker = temp.path().join("blocker");
+        std::fs::write(&blocker, b"").unwrap();
+
+        assert!(copy_and_remove(&from, &blocker.join("to")).is_err());
+        assert_eq!(std::fs::read(from.join("file")).unwrap(), b"data");
+    }
+
+    #[test]
+    fn find_mountpoint_ignores_plain_directories_and_symlinks() {
+        let temp = tempfile::tempdir().unwrap();
+        let volumes = temp.path().join("volumes");
+        std::fs::create_dir_all(volumes.join("local/_data")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/proc", volumes.join("link")).unwrap();
+
+        assert_eq!(find_mountpoint(&volumes).unwrap(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn is_mountpoint_detects_another_file_system() {
+        // /proc is always its own file system on Linux
+        let root = std::fs::symlink_metadata("/").unwrap();
+        assert!(is_mountpoint(Path::new("/proc"), &root).unwrap());
+    }
+}
@@ -0,0 +1,531 @@
+//! System tray: navigation entries plus a live section for running backups,
+//! rebuilt from backend events as they arrive.
+
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuBuilder, MenuItemBuilder, SubmenuBuilder};
+use tauri::tray::{MouseButton, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, Wry};
+
+use crate::backend_client::{BackendClient, BackupSchedule};
+use crate::settings::{ShellSettings, TrayClickAction};
+use crate::{AppState, SidecarState};
+
+pub const TRAY_ID: &str = "main";
+const TRAY_TOOLTIP: &str = "C3i Backup ONE";
+/// Longest file path shown under a running backup; longer ones keep their end
+const MAX_FILE_LABEL_CHARS: usize = 60;
+
+/// Progress of a backup as last reported by `backup:started` / `backup:progress`
+#[derive(Clone, Debug)]
+pub struct RunningBackup {
+    pub volume_name: String,
+    pub repository_name: String,
+    /// Fraction between 0 and 1, as reported by restic
+    pub percent_done: f64,
+    pub seconds_elapsed: f64,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    /// Files restic is reading right now
+    pub current_files: Vec<String>,
+}
+
+impl RunningBackup {
+    pub fn percent(&self) -> u32 {
+        (self.percent_done * 100.0).round().clamp(0.0, 100.0) as u32
+    }
+
+    fn eta_secs(&self) -> Option<u64> {
+        if self.percent_done <= 0.0 || self.seconds_elapsed <= 0.0 {
+            return None;
+        }
+        let remaining = self.seconds_elapsed * (1.0 - self.percent_done) / self.percent_done;
+        Some(remaining.max(0.0).round() as u64)
+    }
+
+    fn label(&self) -> String {
+        let mut label = format!("En curso: {} → {} {}%", self.volume_name, self.repository_name, self.percent());
+        if self.total_bytes > 0 {
+            label.push_str(&format!(
+                " ({} de {})",
+                format_bytes(self.bytes_done),
+                format_bytes(self.total_bytes)
+            ));
+        }
+        label
+    }
+}
+
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    let menu = build_menu(app, &[], &[])?;
+    let left_click = left_click_action(app);
+
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(idle_icon().clone())
+        .tooltip(TRAY_TOOLTIP)
+        .menu(&menu)
+        .show_menu_on_left_click(left_click == TrayClickAction::ShowMenu)
+        .on_menu_event(move |app, event| match event.id().as_ref() {
+            "show" => show_main_window(app),
+            "volumes" => navigate(app, "/volumes"),
+            "repositories" => navigate(app, "/repositories"),
+            "backups" => navigate(app, "/backups"),
+            "notifications" => navigate(app, "/notifications"),
+            "settings" => navigate(app, "/settings"),
+            "open_logs" => {
+                let _ = crate::open_logs_dir(app.clone());
+            }
+            "quit" => crate::quit_app(app),
+            id => {
+                if let Some(schedule_id) = id.strip_prefix("running:") {
+                    navigate(app, &format!("/backups/{}", schedule_id));
+                } else if let Some(schedule_id) = id.strip_prefix("schedule:open:") {
+                    navigate(app, &format!("/backups/{}", schedule_id));
+                } else if let Some(schedule_id) = id.strip_prefix("schedule:run:") {
+                    if let Ok(schedule_id) = schedule_id.parse() {
+                        schedule_action(app, schedule_id, ScheduleAction::Run);
+                    }
+                } else if let Some(schedule_id) = id.strip_prefix("schedule:stop:") {
+                    if let Ok(schedule_id) = schedule_id.parse() {
+                        schedule_action(app, schedule_id, ScheduleAction::Stop);
+                    }
+                }
+            }
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                ..
+            } = event
+            {
+                if left_click_action(tray.app_handle()) == TrayClickAction::ShowWindow {
+                    show_main_window(tray.app_handle());
+                }
+            }
+        })
+        .build(app)?;
+
+    Ok(())
+}
+
+/// Apply changed tray settings to the existing icon
+pub fn apply_settings(app: &AppHandle, settings: &ShellSettings) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_show_menu_on_left_click(settings.tray.left_click == TrayClickAction::ShowMenu);
+    }
+}
+
+fn left_click_action(app: &AppHandle) -> TrayClickAction {
+    app.state::<AppState>()
+        .settings
+        .lock()
+        .map(|s| s.tray.left_click)
+        .unwrap_or_default()
+}
+
+/// Rebuild the menu, tooltip and icon from the current backend state and running backups
+pub fn refresh(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+
+    let state = app.state::<AppState>();
+    let running: Vec<(i64, RunningBackup)> = state
+        .running_backups
+        .lock()
+        .map(|r| r.iter().map(|(id, b)| (*id, b.clone())).collect())
+        .unwrap_or_default();
+    let schedules = state
+        .backup_schedules
+        .lock()
+        .map(|s| s.clone())
+        .unwrap_or_default();
+    let sidecar_state = crate::backend_status(&state).state;
+
+    match build_menu(app, &running, &schedules) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => log::warn!("Failed to rebuild tray menu: {}", e),
+    }
+
+    let _ = tray.set_tooltip(Some(tooltip(&sidecar_state, &running)));
+
+    let icon = if running.is_empty() { idle_icon() } else { busy_icon() };
+    let _ = tray.set_icon(Some(icon.clone()));
+}
+
+/// Fetch the schedule list from the backend and rebuild the tray with it
+pub async fn reload_schedules(app: &AppHandle) {
+    let schedules = match BackendClient::from_app(app) {
+        Ok(client) => client.list_backup_schedules().await,
+        Err(e) => Err(e),
+    };
+
+    match schedules {
+        Ok(schedules) => {
+            let state = app.state::<AppState>();
+            if let Ok(mut cached) = state.backup_schedules.lock() {
+                *cached = schedules;
+            }
+            refresh(app);
+        }
+        Err(e) => log::warn!("Failed to load backup schedules for the tray: {}", e),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScheduleAction {
+    Run,
+    Stop,
+}
+
+/// Run or stop a schedule straight from the tray (or a link), without opening the window
+pub fn schedule_action(app: &AppHandle, schedule_id: i64, action: ScheduleAction) {
+    let app = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let result = match BackendClient::from_app(&app) {
+            Ok(client) => match action {
+                ScheduleAction::Run => client.run_backup(schedule_id).await,
+                ScheduleAction::Stop => client.stop_backup(schedule_id).await,
+            },
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            let title = match action {
+                ScheduleAction::Run => "✗ No se pudo iniciar la copia de seguridad",
+                ScheduleAction::Stop => "✗ No se pudo detener la copia de seguridad",
+            };
+            log::error!("{} (schedule {}): {}", title, schedule_id, e);
+            let _ = crate::show_notification(&app, title, &e.to_string());
+        }
+        reload_schedules(&app).await;
+    });
+}
+
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Bring the main window up on the given frontend route
+pub fn navigate(app: &AppHandle, route: &str) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        let route = serde_json::to_string(route).unwrap_or_else(|_| "'/'".to_string());
+        let _ = window.eval(&format!("window.location.href = {}", route));
+    }
+}
+
+fn build_menu(
+    app: &AppHandle,
+    running: &[(i64, RunningBackup)],
+    schedules: &[BackupSchedule],
+) -> tauri::Result<Menu<Wry>> {
+    // Ordered same as sidebar
+    let show = MenuItemBuilder::with_id("show", "Mostrar App").build(app)?;
+    let volumes = MenuItemBuilder::with_id("volumes", "Volúmenes").build(app)?;
+    let repositories = MenuItemBuilder::with_id("repositories", "Repositorios").build(app)?;
+    let all_backups = MenuItemBuilder::with_id("backups", "Ver todas").build(app)?;
+    let notifications = MenuItemBuilder::with_id("notifications", "Notificaciones").build(app)?;
+    let settings = MenuItemBuilder::with_id("settings", "Configuración").build(app)?;
+    let open_logs = MenuItemBuilder::with_id("open_logs", "Abrir logs").build(app)?;
+    let quit = MenuItemBuilder::with_id("quit", "Salir").build(app)?;
+
+    let mut menu = MenuBuilder::new(app).item(&show).separator();
+
+    if !running.is_empty() {
+        for (schedule_id, backup) in running {
+            let item = MenuItemBuilder::with_id(format!("running:{}", schedule_id), backup.label()).build(app)?;
+            menu = menu.item(&item);
+            for (index, file) in backup.current_files.iter().enumerate() {
+                let item = MenuItemBuilder::with_id(format!("running_file:{}:{}", schedule_id, index), file_label(file))
+                    .enabled(false)
+                    .build(app)?;
+                menu = menu.item(&item);
+            }
+        }
+        menu = menu.separator();
+    }
+
+    let mut backups = SubmenuBuilder::with_id(app, "backups_menu", "Copias de seguridad").item(&all_backups);
+    if !schedules.is_empty() {
+        backups = backups.separator();
+    }
+    for schedule in schedules {
+        let is_running = schedule.last_backup_status.as_deref() == Some("in_progress")
+            || running.iter().any(|(id, _)| *id == schedule.id);
+
+        let run = MenuItemBuilder::with_id(format!("schedule:run:{}", schedule.id), "Ejecutar ahora")
+            .enabled(!is_running)
+            .build(app)?;
+        let stop = MenuItemBuilder::with_id(format!("schedule:stop:{}", schedule.id), "Detener")
+            .enabled(is_running)
+            .build(app)?;
+        let open = MenuItemBuilder::with_id(format!("schedule:open:{}", schedule.id), "Abrir").build(app)?;
+
+        let submenu = SubmenuBuilder::with_id(app, format!("schedule:{}", schedule.id), schedule_label(schedule))
+            .item(&run)
+            .item(&stop)
+            .item(&open)
+            .build()?;
+        backups = backups.item(&submenu);
+    }
+    let backups = backups.build()?;
+
+    menu.item(&volumes)
+        .item(&repositories)
+        .item(&backups)
+        .item(&notifications)
+        .separator()
+        .item(&settings)
+        .item(&open_logs)
+        .separator()
+        .item(&quit)
+        .build()
+}
+
+fn schedule_label(schedule: &BackupSchedule) -> String {
+    let status = match schedule.last_backup_status.as_deref() {
+        Some("success") => "✓",
+        Some("error") => "✗",
+        Some("warning") => "⚠",
+        Some("in_progress") => "⏳",
+        _ => "·",
+    };
+
+    match schedule.last_backup_at {
+        Some(at) => format!("{} {} ({})", status, schedule.name, format_ago(at)),
+        None => format!("{} {} (sin ejecutar)", status, schedule.name),
+    }
+}
+
+fn format_ago(timestamp_ms: u64) -> String {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let secs = now_ms.saturating_sub(timestamp_ms) / 1000;
+
+    match secs {
+        0..=59 => "hace un momento".to_string(),
+        60..=3599 => format!("hace {} min", secs / 60),
+        3600..=86399 => format!("hace {} h", secs / 3600),
+        _ => format!("hace {} d", secs / 86400),
+    }
+}
+
+fn tooltip(sidecar_state: &SidecarState, running: &[(i64, RunningBackup)]) -> String {
+    let status = match sidecar_state {
+        SidecarState::Starting => Some("Iniciando servicio..."),
+        SidecarState::Healthy => None,
+        SidecarState::Degraded => Some("El servicio no responde"),
+        SidecarState::Crashed { .. } => Some("El servicio se ha detenido"),
+        SidecarState::Stopping | SidecarState::Stopped => Some("Servicio detenido"),
+    };
+
+    let mut lines = vec![match status {
+        Some(status) => format!("{} - {}", TRAY_TOOLTIP, status),
+        None => TRAY_TOOLTIP.to_string(),
+    }];
+
+    for (_, backup) in running {
+        let eta = backup
+            .eta_secs()
+            .map(|secs| format!(" (quedan {})", format_eta(secs)))
+            .unwrap_or_default();
+        lines.push(format!(
+            "{} → {}: {}%{}",
+            backup.volume_name,
+            backup.repository_name,
+            backup.percent(),
+            eta
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn format_eta(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("~{} s", secs),
+        60..=3599 => format!("~{} min", secs.div_ceil(60)),
+        _ => format!("~{} h {} min", secs / 3600, (secs % 3600) / 60),
+    }
+}
+
+/// Sizes in SI units, as the web UI shows them
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 || value >= 10.0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit]).replace('.', ",")
+    }
+}
+
+/// A file being read, shortened from the start so its name stays visible
+fn file_label(path: &str) -> String {
+    let chars = path.chars().count();
+    if chars <= MAX_FILE_LABEL_CHARS {
+        return format!("  {}", path);
+    }
+    let tail: String = path.chars().skip(chars - (MAX_FILE_LABEL_CHARS - 1)).collect();
+    format!("  …{}", tail)
+}
+
+fn idle_icon() -> &'static Image<'static> {
+    static ICON: OnceLock<Image<'static>> = OnceLock::new();
+    // Custom C3i Backup ONE logo, embedded at build time
+    ICON.get_or_init(|| {
+        Image::from_bytes(include_bytes!("../../public/images/logo.png"))
+            .expect("Failed to load tray icon")
+    })
+}
+
+/// The logo with an activity dot in the bottom-right corner, shown while backups run
+fn busy_icon() -> &'static Image<'static> {
+    static ICON: OnceLock<Image<'static>> = OnceLock::new();
+    ICON.get_or_init(|| {
+        let base = idle_icon();
+        let (width, height) = (base.width(), base.height());
+        let mut rgba = base.rgba().to_vec();
+
+        let radius = (width.min(height) / 5) as i64;
+        let (cx, cy) = (width as i64 - radius - 1, height as i64 - radius - 1);
+        for y in (cy - radius).max(0)..(cy + radius + 1).min(height as i64) {
+            for x in (cx - radius).max(0)..(cx + radius + 1).min(width as i64) {
+                let distance = (x - cx).pow(2) + (y - cy).pow(2);
+                if distance > radius.pow(2) {
+                    continue;
+                }
+                // Dark ring around a green dot so it stays visible on light and dark panels
+                let color = if distance > (radius - radius / 4).pow(2) {
+                    [0x0f, 0x17, 0x2a, 0xff]
+                } else {
+                    [0x22, 0xc5, 0x5e, 0xff]
+                };
+                let offset = ((y * width as i64 + x) * 4) as usize;
+                rgba[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+
+        Image::new_owned(rgba, width, height)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running(percent_done: f64, seconds_elapsed: f64) -> RunningBackup {
+        RunningBackup {
+            volume_name: "docs".to_string(),
+            repository_name: "nas".to_string(),
+            percent_done,
+            seconds_elapsed,
+            bytes_done: 0,
+            total_bytes: 0,
+            current_files: Vec::new(),
+        }
+    }
+
+    fn now_ms() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+    }
+
+    #[test]
+    fn percent_is_rounded_and_clamped() {
+        assert_eq!(running(0.426, 1.0).percent(), 43);
+        assert_eq!(running(-0.1, 1.0).percent(), 0);
+        assert_eq!(running(1.2, 1.0).percent(), 100);
+    }
+
+    #[test]
+    fn eta_follows_the_rate_so_far() {
+        assert_eq!(running(0.25, 60.0).eta_secs(), Some(180));
+        assert_eq!(running(1.0, 60.0).eta_secs(), Some(0));
+        // Nothing to extrapolate from yet
+        assert_eq!(running(0.0, 60.0).eta_secs(), None);
+        assert_eq!(running(0.5, 0.0).eta_secs(), None);
+    }
+
+    #[test]
+    fn eta_is_formatted_in_the_largest_unit() {
+        assert_eq!(format_eta(45), "~45 s");
+        assert_eq!(format_eta(61), "~2 min");
+        assert_eq!(format_eta(3600), "~1 h 0 min");
+        assert_eq!(format_eta(3 * 3600 + 25 * 60), "~3 h 25 min");
+    }
+
+    #[test]
+    fn last_run_is_shown_relative_to_now() {
+        let now = now_ms();
+
+        assert_eq!(format_ago(now), "hace un momento");
+        assert_eq!(format_ago(now - 5 * 60 * 1000), "hace 5 min");
+        assert_eq!(format_ago(now - 2 * 3600 * 1000), "hace 2 h");
+        assert_eq!(format_ago(now - 3 * 86400 * 1000), "hace 3 d");
+        // A clock set back shows no negative age
+        assert_eq!(format_ago(now + 60 * 1000), "hace un momento");
+    }
+
+    #[test]
+    fn tooltip_shows_the_backend_state_and_each_running_backup() {
+        assert_eq!(tooltip(&SidecarState::Healthy, &[]), TRAY_TOOLTIP);
+        assert_eq!(
+            tooltip(&SidecarState::Degraded, &[]),
+            format!("{} - El servicio no responde", TRAY_TOOLTIP)
+        );
+
+        let backups = [(1, running(0.5, 30.0)), (2, running(0.0, 0.0))];
+        assert_eq!(
+            tooltip(&SidecarState::Healthy, &backups),
+            format!("{}\ndocs → nas: 50% (quedan ~30 s)\ndocs → nas: 0%", TRAY_TOOLTIP)
+        );
+    }
+
+    #[test]
+    fn running_label_shows_the_bytes_once_known() {
+        let mut backup = running(0.42, 10.0);
+        assert_eq!(backup.label(), "En curso: docs → nas 42%");
+
+        backup.bytes_done = 1_250_000_000;
+        backup.total_bytes = 3_400_000_000;
+        assert_eq!(backup.label(), "En curso: docs → nas 42% (1,2 GB de 3,4 GB)");
+    }
+
+    #[test]
+    fn bytes_are_formatted_in_si_units() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(1_500), "1,5 KB");
+        assert_eq!(format_bytes(42_000_000), "42 MB");
+        assert_eq!(format_bytes(7_000_000_000_000_000), "7000 TB");
+    }
+
+    #[test]
+    fn long_file_paths_keep_their_end() {
+        assert_eq!(file_label("/data/docs/report.pdf"), "  /data/docs/report.pdf");
+
+        let long = format!("/data/{}/report.pdf", "ñ".repeat(80));
+        let label = file_label(&long);
+        assert_eq!(label.chars().count(), MAX_FILE_LABEL_CHARS + 2);
+        assert!(label.starts_with("  …"));
+        assert!(label.ends_with("ñ/report.pdf"));
+    }
+}
@@ -0,0 +1,185 @@
+//! Upgrades of the app, detected by comparing this version with the last one whose
+//! backend started. A new backend migrates the database on its first start with no
+//! way back, so the database is snapshotted before that start; if the new version
+//! then fails to start, the database can be rolled back to the snapshot.
+
+use crate::settings::{self, ShellSettings, UpgradeHistory, UpgradeOutcome, UpgradeRecord};
+use crate::snapshots::{self, SnapshotReason};
+use crate::{storage, SidecarHost};
+
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Upgrade records kept in the settings, oldest dropped first
+const MAX_HISTORY: usize = 20;
+
+/// Snapshot the database before the first start of a new version. Called before
+/// every start; only the first one after a version change does anything. Fails when
+/// the snapshot cannot be taken, so the new version never migrates without one.
+pub fn prepare(host: &impl SidecarHost) -> Result<(), String> {
+    let upgrades = host.app_state().settings.lock().map_err(|e| e.to_string())?.upgrades.clone();
+    if upgrades.last_run_version.as_deref() == Some(CURRENT_VERSION) {
+        return Ok(());
+    }
+    // An earlier start of this version took the snapshot, and may have migrated
+    // part of the database since
+    if pending(&upgrades).is_some_and(|record| record.to == CURRENT_VERSION) {
+        return Ok(());
+    }
+    // A fresh install has nothing to migrate
+    if !storage::resolve(host)?.database.exists() {
+        return Ok(());
+    }
+
+    let from = upgrades.last_run_version;
+    log::info!(
+        "Version changed from {} to {}, snapshotting the database before it is migrated",
+        from.as_deref().unwrap_or("an earlier version"),
+        CURRENT_VERSION
+    );
+    let previous_version = from.as_deref().unwrap_or("unknown");
+    let snapshot = snapshots::take_as(host, SnapshotReason::BeforeUpgrade, previous_version).map_err(|e| {
+        format!(
+            "The database could not be snapshotted before upgrading to {}, so the new version was not started: {}",
+            CURRENT_VERSION, e
+        )
+    })?;
+    // The database went away since it was checked, so there is nothing to migrate
+    let Some(snapshot) = snapshot else {
+        return Ok(());
+    };
+
+    update(host, |upgrades| {
+        upgrades.history.push(UpgradeRecord {
+            from,
+            to: CURRENT_VERSION.to_string(),
+            started_at: crate::unix_now(),
+            finished_at: None,
+            snapshot: Some(snapshot.id),
+            outcome: UpgradeOutcome::Pending,
+        })
+    })
+}
+
+/// Record that the backend of this version started, which completes a pending upgrade
+pub fn confirm(host: &impl SidecarHost) {
+    let last_run = host
+        .app_state()
+        .settings
+        .lock()
+        .map(|s| s.upgrades.last_run_version.clone())
+        .unwrap_or_default();
+    if last_run.as_deref() == Some(CURRENT_VERSION) {
+        return;
+    }
+
+    let result = update(host, |upgrades| {
+        upgrades.last_run_version = Some(CURRENT_VERSION.to_string());
+        if let Some(record) = pending_mut(upgrades).filter(|record| record.to == CURRENT_VERSION) {
+            record.outcome = UpgradeOutcome::Succeeded;
+            record.finished_at = Some(crate::unix_now());
+            log::info!("Upgrade to {} completed", CURRENT_VERSION);
+        }
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to record the version that started: {}", e);
+    }
+}
+
+/// Roll the database back to the snapshot taken before the pending upgrade. The
+/// sidecar must be stopped. Returns the record and the settings to apply, in which
+/// the previous version is the last one run again, so the next start retries the
+/// upgrade from the restored database.
+pub fn rollback(host: &impl SidecarHost) -> Result<(UpgradeRecord, ShellSettings), String> {
+    let upgrades = host.app_state().settings.lock().map_err(|e| e.to_string())?.upgrades.clone();
+    let record = pending(&upgrades).ok_or("There is no upgrade in progress to roll back")?;
+    let snapshot = record
+        .snapshot
+        .clone()
+        .ok_or("No snapshot of the database could be taken before this upgrade")?;
+
+    let rolled_back = snapshots::rollback(host, &snapshot)?;
+    let mut settings = match rolled_back.settings {
+        Some(settings) => settings,
+        None => host.app_state().settings.lock().map_err(|e| e.to_string())?.clone(),
+    };
+
+    settings.upgrades.last_run_version = record.from.clone();
+    let record = pending_mut(&mut settings.upgrades).ok_or("The upgrade history changed meanwhile")?;
+    record.outcome = UpgradeOutcome::RolledBack;
+    record.finished_at = Some(crate::unix_now());
+    let record = record.clone();
+
+    log::info!("Rolled back the upgrade to {} using snapshot {}", record.to, snapshot);
+    Ok((record, settings))
+}
+
+/// The upgrade whose new version has not started yet, if any
+fn pending(upgrades: &UpgradeHistory) -> Option<&UpgradeRecord> {
+    upgrades.history.last().filter(|record| record.outcome == UpgradeOutcome::Pending)
+}
+
+fn pending_mut(upgrades: &mut UpgradeHistory) -> Option<&mut UpgradeRecord> {
+    upgrades.history.last_mut().filter(|record| record.outcome == UpgradeOutcome::Pending)
+}
+
+/// Change the upgrade history and save the settings
+fn update(host: &impl SidecarHost, change: impl FnOnce(&mut UpgradeHistory)) -> Result<(), String> {
+    let settings = {
+        let mut settings = host.app_state().settings.lock().map_err(|e| e.to_string())?;
+        change(&mut settings.upgrades);
+        trim_history(&mut settings.upgrades.history);
+        settings.clone()
+    };
+    settings::save_to(&host.config_dir()?, &settings)
+}
+
+/// Drop the oldest records beyond `MAX_HISTORY`
+fn trim_history(history: &mut Vec<UpgradeRecord>) {
+    if history.len() > MAX_HISTORY {
+        history.drain(..history.len() - MAX_HISTORY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(to: &str, outcome: UpgradeOutcome) -> UpgradeRecord {
+        UpgradeRecord {
+            from: None,
+            to: to.to_string(),
+            started_at: 0,
+            finished_at: None,
+            snapshot: None,
+            outcome,
+        }
+    }
+
+    #[test]
+    fn only_the_last_record_can_be_pending() {
+        let mut upgrades = UpgradeHistory::default();
+        assert!(pending(&upgrades).is_none());
+
+        upgrades.history.push(record("1.1.0", UpgradeOutcome::Pending));
+        upgrades.history.push(record("1.2.0", UpgradeOutcome::Succeeded));
+        assert!(pending(&upgrades).is_none());
+
+        upgrades.history.push(record("1.3.0", UpgradeOutcome::Pending));
+        assert_eq!(pending(&upgrades).map(|r| r.to.as_str()), Some("1.3.0"));
+
+        pending_mut(&mut upgrades).unwrap().outcome = UpgradeOutcome::RolledBack;
+        assert!(pending(&upgrades).is_none());
+    }
+
+    #[test]
+    fn history_keeps_the_newest_records() {
+        let mut history: Vec<_> = (0..MAX_HISTORY + 3)
+            .map(|i| record(&format!("1.{}.0", i), UpgradeOutcome::Succeeded))
+            .collect();
+
+        trim_history(&mut history);
+
+        assert_eq!(history.len(), MAX_HISTORY);
+        assert_eq!(history[0].to, "1.3.0");
+        assert_eq!(history[MAX_HISTORY - 1].to, format!("1.{}.0", MAX_HISTORY + 2));
+    }
+}